@@ -83,13 +83,13 @@ pub fn generate_to_bytes(s: &DataStruct, name: &Ident) -> TokenStream {
     }
 }
 
-pub fn generate_from_buf(s: &DataStruct, name: &Ident) -> TokenStream {
+pub fn generate_try_from_buf(s: &DataStruct, name: &Ident) -> TokenStream {
     let raw_fields: Vec<_> = get_field_tuples(s).collect();
     let decls = raw_fields.iter().map(|(ident, size)| match size {
-        BitSize::Data(n) => quote! { #ident: buf.get_un(#n) },
-        BitSize::Zero(n) => quote! { #ident: { let _ = buf.get_un::<u32>(#n); PhantomData } },
+        BitSize::Data(n) => quote! { #ident: buf.try_get_un(#n)? },
+        BitSize::Zero(n) => quote! { #ident: { let _ = buf.try_get_un::<u32>(#n)?; PhantomData } },
         BitSize::Vec(..) => quote! { #ident: vec![] },
-        BitSize::Ipv4 => quote! { #ident: ::std::net::Ipv4Addr::from_buf(buf.get_buf()) },
+        BitSize::Ipv4 => quote! { #ident: ::std::net::Ipv4Addr::try_from_buf(buf.get_buf())? },
     });
     let assigns = raw_fields.iter().map(|(ident, size)| {
         if let BitSize::Vec(ty, size) = size {
@@ -99,7 +99,7 @@ pub fn generate_from_buf(s: &DataStruct, name: &Ident) -> TokenStream {
                     let mut vec = vec![];
                     let buf = buf.get_buf();
                     while buf.has_remaining() {
-                        vec.push(#ty::from_buf(buf));
+                        vec.push(#ty::try_from_buf(buf)?);
                     }
                     vec
                 }}}
@@ -109,7 +109,7 @@ pub fn generate_from_buf(s: &DataStruct, name: &Ident) -> TokenStream {
                     let mut vec = vec![];
                     let buf = buf.get_buf();
                     for _ in 0..s.#sz {
-                        vec.push(#ty::from_buf(buf));
+                        vec.push(#ty::try_from_buf(buf)?);
                     }
                     vec
                 }}}
@@ -119,12 +119,12 @@ pub fn generate_from_buf(s: &DataStruct, name: &Ident) -> TokenStream {
         }
     });
     quote! {
-        impl FromBuf for #name {
-            fn from_buf(buf: &mut impl ::bytes::Buf) -> Self {
+        impl TryFromBuf for #name {
+            fn try_from_buf(buf: &mut impl ::bytes::Buf) -> Result<Self, DecodeError> {
                 let mut buf = Bits::from(buf);
                 let mut s = Self { #(#decls,)* };
                 #(#assigns)*
-                s
+                Ok(s)
             }
         }
     }
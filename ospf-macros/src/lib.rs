@@ -16,12 +16,12 @@ pub fn derive_to_bytes(input: TokenStream) -> TokenStream {
     s.into()
 }
 
-#[proc_macro_derive(FromBuf, attributes(size))]
-pub fn derive_from_bytes(input: TokenStream) -> TokenStream {
+#[proc_macro_derive(TryFromBuf, attributes(size))]
+pub fn derive_try_from_bytes(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let name = &ast.ident;
     let s = match &ast.data {
-        syn::Data::Struct(ref s) => decorator::generate_from_buf(s, name),
+        syn::Data::Struct(ref s) => decorator::generate_try_from_buf(s, name),
         _ => panic!("Only structs are supported"),
     };
     s.into()
@@ -33,7 +33,7 @@ pub fn raw_packet(_attrs: TokenStream, code: TokenStream) -> TokenStream {
     let ast = input.clone();
     // enhancement: if input already has Clone and/or Debug, do not add them
     let derived = quote! {
-        #[derive(::ospf_macros::ToBytesMut, ::ospf_macros::FromBuf, Clone, Debug)]
+        #[derive(::ospf_macros::ToBytesMut, ::ospf_macros::TryFromBuf, Clone, Debug)]
         #input
     };
     let name = &ast.ident;
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ospf_packet::{packet::DBDescription, ToBytes, TryFromBuf};
+
+// 任意输入都不能 panic；能解码的输入重新编码后必须能解码出相同的字节
+fuzz_target!(|data: &[u8]| {
+    if let Ok(value) = DBDescription::try_from_slice(data) {
+        let bytes = value.to_bytes();
+        let decoded = DBDescription::try_from_slice(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ospf_packet::{packet::Message, Ospf, OspfPacket};
+
+// 模拟接收路径：先解析 OSPF 报头，再用守护进程的解码函数按长度和类型解码报文体及 LLS 数据块
+fuzz_target!(|data: &[u8]| {
+    let Some(packet) = OspfPacket::new(data) else {
+        return;
+    };
+    let _ = packet.auto_test_checksum();
+    let _ = Message::decode(&Ospf::from(packet));
+});
@@ -16,10 +16,66 @@ impl<T: ToBytesMut> ToBytes for T {
     }
 }
 
+/// 报文解码失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    #[error("Truncated: {need} bytes needed but {remaining} remaining")]
+    Truncated { need: usize, remaining: usize },
+    #[error("Bad length field: {0}")]
+    BadLength(usize),
+    #[error("Unknown type: {0}")]
+    UnknownType(u8),
+    #[error("Trailing bytes: {0}")]
+    TrailingBytes(usize),
+    #[error("Bad checksum")]
+    BadChecksum,
+}
+
+/// 可失败的解码，报文来自网络时必须使用这个接口
+pub trait TryFromBuf: Sized {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError>;
+
+    /// 从完整的字节串解码，不允许有多余的字节
+    fn try_from_slice(mut buf: &[u8]) -> Result<Self, DecodeError> {
+        let value = Self::try_from_buf(&mut buf)?;
+        match buf.remaining() {
+            0 => Ok(value),
+            n => Err(DecodeError::TrailingBytes(n)),
+        }
+    }
+}
+
+/// 不可失败的解码，输入不合法时 panic，只用于可信的数据
 pub trait FromBuf {
     fn from_buf(buf: &mut impl Buf) -> Self;
 }
 
+impl<T: TryFromBuf> FromBuf for T {
+    fn from_buf(buf: &mut impl Buf) -> Self {
+        Self::try_from_buf(buf).expect("bad packet")
+    }
+}
+
+/// 检查缓冲区中是否还有 need 个字节
+pub fn check_remaining(buf: &impl Buf, need: usize) -> Result<(), DecodeError> {
+    let remaining = buf.remaining();
+    if remaining < need {
+        Err(DecodeError::Truncated { need, remaining })
+    } else {
+        Ok(())
+    }
+}
+
+/// IP 校验和：按 16 位求反码和再取反，奇数长度时末尾补零。校验时对包含校验和的数据计算，结果为 0 表示正确
+pub fn ip_checksum(data: &[u8]) -> u16 {
+    let sum = data.chunks(2).fold(0u16, |acc, e| {
+        let e = u16::from_be_bytes([e[0], e.get(1).copied().unwrap_or(0)]);
+        let n = acc as u32 + e as u32;
+        n as u16 + (n >> 16) as u16
+    });
+    sum ^ 0xffff
+}
+
 impl<T: ToBytes> ToBytesMut for Vec<T> {
     fn to_bytes_mut(&self) -> BytesMut {
         self.iter().fold(BytesMut::new(), |mut acc, v| {
@@ -45,8 +101,17 @@ impl<T: Buf> Bits<T> {
         }
     }
 
+    /// 读取 n 位前检查剩余长度
+    pub fn try_get_un<N: PrimitiveInteger>(&mut self, n: u32) -> Result<N, DecodeError> {
+        // 当前字节中还没有读取的位
+        let cached = if self.bit == 0 { 0 } else { 8 - self.bit as u32 };
+        let need = n.saturating_sub(cached).div_ceil(8) as usize;
+        check_remaining(&self.buf, need)?;
+        Ok(self.get_un(n))
+    }
+
     pub fn get_un<N: PrimitiveInteger>(&mut self, n: u32) -> N {
-        if n % 8 == 0 {
+        if n.is_multiple_of(8) {
             assert_eq!(self.bit, 0);
             let mut num = 0u128;
             for _ in 0..n / 8 {
@@ -94,7 +159,7 @@ impl BitsMut {
     }
 
     pub fn put_un<N: PrimitiveInteger>(&mut self, val: N, n: u32) {
-        if n % 8 == 0 {
+        if n.is_multiple_of(8) {
             assert_eq!(self.bit, 0);
             let num = val.to_u128();
             let b = n / 8;
@@ -132,13 +197,14 @@ impl ToBytesMut for Ipv4Addr {
     }
 }
 
-impl FromBuf for Ipv4Addr {
-    fn from_buf(buf: &mut impl Buf) -> Self {
+impl TryFromBuf for Ipv4Addr {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        check_remaining(buf, 4)?;
         let a = buf.get_u8();
         let b = buf.get_u8();
         let c = buf.get_u8();
         let d = buf.get_u8();
-        Ipv4Addr::new(a, b, c, d)
+        Ok(Ipv4Addr::new(a, b, c, d))
     }
 }
 
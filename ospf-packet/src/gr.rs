@@ -0,0 +1,100 @@
+//! 平滑重启（RFC 3623）。Grace LSA 是不透明类型为 3、不透明标识为 0 的链路范围不透明 LSA，
+//! 由重启的路由器在重启前后发出，通知邻居在宽限期内继续把它当作完全邻接。
+
+use std::net::Ipv4Addr;
+
+use bytes::{Buf, BytesMut};
+
+use crate::{
+    bits::*,
+    tlv::{self, Tlv},
+};
+
+pub const GRACE_OPAQUE_TYPE: u8 = 3;
+
+pub mod tlv_types {
+    pub const GRACE_PERIOD: u16 = 1;
+    pub const RESTART_REASON: u16 = 2;
+    pub const INTERFACE_ADDRESS: u16 = 3;
+}
+
+pub mod restart_reasons {
+    pub const UNKNOWN: u8 = 0;
+    pub const SOFTWARE_RESTART: u8 = 1;
+    pub const SOFTWARE_UPGRADE: u8 = 2;
+    pub const SWITCH_TO_BACKUP: u8 = 3;
+
+    pub fn to_string(reason: u8) -> &'static str {
+        match reason {
+            SOFTWARE_RESTART => "software restart",
+            SOFTWARE_UPGRADE => "software upgrade",
+            SWITCH_TO_BACKUP => "switch to backup",
+            _ => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraceLsa {
+    /// 宽限期（秒），从 LSA 生成时算起
+    pub grace_period: u32,
+    pub reason: u8,
+    /// 广播和 NBMA 网络上必须带有重启路由器的接口地址
+    pub interface_address: Option<Ipv4Addr>,
+}
+
+impl ToBytesMut for GraceLsa {
+    fn to_bytes_mut(&self) -> BytesMut {
+        let mut tlvs = vec![
+            Tlv::from_u32(tlv_types::GRACE_PERIOD, self.grace_period),
+            Tlv::new(tlv_types::RESTART_REASON, [self.reason]),
+        ];
+        if let Some(addr) = self.interface_address {
+            tlvs.push(Tlv::from_ip(tlv_types::INTERFACE_ADDRESS, addr));
+        }
+        BytesMut::from(tlv::encode_all(&tlvs).as_slice())
+    }
+}
+
+impl TryFromBuf for GraceLsa {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        let mut grace_period = None;
+        let mut lsa = Self {
+            grace_period: 0,
+            reason: restart_reasons::UNKNOWN,
+            interface_address: None,
+        };
+        for tlv in tlv::decode_all(buf)? {
+            match tlv.ty {
+                tlv_types::GRACE_PERIOD => grace_period = Some(tlv.as_u32()?),
+                tlv_types::RESTART_REASON => {
+                    lsa.reason = *tlv.value.first().ok_or(DecodeError::BadLength(0))?
+                }
+                tlv_types::INTERFACE_ADDRESS => lsa.interface_address = Some(tlv.as_ip()?),
+                _ => (),
+            }
+        }
+        // 宽限期是必须的
+        lsa.grace_period = grace_period.ok_or(DecodeError::UnknownType(tlv_types::GRACE_PERIOD as u8))?;
+        Ok(lsa)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let lsa = GraceLsa {
+            grace_period: 120,
+            reason: restart_reasons::SOFTWARE_RESTART,
+            interface_address: Some(Ipv4Addr::new(10, 0, 0, 1)),
+        };
+        let bytes = lsa.to_bytes();
+        // 宽限期 8 字节，原因 4 + 4 字节，接口地址 8 字节
+        assert_eq!(bytes.len(), 8 + 8 + 8);
+        assert_eq!(GraceLsa::try_from_slice(&bytes).unwrap(), lsa);
+        assert!(GraceLsa::try_from_slice(&[0, 2, 0, 1, 1, 0, 0, 0]).is_err());
+    }
+}
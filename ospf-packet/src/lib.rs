@@ -1,9 +1,15 @@
 pub mod bits;
 mod constant;
+pub mod gr;
+pub mod lls;
 pub mod lsa;
 pub mod packet;
+pub mod ri;
+pub mod sr;
+pub mod te;
+pub mod tlv;
 
-pub use bits::{FromBuf, ToBytes, ToBytesMut};
+pub use bits::{DecodeError, FromBuf, ToBytes, ToBytesMut, TryFromBuf};
 pub use packet::message_type_string;
 
 use std::io;
@@ -45,23 +51,17 @@ impl Ospf {
 impl MutableOspfPacket<'_> {
     pub fn auto_set_checksum(&mut self) {
         self.set_checksum(0);
-        let checksum = self.packet().chunks(2).fold(0u16, |acc, e| {
-            let e = u16::from_be_bytes(e.try_into().unwrap());
-            let n = acc as u32 + e as u32;
-            n as u16 + (n >> 16) as u16
-        });
-        self.set_checksum(checksum ^ 0xffff);
+        let checksum = bits::ip_checksum(self.packet());
+        self.set_checksum(checksum);
     }
 }
 
 impl OspfPacket<'_> {
+    /// 校验和只覆盖报头中长度以内的部分，之后可能附带 LLS 数据块
     pub fn auto_test_checksum(&self) -> bool {
-        let checksum = self.packet().chunks(2).fold(0u16, |acc, e| {
-            let e = u16::from_be_bytes(e.try_into().unwrap());
-            let n = acc as u32 + e as u32;
-            n as u16 + (n >> 16) as u16
-        });
-        checksum == 0xffff
+        self.packet()
+            .get(..self.get_length() as usize)
+            .is_some_and(|packet| bits::ip_checksum(packet) == 0)
     }
 }
 
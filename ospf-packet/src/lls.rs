@@ -0,0 +1,107 @@
+//! 链路本地信令（RFC 5613）。Hello 和 DD 包的选项中设置 L 位时，OSPF 报文之后附带 LLS 数据块，
+//! 不计入报头中的长度和校验和。数据块由 2 字节校验和、2 字节长度（以 4 字节为单位，包括这 4 字节）和一组 TLV 组成。
+
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::{
+    bits::*,
+    tlv::{self, Tlv},
+};
+
+pub mod tlv_types {
+    pub const EXTENDED_OPTIONS: u16 = 1;
+    pub const CRYPTO_AUTH: u16 = 2;
+}
+
+/// 扩展选项位
+pub mod extended_options {
+    /// 支持带外 LSDB 重新同步（RFC 4811）
+    pub const LR: u32 = 0x0000_0001;
+    /// 重启信号（RFC 4812）
+    pub const RS: u32 = 0x0000_0002;
+
+    pub fn to_string(options: u32) -> String {
+        let names: Vec<_> = [(LR, "LR"), (RS, "RS")]
+            .iter()
+            .filter(|(bit, _)| options & bit != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        names.join(",")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lls {
+    pub extended_options: Option<u32>,
+    /// 其余未解析的 TLV，原样保留
+    pub others: Vec<Tlv>,
+}
+
+impl ToBytesMut for Lls {
+    fn to_bytes_mut(&self) -> BytesMut {
+        let mut tlvs = vec![];
+        if let Some(options) = self.extended_options {
+            tlvs.push(Tlv::from_u32(tlv_types::EXTENDED_OPTIONS, options));
+        }
+        tlvs.extend(self.others.iter().cloned());
+        let data = tlv::encode_all(&tlvs);
+        let mut buf = BytesMut::new();
+        buf.put_u16(0);
+        buf.put_u16((4 + data.len() as u16) / 4);
+        buf.extend_from_slice(&data);
+        let checksum = ip_checksum(&buf);
+        buf[..2].copy_from_slice(&checksum.to_be_bytes());
+        buf
+    }
+}
+
+impl TryFromBuf for Lls {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        check_remaining(buf, 4)?;
+        let checksum = buf.get_u16();
+        let length = buf.get_u16() as usize * 4;
+        let data_len = length.checked_sub(4).ok_or(DecodeError::BadLength(length))?;
+        check_remaining(buf, data_len)?;
+        let data = buf.copy_to_bytes(data_len);
+        // 校验和为 0 表示发送方没有计算
+        let mut block = BytesMut::new();
+        block.put_u16(checksum);
+        block.put_u16((length / 4) as u16);
+        block.extend_from_slice(&data);
+        if checksum != 0 && ip_checksum(&block) != 0 {
+            return Err(DecodeError::BadChecksum);
+        }
+        let mut lls = Self::default();
+        for tlv in tlv::decode_all(&mut data.as_ref())? {
+            match tlv.ty {
+                tlv_types::EXTENDED_OPTIONS => lls.extended_options = Some(tlv.as_u32()?),
+                _ => lls.others.push(tlv),
+            }
+        }
+        Ok(lls)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let lls = Lls {
+            extended_options: Some(extended_options::LR | extended_options::RS),
+            others: vec![],
+        };
+        let bytes = lls.to_bytes();
+        assert_eq!(&bytes[2..], &[0, 3, 0, 1, 0, 4, 0, 0, 0, 3]);
+        assert_eq!(ip_checksum(&bytes), 0);
+        assert_eq!(Lls::try_from_slice(&bytes).unwrap(), lls);
+        // 校验和错误
+        let mut bad = bytes.to_vec();
+        bad[0] ^= 1;
+        assert_eq!(Lls::try_from_slice(&bad), Err(DecodeError::BadChecksum));
+        // 长度超出数据
+        assert!(Lls::try_from_slice(&[0, 0, 0, 4, 0, 1, 0, 4]).is_err());
+        assert_eq!(extended_options::to_string(3), "LR,RS");
+    }
+}
@@ -12,6 +12,9 @@ pub mod types {
     pub const SUMMARY_IP_LSA: u8 = 3;
     pub const SUMMARY_ASBR_LSA: u8 = 4;
     pub const AS_EXTERNAL_LSA: u8 = 5;
+    pub const OPAQUE_LINK_LSA: u8 = 9;
+    pub const OPAQUE_AREA_LSA: u8 = 10;
+    pub const OPAQUE_AS_LSA: u8 = 11;
 
     pub fn to_string(ls_type: u8) -> &'static str {
         match ls_type {
@@ -20,9 +23,25 @@ pub mod types {
             SUMMARY_IP_LSA => "Sum-Net",
             SUMMARY_ASBR_LSA => "Sum-ASBR",
             AS_EXTERNAL_LSA => "External",
+            OPAQUE_LINK_LSA => "Opq-Link",
+            OPAQUE_AREA_LSA => "Opq-Area",
+            OPAQUE_AS_LSA => "Opq-AS",
             _ => "Unknown",
         }
     }
+
+    pub fn is_known(ls_type: u8) -> bool {
+        matches!(ls_type, 1..=5 | 9..=11)
+    }
+
+    pub fn is_opaque(ls_type: u8) -> bool {
+        matches!(ls_type, OPAQUE_LINK_LSA..=OPAQUE_AS_LSA)
+    }
+
+    /// 在整个自治系统内洪泛的 LSA
+    pub fn is_as_scope(ls_type: u8) -> bool {
+        matches!(ls_type, AS_EXTERNAL_LSA | OPAQUE_AS_LSA)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -94,20 +113,27 @@ impl ToBytesMut for Lsa {
     }
 }
 
-impl FromBuf for Lsa {
-    fn from_buf(buf: &mut impl Buf) -> Self {
-        let header = LsaHeader::from_buf(buf);
-        let mut buf = buf.take(header.length as usize - size_of_val(&header));
+impl TryFromBuf for Lsa {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        let header = LsaHeader::try_from_buf(buf)?;
+        let length = (header.length as usize)
+            .checked_sub(size_of_val(&header))
+            .ok_or(DecodeError::BadLength(header.length as usize))?;
+        check_remaining(buf, length).map_err(|_| DecodeError::BadLength(header.length as usize))?;
+        let mut buf = buf.take(length);
         let data = match header.ls_type {
-            types::ROUTER_LSA => LsaData::Router(RouterLSA::from_buf(&mut buf)),
-            types::NETWORK_LSA => LsaData::Network(NetworkLSA::from_buf(&mut buf)),
-            types::SUMMARY_IP_LSA => LsaData::SummaryIP(SummaryLSA::from_buf(&mut buf)),
-            types::SUMMARY_ASBR_LSA => LsaData::SummaryASBR(SummaryLSA::from_buf(&mut buf)),
-            types::AS_EXTERNAL_LSA => LsaData::ASExternal(AsExternalLSA::from_buf(&mut buf)),
-            _ => panic!("wrong ls type!"),
+            types::ROUTER_LSA => LsaData::Router(RouterLSA::try_from_buf(&mut buf)?),
+            types::NETWORK_LSA => LsaData::Network(NetworkLSA::try_from_buf(&mut buf)?),
+            types::SUMMARY_IP_LSA => LsaData::SummaryIP(SummaryLSA::try_from_buf(&mut buf)?),
+            types::SUMMARY_ASBR_LSA => LsaData::SummaryASBR(SummaryLSA::try_from_buf(&mut buf)?),
+            types::AS_EXTERNAL_LSA => LsaData::ASExternal(AsExternalLSA::try_from_buf(&mut buf)?),
+            ty if types::is_opaque(ty) => LsaData::Opaque(OpaqueLSA::try_from_buf(&mut buf)?),
+            ty => return Err(DecodeError::UnknownType(ty)),
         };
-        assert!(!buf.has_remaining());
-        Self { header, data }
+        match buf.remaining() {
+            0 => Ok(Self { header, data }),
+            n => Err(DecodeError::TrailingBytes(n)),
+        }
     }
 }
 
@@ -153,6 +179,21 @@ impl Ord for LsaHeader {
     }
 }
 
+impl LsaHeader {
+    /// 不透明 LSA 的连接状态标识：高 8 位为不透明类型，低 24 位为不透明标识
+    pub fn opaque_id(opaque_type: u8, opaque_id: u32) -> Ipv4Addr {
+        Ipv4Addr::from((opaque_type as u32) << 24 | (opaque_id & 0x00ff_ffff))
+    }
+
+    pub fn opaque_type(&self) -> u8 {
+        self.link_state_id.octets()[0]
+    }
+
+    pub fn opaque_local_id(&self) -> u32 {
+        u32::from(self.link_state_id) & 0x00ff_ffff
+    }
+}
+
 impl PartialOrd for LsaHeader {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -172,6 +213,7 @@ pub enum LsaData {
     SummaryIP(SummaryLSA),
     SummaryASBR(SummaryLSA),
     ASExternal(AsExternalLSA),
+    Opaque(OpaqueLSA),
 }
 
 impl ToBytesMut for LsaData {
@@ -182,6 +224,7 @@ impl ToBytesMut for LsaData {
             LsaData::SummaryIP(lsa) => lsa.to_bytes_mut(),
             LsaData::SummaryASBR(lsa) => lsa.to_bytes_mut(),
             LsaData::ASExternal(lsa) => lsa.to_bytes_mut(),
+            LsaData::Opaque(lsa) => lsa.to_bytes_mut(),
         }
     }
 }
@@ -252,6 +295,25 @@ pub struct AsExternalLSA {
     pub external_router_tag: u32,
 }
 
+/// 不透明 LSA（RFC 5250）的内容由应用自行解释，这里只保存原始数据
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpaqueLSA {
+    pub data: Vec<u8>,
+}
+
+impl ToBytesMut for OpaqueLSA {
+    fn to_bytes_mut(&self) -> BytesMut {
+        BytesMut::from(self.data.as_slice())
+    }
+}
+
+impl TryFromBuf for OpaqueLSA {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        let data = buf.copy_to_bytes(buf.remaining()).to_vec();
+        Ok(Self { data })
+    }
+}
+
 pub mod link_types {
     pub const P2P_LINK: u8 = 1;
     pub const TRANSIT_LINK: u8 = 2;
@@ -306,7 +368,7 @@ impl TryFrom<(LsaHeader, $T)> for Lsa {
         use LsaData::*;
         match header.ls_type {
             $(types::$id => Ok(Self { header, data: $e(data) }),)+
-            _x @ 1..=5 => Err(ConvertError::TypeMismatched),
+            x if types::is_known(x) => Err(ConvertError::TypeMismatched),
             _ => Err(ConvertError::TypeUnknown),
         }
     }
@@ -319,7 +381,7 @@ impl TryFrom<Lsa> for (LsaHeader, $T) {
         use LsaData::*;
         match lsa.header.ls_type {
             $(types::$id => Ok((lsa.header, unpack!(lsa.data, $e))),)+
-            _x @ 1..=5 => Err(ConvertError::TypeMismatched),
+            x if types::is_known(x) => Err(ConvertError::TypeMismatched),
             _ => Err(ConvertError::TypeUnknown),
         }
     }
@@ -335,6 +397,12 @@ build_convert!(
     (SUMMARY_ASBR_LSA, SummaryASBR)
 );
 build_convert!(AsExternalLSA, (AS_EXTERNAL_LSA, ASExternal));
+build_convert!(
+    OpaqueLSA,
+    (OPAQUE_LINK_LSA, Opaque),
+    (OPAQUE_AREA_LSA, Opaque),
+    (OPAQUE_AS_LSA, Opaque)
+);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LsaIndex {
@@ -377,3 +445,44 @@ impl std::fmt::Display for LsaHeader {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut lsa: Lsa = (
+            LsaHeader {
+                ls_type: types::SUMMARY_IP_LSA,
+                ..Default::default()
+            },
+            SummaryLSA::default(),
+        )
+            .try_into()
+            .unwrap();
+        lsa.update_length();
+        let bytes = lsa.to_bytes();
+        assert!(Lsa::try_from_slice(&bytes).is_ok());
+        assert!(matches!(
+            Lsa::try_from_slice(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::BadLength(28))
+        ));
+        assert!(matches!(
+            LsaHeader::try_from_slice(&bytes[..10]),
+            Err(DecodeError::Truncated { .. })
+        ));
+        let mut trailing = bytes.to_vec();
+        trailing.push(0);
+        assert_eq!(
+            Lsa::try_from_slice(&trailing).unwrap_err(),
+            DecodeError::TrailingBytes(1)
+        );
+        let mut unknown = bytes.to_vec();
+        unknown[3] = 42;
+        assert_eq!(
+            Lsa::try_from_slice(&unknown).unwrap_err(),
+            DecodeError::UnknownType(42)
+        );
+    }
+}
@@ -2,7 +2,9 @@ use std::marker::PhantomData;
 use std::net::Ipv4Addr;
 
 use super::bits::*;
+use super::lls::Lls;
 use super::lsa::*;
+use super::Ospf;
 
 use bytes::Buf;
 use ospf_macros::raw_packet;
@@ -36,8 +38,12 @@ pub mod options {
     pub const NP: u8 = 0b0000_1000;
     #[doc = "该位描述了是否按［引用 20］的说明忽略还是接收并转发 External-Attributes-LSA。"]
     pub const EA: u8 = 0b0001_0000;
+    #[doc = "Hello 和 DD 包中该位（原 EA 位）表示报文之后附带 LLS 数据块，见 RFC 5613。"]
+    pub const L: u8 = 0b0001_0000;
     #[doc = "该位描述了按［引用 21］的说明处理按需链路。"]
     pub const DC: u8 = 0b0010_0000;
+    #[doc = "该位描述是否能够接收和转发不透明 LSA，见 RFC 5250。"]
+    pub const O: u8 = 0b0100_0000;
 
     pub trait OptionExt {
         fn is_set(&self, option: u8) -> bool;
@@ -80,7 +86,9 @@ pub struct HelloPacket {
 pub struct DBDescription {
     pub interface_mtu: u16,
     pub options: u8,
-    pub _zeros: PhantomData<u5>,
+    pub _zeros: PhantomData<u4>,
+    /// 带外重新同步（RFC 4811）
+    pub resync: u1,
     pub init: u1,
     pub more: u1,
     pub master: u1,
@@ -123,18 +131,31 @@ pub trait OspfSubPacket: ToBytes + ToBytesMut + FromBuf + std::fmt::Debug {
     fn get_lsa_and_then(&self, f: impl FnMut(&Lsa)) {
         let _ = f;
     }
+
+    /// 报文之后是否附带 LLS 数据块
+    fn has_lls(&self) -> bool {
+        false
+    }
 }
 
 impl OspfSubPacket for HelloPacket {
     fn get_type(&self) -> u8 {
         types::HELLO_PACKET
     }
+
+    fn has_lls(&self) -> bool {
+        options::OptionExt::is_set(self, options::L)
+    }
 }
 
 impl OspfSubPacket for DBDescription {
     fn get_type(&self) -> u8 {
         types::DB_DESCRIPTION
     }
+
+    fn has_lls(&self) -> bool {
+        options::OptionExt::is_set(self, options::L)
+    }
 }
 
 impl OspfSubPacket for LSRequest {
@@ -158,3 +179,49 @@ impl OspfSubPacket for LSAcknowledge {
         types::LS_ACKNOWLEDGE
     }
 }
+
+/// 解码后的报文体，Hello 和 DD 包带有报文之后的 LLS 数据块（有错误时为解码错误）
+#[derive(Debug)]
+pub enum Message {
+    Hello(HelloPacket, Option<Result<Lls, DecodeError>>),
+    DbDescription(DBDescription, Option<Result<Lls, DecodeError>>),
+    LsRequest(Vec<LSRequest>),
+    LsUpdate(LSUpdate),
+    LsAck(LSAcknowledge),
+}
+
+impl Message {
+    /// 按报头中的长度截取报文体并按类型解码，之后的字节（如以太网填充）不属于报文
+    pub fn decode(packet: &Ospf) -> Result<Self, DecodeError> {
+        let length = packet.length as usize;
+        let body = length
+            .checked_sub(24)
+            .and_then(|n| packet.payload.get(..n))
+            .ok_or(DecodeError::BadLength(length))?;
+        let lls = |has_lls: bool| has_lls.then(|| Lls::try_from_buf(&mut &packet.payload[body.len()..]));
+        Ok(match packet.message_type {
+            types::HELLO_PACKET => {
+                let hello = HelloPacket::try_from_slice(body)?;
+                let lls = lls(hello.has_lls());
+                Message::Hello(hello, lls)
+            }
+            types::DB_DESCRIPTION => {
+                let dd = DBDescription::try_from_slice(body)?;
+                let lls = lls(dd.has_lls());
+                Message::DbDescription(dd, lls)
+            }
+            types::LS_REQUEST => {
+                // 一个请求报文中可以有多条请求
+                let mut buf = body;
+                let mut requests = vec![];
+                while !buf.is_empty() {
+                    requests.push(LSRequest::try_from_buf(&mut buf)?);
+                }
+                Message::LsRequest(requests)
+            }
+            types::LS_UPDATE => Message::LsUpdate(LSUpdate::try_from_slice(body)?),
+            types::LS_ACKNOWLEDGE => Message::LsAck(LSAcknowledge::try_from_slice(body)?),
+            ty => return Err(DecodeError::UnknownType(ty)),
+        })
+    }
+}
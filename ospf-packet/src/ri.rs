@@ -0,0 +1,140 @@
+//! 路由器信息 LSA（RFC 7770）。RI LSA 是不透明类型为 4、不透明标识为 0 的不透明 LSA，
+//! 内容为一组 TLV：能力、主机名（RFC 5642）、分段路由的算法和标签范围（RFC 8665）等。
+
+use bytes::{Buf, BytesMut};
+
+use crate::{
+    bits::*,
+    sr::{ri_tlv_types, SidLabelRange},
+    tlv::{self, Tlv},
+};
+
+pub const RI_OPAQUE_TYPE: u8 = 4;
+
+pub mod tlv_types {
+    pub const INFORMATIONAL_CAPABILITIES: u16 = 1;
+    pub const HOSTNAME: u16 = 7;
+}
+
+/// 信息能力位，位 0 为最高位
+pub mod capabilities {
+    pub const GRACEFUL_RESTART: u32 = 1 << 31;
+    pub const GRACEFUL_RESTART_HELPER: u32 = 1 << 30;
+    pub const STUB_ROUTER: u32 = 1 << 29;
+    pub const TRAFFIC_ENGINEERING: u32 = 1 << 28;
+    pub const P2P_OVER_LAN: u32 = 1 << 27;
+    pub const EXPERIMENTAL_TE: u32 = 1 << 26;
+
+    pub fn to_string(capabilities: u32) -> String {
+        let names = [
+            (GRACEFUL_RESTART, "GR"),
+            (GRACEFUL_RESTART_HELPER, "GR-Helper"),
+            (STUB_ROUTER, "Stub-Router"),
+            (TRAFFIC_ENGINEERING, "TE"),
+            (P2P_OVER_LAN, "P2P-LAN"),
+            (EXPERIMENTAL_TE, "Exp-TE"),
+        ];
+        let names: Vec<_> = names
+            .iter()
+            .filter(|(bit, _)| capabilities & bit != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        names.join(",")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouterInfo {
+    pub capabilities: Option<u32>,
+    pub hostname: Option<String>,
+    pub sr_algorithms: Option<Vec<u8>>,
+    /// 全局标签块（SRGB），只处理第一个范围
+    pub srgb: Option<SidLabelRange>,
+    /// 本地标签块（SRLB）
+    pub srlb: Option<SidLabelRange>,
+    /// 其余未解析的 TLV，原样保留
+    pub others: Vec<Tlv>,
+}
+
+impl ToBytesMut for RouterInfo {
+    fn to_bytes_mut(&self) -> BytesMut {
+        let mut tlvs = vec![];
+        if let Some(cap) = self.capabilities {
+            tlvs.push(Tlv::from_u32(tlv_types::INFORMATIONAL_CAPABILITIES, cap));
+        }
+        if let Some(ref name) = self.hostname {
+            tlvs.push(Tlv::new(tlv_types::HOSTNAME, name.as_bytes()));
+        }
+        if let Some(ref algorithms) = self.sr_algorithms {
+            tlvs.push(Tlv::new(ri_tlv_types::SR_ALGORITHM, algorithms.as_slice()));
+        }
+        if let Some(srgb) = self.srgb {
+            tlvs.push(srgb.to_tlv(ri_tlv_types::SID_LABEL_RANGE));
+        }
+        if let Some(srlb) = self.srlb {
+            tlvs.push(srlb.to_tlv(ri_tlv_types::SR_LOCAL_BLOCK));
+        }
+        tlvs.extend(self.others.iter().cloned());
+        BytesMut::from(tlv::encode_all(&tlvs).as_slice())
+    }
+}
+
+impl TryFromBuf for RouterInfo {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        let mut info = Self::default();
+        for tlv in tlv::decode_all(buf)? {
+            match tlv.ty {
+                tlv_types::INFORMATIONAL_CAPABILITIES => {
+                    // 能力 TLV 可以更长，只取前 32 位
+                    let value = tlv.value.get(..4).ok_or(DecodeError::BadLength(tlv.value.len()))?;
+                    info.capabilities = Some(u32::from_be_bytes(value.try_into().unwrap()));
+                }
+                tlv_types::HOSTNAME => {
+                    // 主机名可能以 0 结尾补齐
+                    let name = tlv.value.split(|&b| b == 0).next().unwrap_or_default();
+                    info.hostname = Some(String::from_utf8_lossy(name).into_owned());
+                }
+                ri_tlv_types::SR_ALGORITHM => info.sr_algorithms = Some(tlv.value),
+                ri_tlv_types::SID_LABEL_RANGE if info.srgb.is_none() => {
+                    info.srgb = Some(SidLabelRange::from_tlv(&tlv)?)
+                }
+                ri_tlv_types::SR_LOCAL_BLOCK if info.srlb.is_none() => {
+                    info.srlb = Some(SidLabelRange::from_tlv(&tlv)?)
+                }
+                _ => info.others.push(tlv),
+            }
+        }
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let info = RouterInfo {
+            capabilities: Some(capabilities::TRAFFIC_ENGINEERING),
+            hostname: Some("core-1".into()),
+            others: vec![Tlv::new(100, [1, 2])],
+            ..Default::default()
+        };
+        let bytes = info.to_bytes();
+        // 能力 8 字节，主机名 4 + 8 字节，未知 TLV 4 + 4 字节
+        assert_eq!(bytes.len(), 8 + 12 + 8);
+        assert_eq!(RouterInfo::try_from_slice(&bytes).unwrap(), info);
+        assert_eq!(capabilities::to_string(info.capabilities.unwrap()), "TE");
+        let info = RouterInfo {
+            sr_algorithms: Some(vec![0]),
+            srgb: Some(SidLabelRange {
+                size: 8000,
+                first: crate::sr::Sid::Label(16000),
+            }),
+            ..Default::default()
+        };
+        // 算法 4 + 4 字节，标签范围 4 + 4 + 8 字节
+        assert_eq!(info.to_bytes().len(), 8 + 16);
+        assert_eq!(RouterInfo::try_from_slice(&info.to_bytes()).unwrap(), info);
+    }
+}
@@ -0,0 +1,354 @@
+//! 分段路由扩展（RFC 8665）：扩展前缀 LSA 与扩展链路 LSA（RFC 7684）中的 SID，
+//! 以及路由器信息 LSA 中的 SR 算法和 SID/标签范围 TLV。
+
+use std::net::Ipv4Addr;
+
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::{
+    bits::*,
+    tlv::{self, Tlv},
+};
+
+pub const EXTENDED_PREFIX_OPAQUE_TYPE: u8 = 7;
+pub const EXTENDED_LINK_OPAQUE_TYPE: u8 = 8;
+
+/// 路由器信息 LSA 中的 TLV
+pub mod ri_tlv_types {
+    pub const SR_ALGORITHM: u16 = 8;
+    pub const SID_LABEL_RANGE: u16 = 9;
+    pub const SR_LOCAL_BLOCK: u16 = 14;
+}
+
+pub mod algorithms {
+    pub const SPF: u8 = 0;
+    pub const STRICT_SPF: u8 = 1;
+}
+
+/// 扩展前缀/扩展链路 LSA 中的 TLV
+pub mod tlv_types {
+    pub const EXTENDED_PREFIX: u16 = 1;
+    pub const EXTENDED_LINK: u16 = 1;
+}
+
+pub mod sub_tlv_types {
+    pub const SID_LABEL: u16 = 1;
+    pub const PREFIX_SID: u16 = 2;
+    pub const ADJ_SID: u16 = 2;
+    pub const LAN_ADJ_SID: u16 = 3;
+}
+
+/// 扩展前缀 TLV 的标志
+pub mod prefix_flags {
+    pub const ATTACH: u8 = 0x80;
+    pub const NODE: u8 = 0x40;
+}
+
+/// 前缀 SID 子 TLV 的标志
+pub mod prefix_sid_flags {
+    /// 不弹出倒数第二跳的标签
+    pub const NO_PHP: u8 = 0x40;
+    pub const MAPPING_SERVER: u8 = 0x20;
+    pub const EXPLICIT_NULL: u8 = 0x10;
+    pub const VALUE: u8 = 0x08;
+    pub const LOCAL: u8 = 0x04;
+}
+
+/// 邻接 SID 子 TLV 的标志
+pub mod adj_sid_flags {
+    pub const BACKUP: u8 = 0x80;
+    pub const VALUE: u8 = 0x40;
+    pub const LOCAL: u8 = 0x20;
+    pub const GROUP: u8 = 0x10;
+    pub const PERSISTENT: u8 = 0x08;
+}
+
+/// SID 为 3 字节时是标签，4 字节时是索引
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sid {
+    Label(u32),
+    Index(u32),
+}
+
+impl Sid {
+    fn to_vec(self) -> Vec<u8> {
+        match self {
+            Sid::Label(label) => label.to_be_bytes()[1..].to_vec(),
+            Sid::Index(index) => index.to_be_bytes().to_vec(),
+        }
+    }
+
+    fn from_slice(value: &[u8]) -> Result<Self, DecodeError> {
+        match value.len() {
+            3 => Ok(Sid::Label(u32::from_be_bytes([0, value[0], value[1], value[2]]) & 0xfffff)),
+            4 => Ok(Sid::Index(u32::from_be_bytes(value.try_into().unwrap()))),
+            n => Err(DecodeError::BadLength(n)),
+        }
+    }
+}
+
+impl std::fmt::Display for Sid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sid::Label(label) => write!(f, "label {}", label),
+            Sid::Index(index) => write!(f, "index {}", index),
+        }
+    }
+}
+
+/// SID/标签范围 TLV（SRGB）或 SR 本地块 TLV（SRLB）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SidLabelRange {
+    pub size: u32,
+    pub first: Sid,
+}
+
+impl SidLabelRange {
+    pub fn to_tlv(&self, ty: u16) -> Tlv {
+        let mut value = self.size.to_be_bytes()[1..].to_vec();
+        value.push(0);
+        value.extend(Tlv::new(sub_tlv_types::SID_LABEL, self.first.to_vec()).to_bytes());
+        Tlv::new(ty, value)
+    }
+
+    pub fn from_tlv(tlv: &Tlv) -> Result<Self, DecodeError> {
+        must_len(tlv, 4)?;
+        let v = &tlv.value;
+        let size = u32::from_be_bytes([0, v[0], v[1], v[2]]);
+        let sub = tlv::decode_all(&mut &v[4..])?;
+        let first = sub
+            .iter()
+            .find(|t| t.ty == sub_tlv_types::SID_LABEL)
+            .ok_or(DecodeError::BadLength(v.len()))?;
+        Ok(Self {
+            size,
+            first: Sid::from_slice(&first.value)?,
+        })
+    }
+
+    /// 范围内第 index 个标签
+    pub fn label(&self, index: u32) -> Option<u32> {
+        match self.first {
+            Sid::Label(base) if index < self.size => Some(base + index),
+            _ => None,
+        }
+    }
+}
+
+fn must_len(tlv: &Tlv, len: usize) -> Result<(), DecodeError> {
+    if tlv.value.len() < len {
+        return Err(DecodeError::BadLength(tlv.value.len()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixSid {
+    pub flags: u8,
+    pub mt_id: u8,
+    pub algorithm: u8,
+    pub sid: Sid,
+}
+
+/// 扩展前缀 TLV，扩展前缀 LSA 中只有一个
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPrefix {
+    pub route_type: u8,
+    pub prefix_len: u8,
+    pub flags: u8,
+    pub prefix: Ipv4Addr,
+    pub prefix_sids: Vec<PrefixSid>,
+}
+
+impl ToBytesMut for ExtendedPrefix {
+    fn to_bytes_mut(&self) -> BytesMut {
+        let mut value = BytesMut::new();
+        value.put_u8(self.route_type);
+        value.put_u8(self.prefix_len);
+        value.put_u8(0); // address family: IPv4 unicast
+        value.put_u8(self.flags);
+        // 前缀按 32 位字对齐，长度为 0 时不占空间
+        if self.prefix_len > 0 {
+            value.put_slice(&self.prefix.octets());
+        }
+        for sid in self.prefix_sids.iter() {
+            let mut v = vec![sid.flags, 0, sid.mt_id, sid.algorithm];
+            v.extend(sid.sid.to_vec());
+            value.extend(Tlv::new(sub_tlv_types::PREFIX_SID, v).to_bytes());
+        }
+        Tlv::new(tlv_types::EXTENDED_PREFIX, value.to_vec()).to_bytes_mut()
+    }
+}
+
+impl TryFromBuf for ExtendedPrefix {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        let tlv = first_tlv(buf, tlv_types::EXTENDED_PREFIX)?;
+        must_len(&tlv, 4)?;
+        let v = &tlv.value;
+        let (route_type, prefix_len, flags) = (v[0], v[1], v[3]);
+        if prefix_len > 32 {
+            return Err(DecodeError::BadLength(prefix_len as usize));
+        }
+        let len = if prefix_len > 0 { 4 } else { 0 };
+        must_len(&tlv, 4 + len)?;
+        let prefix = match len {
+            0 => Ipv4Addr::UNSPECIFIED,
+            _ => Ipv4Addr::new(v[4], v[5], v[6], v[7]),
+        };
+        let mut prefix_sids = vec![];
+        for sub in tlv::decode_all(&mut &v[4 + len..])? {
+            if sub.ty != sub_tlv_types::PREFIX_SID {
+                continue;
+            }
+            must_len(&sub, 4)?;
+            let s = &sub.value;
+            prefix_sids.push(PrefixSid {
+                flags: s[0],
+                mt_id: s[2],
+                algorithm: s[3],
+                sid: Sid::from_slice(&s[4..])?,
+            });
+        }
+        Ok(Self {
+            route_type,
+            prefix_len,
+            flags,
+            prefix,
+            prefix_sids,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdjSid {
+    pub flags: u8,
+    pub mt_id: u8,
+    pub weight: u8,
+    /// 广播网络上的 LAN 邻接 SID 带有邻居的路由器标识
+    pub neighbor: Option<Ipv4Addr>,
+    pub sid: Sid,
+}
+
+/// 扩展链路 TLV，扩展链路 LSA 中只有一个
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedLink {
+    pub link_type: u8,
+    pub link_id: Ipv4Addr,
+    pub link_data: Ipv4Addr,
+    pub adj_sids: Vec<AdjSid>,
+}
+
+impl ToBytesMut for ExtendedLink {
+    fn to_bytes_mut(&self) -> BytesMut {
+        let mut value = BytesMut::new();
+        value.put_u8(self.link_type);
+        value.put_bytes(0, 3);
+        value.put_slice(&self.link_id.octets());
+        value.put_slice(&self.link_data.octets());
+        for sid in self.adj_sids.iter() {
+            let mut v = vec![sid.flags, 0, sid.mt_id, sid.weight];
+            let ty = match sid.neighbor {
+                Some(neighbor) => {
+                    v.extend(neighbor.octets());
+                    sub_tlv_types::LAN_ADJ_SID
+                }
+                None => sub_tlv_types::ADJ_SID,
+            };
+            v.extend(sid.sid.to_vec());
+            value.extend(Tlv::new(ty, v).to_bytes());
+        }
+        Tlv::new(tlv_types::EXTENDED_LINK, value.to_vec()).to_bytes_mut()
+    }
+}
+
+impl TryFromBuf for ExtendedLink {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        let tlv = first_tlv(buf, tlv_types::EXTENDED_LINK)?;
+        must_len(&tlv, 12)?;
+        let v = &tlv.value;
+        let mut adj_sids = vec![];
+        for sub in tlv::decode_all(&mut &v[12..])? {
+            let (neighbor, sid) = match sub.ty {
+                sub_tlv_types::ADJ_SID => {
+                    must_len(&sub, 4)?;
+                    (None, &sub.value[4..])
+                }
+                sub_tlv_types::LAN_ADJ_SID => {
+                    must_len(&sub, 8)?;
+                    let n = &sub.value[4..8];
+                    (Some(Ipv4Addr::new(n[0], n[1], n[2], n[3])), &sub.value[8..])
+                }
+                _ => continue,
+            };
+            adj_sids.push(AdjSid {
+                flags: sub.value[0],
+                mt_id: sub.value[2],
+                weight: sub.value[3],
+                neighbor,
+                sid: Sid::from_slice(sid)?,
+            });
+        }
+        Ok(Self {
+            link_type: v[0],
+            link_id: Ipv4Addr::new(v[4], v[5], v[6], v[7]),
+            link_data: Ipv4Addr::new(v[8], v[9], v[10], v[11]),
+            adj_sids,
+        })
+    }
+}
+
+/// 取出指定类型的第一个 TLV，其余的被忽略
+fn first_tlv(buf: &mut impl Buf, ty: u16) -> Result<Tlv, DecodeError> {
+    let tlvs = tlv::decode_all(buf)?;
+    tlvs.into_iter()
+        .find(|t| t.ty == ty)
+        .ok_or(DecodeError::UnknownType(ty as u8))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let prefix = ExtendedPrefix {
+            route_type: 1,
+            prefix_len: 32,
+            flags: prefix_flags::NODE,
+            prefix: Ipv4Addr::new(10, 255, 0, 1),
+            prefix_sids: vec![PrefixSid {
+                flags: 0,
+                mt_id: 0,
+                algorithm: algorithms::SPF,
+                sid: Sid::Index(1),
+            }],
+        };
+        let bytes = prefix.to_bytes();
+        assert_eq!(bytes.len(), 4 + 8 + 4 + 8);
+        assert_eq!(ExtendedPrefix::try_from_slice(&bytes).unwrap(), prefix);
+        let link = ExtendedLink {
+            link_type: 2,
+            link_id: Ipv4Addr::new(10, 0, 0, 1),
+            link_data: Ipv4Addr::new(10, 0, 0, 2),
+            adj_sids: vec![AdjSid {
+                flags: adj_sid_flags::VALUE | adj_sid_flags::LOCAL,
+                mt_id: 0,
+                weight: 0,
+                neighbor: Some(Ipv4Addr::new(1, 1, 1, 1)),
+                sid: Sid::Label(15000),
+            }],
+        };
+        let bytes = link.to_bytes();
+        // LAN 邻接 SID：4 + 8 + 3 字节，补齐到 4 + 12
+        assert_eq!(bytes.len(), 4 + 12 + 4 + 12);
+        assert_eq!(ExtendedLink::try_from_slice(&bytes).unwrap(), link);
+        let srgb = SidLabelRange {
+            size: 8000,
+            first: Sid::Label(16000),
+        };
+        let tlv = srgb.to_tlv(ri_tlv_types::SID_LABEL_RANGE);
+        assert_eq!(SidLabelRange::from_tlv(&tlv).unwrap(), srgb);
+        assert_eq!(srgb.label(10), Some(16010));
+        assert_eq!(srgb.label(8000), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! 流量工程扩展（RFC 3630）。TE LSA 是不透明类型为 1 的区域范围不透明 LSA，
+//! 内容为一个顶层 TLV：路由器地址 TLV 或链路 TLV。
+
+use std::net::Ipv4Addr;
+
+use bytes::{Buf, BytesMut};
+
+use crate::{bits::*, tlv::Tlv};
+
+pub const TE_OPAQUE_TYPE: u8 = 1;
+
+pub mod tlv_types {
+    pub const ROUTER_ADDRESS: u16 = 1;
+    pub const LINK: u16 = 2;
+}
+
+pub mod link_tlv_types {
+    pub const LINK_TYPE: u16 = 1;
+    pub const LINK_ID: u16 = 2;
+    pub const LOCAL_ADDRESS: u16 = 3;
+    pub const REMOTE_ADDRESS: u16 = 4;
+    pub const TE_METRIC: u16 = 5;
+    pub const MAX_BANDWIDTH: u16 = 6;
+    pub const MAX_RESERVABLE_BANDWIDTH: u16 = 7;
+    pub const UNRESERVED_BANDWIDTH: u16 = 8;
+    pub const ADMIN_GROUP: u16 = 9;
+}
+
+pub mod te_link_types {
+    pub const P2P_LINK: u8 = 1;
+    pub const MULTI_ACCESS_LINK: u8 = 2;
+
+    pub fn to_string(link_type: u8) -> &'static str {
+        match link_type {
+            P2P_LINK => "P2P",
+            MULTI_ACCESS_LINK => "Multi-access",
+            _ => "Unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeLsa {
+    RouterAddress(Ipv4Addr),
+    Link(TeLink),
+    Unknown(Tlv),
+}
+
+/// 链路 TLV，带宽的单位为字节每秒
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeLink {
+    pub link_type: u8,
+    pub link_id: Ipv4Addr,
+    pub local_addrs: Vec<Ipv4Addr>,
+    pub remote_addrs: Vec<Ipv4Addr>,
+    pub te_metric: Option<u32>,
+    pub max_bandwidth: Option<f32>,
+    pub max_reservable_bandwidth: Option<f32>,
+    /// 8 个优先级上未预留的带宽
+    pub unreserved_bandwidth: Option<[f32; 8]>,
+    pub admin_group: Option<u32>,
+}
+
+impl ToBytesMut for TeLsa {
+    fn to_bytes_mut(&self) -> BytesMut {
+        match self {
+            TeLsa::RouterAddress(addr) => Tlv::from_ip(tlv_types::ROUTER_ADDRESS, *addr),
+            TeLsa::Link(link) => Tlv::from_tlvs(tlv_types::LINK, &link.to_tlvs()),
+            TeLsa::Unknown(tlv) => tlv.clone(),
+        }
+        .to_bytes_mut()
+    }
+}
+
+impl TryFromBuf for TeLsa {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        let tlv = Tlv::try_from_buf(buf)?;
+        Ok(match tlv.ty {
+            tlv_types::ROUTER_ADDRESS => TeLsa::RouterAddress(tlv.as_ip()?),
+            tlv_types::LINK => TeLsa::Link(TeLink::from_tlvs(&tlv.as_tlvs()?)?),
+            _ => TeLsa::Unknown(tlv),
+        })
+    }
+}
+
+impl TeLink {
+    fn to_tlvs(&self) -> Vec<Tlv> {
+        use link_tlv_types::*;
+        let addrs = |addrs: &[Ipv4Addr]| addrs.iter().flat_map(|a| a.octets()).collect::<Vec<_>>();
+        let mut tlvs = vec![
+            Tlv::new(LINK_TYPE, [self.link_type]),
+            Tlv::from_ip(LINK_ID, self.link_id),
+        ];
+        if !self.local_addrs.is_empty() {
+            tlvs.push(Tlv::new(LOCAL_ADDRESS, addrs(&self.local_addrs)));
+        }
+        if !self.remote_addrs.is_empty() {
+            tlvs.push(Tlv::new(REMOTE_ADDRESS, addrs(&self.remote_addrs)));
+        }
+        if let Some(metric) = self.te_metric {
+            tlvs.push(Tlv::from_u32(TE_METRIC, metric));
+        }
+        if let Some(bw) = self.max_bandwidth {
+            tlvs.push(Tlv::from_u32(MAX_BANDWIDTH, bw.to_bits()));
+        }
+        if let Some(bw) = self.max_reservable_bandwidth {
+            tlvs.push(Tlv::from_u32(MAX_RESERVABLE_BANDWIDTH, bw.to_bits()));
+        }
+        if let Some(bw) = self.unreserved_bandwidth {
+            let value: Vec<_> = bw.iter().flat_map(|b| b.to_be_bytes()).collect();
+            tlvs.push(Tlv::new(UNRESERVED_BANDWIDTH, value));
+        }
+        if let Some(group) = self.admin_group {
+            tlvs.push(Tlv::from_u32(ADMIN_GROUP, group));
+        }
+        tlvs
+    }
+
+    /// 未知的子 TLV 被忽略
+    fn from_tlvs(tlvs: &[Tlv]) -> Result<Self, DecodeError> {
+        use link_tlv_types::*;
+        let addrs = |tlv: &Tlv| {
+            if !tlv.value.len().is_multiple_of(4) {
+                return Err(DecodeError::BadLength(tlv.value.len()));
+            }
+            Ok(tlv
+                .value
+                .chunks(4)
+                .map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3]))
+                .collect::<Vec<_>>())
+        };
+        let mut link = Self {
+            link_type: 0,
+            link_id: Ipv4Addr::UNSPECIFIED,
+            local_addrs: vec![],
+            remote_addrs: vec![],
+            te_metric: None,
+            max_bandwidth: None,
+            max_reservable_bandwidth: None,
+            unreserved_bandwidth: None,
+            admin_group: None,
+        };
+        for tlv in tlvs {
+            match tlv.ty {
+                LINK_TYPE => {
+                    if tlv.value.len() != 1 {
+                        return Err(DecodeError::BadLength(tlv.value.len()));
+                    }
+                    link.link_type = tlv.value[0];
+                }
+                LINK_ID => link.link_id = tlv.as_ip()?,
+                LOCAL_ADDRESS => link.local_addrs = addrs(tlv)?,
+                REMOTE_ADDRESS => link.remote_addrs = addrs(tlv)?,
+                TE_METRIC => link.te_metric = Some(tlv.as_u32()?),
+                MAX_BANDWIDTH => link.max_bandwidth = Some(f32::from_bits(tlv.as_u32()?)),
+                MAX_RESERVABLE_BANDWIDTH => {
+                    link.max_reservable_bandwidth = Some(f32::from_bits(tlv.as_u32()?))
+                }
+                UNRESERVED_BANDWIDTH => {
+                    let value: [u8; 32] = tlv.value[..]
+                        .try_into()
+                        .map_err(|_| DecodeError::BadLength(tlv.value.len()))?;
+                    let mut bw = [0f32; 8];
+                    for (b, c) in bw.iter_mut().zip(value.chunks(4)) {
+                        *b = f32::from_be_bytes(c.try_into().unwrap());
+                    }
+                    link.unreserved_bandwidth = Some(bw);
+                }
+                ADMIN_GROUP => link.admin_group = Some(tlv.as_u32()?),
+                _ => (),
+            }
+        }
+        Ok(link)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let link = TeLsa::Link(TeLink {
+            link_type: te_link_types::MULTI_ACCESS_LINK,
+            link_id: Ipv4Addr::new(10, 0, 0, 1),
+            local_addrs: vec![Ipv4Addr::new(10, 0, 0, 2)],
+            remote_addrs: vec![],
+            te_metric: Some(10),
+            max_bandwidth: Some(125_000_000.0),
+            max_reservable_bandwidth: Some(100_000_000.0),
+            unreserved_bandwidth: Some([100_000_000.0; 8]),
+            admin_group: Some(0x5),
+        });
+        let bytes = link.to_bytes();
+        // 链路 TLV 头 4 字节，子 TLV：类型 8、标识 8、本地地址 8、度量 8、带宽 8 * 2、未预留带宽 36、管理组 8
+        assert_eq!(bytes.len(), 4 + 8 + 8 + 8 + 8 + 16 + 36 + 8);
+        assert_eq!(TeLsa::try_from_slice(&bytes).unwrap(), link);
+        let addr = TeLsa::RouterAddress(Ipv4Addr::new(1, 1, 1, 1));
+        assert_eq!(addr.to_bytes().as_ref(), &[0, 1, 0, 4, 1, 1, 1, 1]);
+        assert_eq!(TeLsa::try_from_slice(&addr.to_bytes()).unwrap(), addr);
+        assert!(matches!(
+            TeLsa::try_from_slice(&[0, 1, 0, 3, 1, 1, 1, 0]),
+            Err(DecodeError::BadLength(3))
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+//! 不透明 LSA 中的 TLV 编码：2 字节类型、2 字节长度，值按 4 字节对齐补零
+
+use std::net::Ipv4Addr;
+
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::bits::*;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tlv {
+    pub ty: u16,
+    pub value: Vec<u8>,
+}
+
+impl Tlv {
+    pub fn new(ty: u16, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            ty,
+            value: value.into(),
+        }
+    }
+
+    pub fn from_u32(ty: u16, value: u32) -> Self {
+        Self::new(ty, value.to_be_bytes())
+    }
+
+    pub fn from_ip(ty: u16, value: Ipv4Addr) -> Self {
+        Self::new(ty, value.octets())
+    }
+
+    pub fn from_tlvs(ty: u16, tlvs: &[Tlv]) -> Self {
+        Self::new(ty, encode_all(tlvs))
+    }
+
+    pub fn as_u32(&self) -> Result<u32, DecodeError> {
+        let value: [u8; 4] = self.value[..]
+            .try_into()
+            .map_err(|_| DecodeError::BadLength(self.value.len()))?;
+        Ok(u32::from_be_bytes(value))
+    }
+
+    pub fn as_ip(&self) -> Result<Ipv4Addr, DecodeError> {
+        self.as_u32().map(Ipv4Addr::from)
+    }
+
+    pub fn as_tlvs(&self) -> Result<Vec<Tlv>, DecodeError> {
+        decode_all(&mut self.value.as_slice())
+    }
+}
+
+impl ToBytesMut for Tlv {
+    fn to_bytes_mut(&self) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u16(self.ty);
+        buf.put_u16(self.value.len() as u16);
+        buf.extend_from_slice(&self.value);
+        buf.put_bytes(0, padding(self.value.len()));
+        buf
+    }
+}
+
+impl TryFromBuf for Tlv {
+    fn try_from_buf(buf: &mut impl Buf) -> Result<Self, DecodeError> {
+        check_remaining(buf, 4)?;
+        let ty = buf.get_u16();
+        let len = buf.get_u16() as usize;
+        check_remaining(buf, len)?;
+        let value = buf.copy_to_bytes(len).to_vec();
+        // 最后一个 TLV 之后的补齐可能被省略
+        buf.advance(padding(len).min(buf.remaining()));
+        Ok(Self { ty, value })
+    }
+}
+
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+pub fn encode_all(tlvs: &[Tlv]) -> Vec<u8> {
+    tlvs.iter().flat_map(|tlv| tlv.to_bytes()).collect()
+}
+
+pub fn decode_all(buf: &mut impl Buf) -> Result<Vec<Tlv>, DecodeError> {
+    let mut tlvs = vec![];
+    while buf.has_remaining() {
+        tlvs.push(Tlv::try_from_buf(buf)?);
+    }
+    Ok(tlvs)
+}
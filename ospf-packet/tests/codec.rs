@@ -0,0 +1,275 @@
+//! 编解码的性质测试：解码(编码(x)) == x，校验和，以及不足一字节的位域
+
+use std::{marker::PhantomData, net::Ipv4Addr};
+
+use ospf_packet::{
+    bits::{Bits, BitsMut},
+    lsa::{types::*, *},
+    packet::*,
+    Ospf, ToBytes, TryFromBuf,
+};
+use proptest::prelude::*;
+
+fn ipv4() -> impl Strategy<Value = Ipv4Addr> {
+    any::<u32>().prop_map(Ipv4Addr::from)
+}
+
+fn bit() -> impl Strategy<Value = u8> {
+    0u8..2
+}
+
+fn u24() -> impl Strategy<Value = u32> {
+    0u32..1 << 24
+}
+
+fn header() -> impl Strategy<Value = LsaHeader> {
+    (any::<u16>(), any::<u8>(), ipv4(), ipv4(), any::<i32>()).prop_map(
+        |(ls_age, options, link_state_id, advertising_router, ls_sequence_number)| LsaHeader {
+            ls_age,
+            options,
+            ls_type: 0,
+            link_state_id,
+            advertising_router,
+            ls_sequence_number,
+            ls_checksum: 0,
+            length: 0,
+        },
+    )
+}
+
+fn router_lsa() -> impl Strategy<Value = RouterLSA> {
+    let link = (ipv4(), ipv4(), any::<u8>(), any::<u8>(), any::<u16>()).prop_map(
+        |(link_id, link_data, link_type, tos, metric)| RouterLSALink {
+            link_id,
+            link_data,
+            link_type,
+            tos,
+            metric,
+        },
+    );
+    (bit(), bit(), bit(), prop::collection::vec(link, 0..8)).prop_map(|(v, e, b, links)| {
+        RouterLSA {
+            _z1: PhantomData,
+            v,
+            e,
+            b,
+            _z2: PhantomData,
+            num_links: links.len() as u16,
+            links,
+        }
+    })
+}
+
+fn network_lsa() -> impl Strategy<Value = NetworkLSA> {
+    (ipv4(), prop::collection::vec(ipv4(), 0..8)).prop_map(|(network_mask, attached_routers)| {
+        NetworkLSA {
+            network_mask,
+            attached_routers,
+        }
+    })
+}
+
+fn summary_lsa() -> impl Strategy<Value = SummaryLSA> {
+    (ipv4(), u24()).prop_map(|(network_mask, metric)| SummaryLSA {
+        network_mask,
+        _zeros: PhantomData,
+        metric,
+    })
+}
+
+fn external_lsa() -> impl Strategy<Value = AsExternalLSA> {
+    (ipv4(), bit(), u24(), ipv4(), any::<u32>()).prop_map(
+        |(network_mask, e, metric, forwarding_address, external_router_tag)| AsExternalLSA {
+            network_mask,
+            e,
+            _zeros: PhantomData,
+            metric,
+            forwarding_address,
+            external_router_tag,
+        },
+    )
+}
+
+fn lsa() -> impl Strategy<Value = Lsa> {
+    let data = prop_oneof![
+        router_lsa().prop_map(|lsa| (ROUTER_LSA, LsaData::Router(lsa))),
+        network_lsa().prop_map(|lsa| (NETWORK_LSA, LsaData::Network(lsa))),
+        summary_lsa().prop_map(|lsa| (SUMMARY_IP_LSA, LsaData::SummaryIP(lsa))),
+        summary_lsa().prop_map(|lsa| (SUMMARY_ASBR_LSA, LsaData::SummaryASBR(lsa))),
+        external_lsa().prop_map(|lsa| (AS_EXTERNAL_LSA, LsaData::ASExternal(lsa))),
+        (OPAQUE_LINK_LSA..=OPAQUE_AS_LSA, prop::collection::vec(any::<u8>(), 0..16))
+            .prop_map(|(ty, data)| (ty, LsaData::Opaque(OpaqueLSA { data }))),
+    ];
+    (header(), data).prop_map(|(mut header, (ls_type, data))| {
+        header.ls_type = ls_type;
+        let mut lsa = Lsa { header, data };
+        lsa.update_length();
+        lsa.update_checksum();
+        lsa
+    })
+}
+
+/// 编码后再解码，重新编码的结果必须与第一次编码相同
+fn round_trip<T: ToBytes + TryFromBuf>(value: &T) -> T {
+    let bytes = value.to_bytes();
+    let decoded = T::try_from_slice(&bytes).unwrap();
+    assert_eq!(decoded.to_bytes(), bytes);
+    decoded
+}
+
+proptest! {
+    #[test]
+    fn lsa_round_trip(lsa in lsa()) {
+        let decoded = round_trip(&lsa);
+        prop_assert_eq!(decoded.header.ls_type, lsa.header.ls_type);
+        prop_assert_eq!(decoded.header.length as usize, lsa.to_bytes().len());
+        prop_assert_eq!(decoded.data, lsa.data);
+    }
+
+    #[test]
+    fn lsa_checksum(mut lsa in lsa(), age in any::<u16>()) {
+        prop_assert!(lsa.checksum_ok());
+        prop_assert_eq!(lsa.checksum(), lsa.header.ls_checksum);
+        // LS 时限不在校验范围内
+        lsa.header.ls_age = age;
+        prop_assert!(lsa.checksum_ok());
+        lsa.header.ls_sequence_number = lsa.header.ls_sequence_number.wrapping_add(1);
+        prop_assert!(!lsa.checksum_ok());
+    }
+
+    #[test]
+    fn router_lsa_bits(lsa in router_lsa()) {
+        let decoded = round_trip(&lsa);
+        prop_assert_eq!((decoded.v, decoded.e, decoded.b), (lsa.v, lsa.e, lsa.b));
+        prop_assert_eq!(decoded, lsa);
+    }
+
+    #[test]
+    fn external_lsa_bits(lsa in external_lsa()) {
+        prop_assert_eq!(round_trip(&lsa), lsa);
+    }
+
+    #[test]
+    fn hello_round_trip(
+        (network_mask, hello_interval, options, router_priority) in (ipv4(), any::<u16>(), any::<u8>(), any::<u8>()),
+        (router_dead_interval, designated_router, backup_designated_router) in (any::<u32>(), ipv4(), ipv4()),
+        neighbors in prop::collection::vec(ipv4(), 0..8),
+    ) {
+        let packet = HelloPacket {
+            network_mask,
+            hello_interval,
+            options,
+            router_priority,
+            router_dead_interval,
+            designated_router,
+            backup_designated_router,
+            neighbors,
+        };
+        let decoded = round_trip(&packet);
+        prop_assert_eq!(decoded.neighbors, packet.neighbors);
+    }
+
+    #[test]
+    fn dd_round_trip(
+        (interface_mtu, options, resync, init, more, master) in (any::<u16>(), any::<u8>(), bit(), bit(), bit(), bit()),
+        db_sequence_number in any::<u32>(),
+        lsa_header in prop::collection::vec(header(), 0..8),
+    ) {
+        let packet = DBDescription {
+            interface_mtu,
+            options,
+            _zeros: PhantomData,
+            resync,
+            init,
+            more,
+            master,
+            db_sequence_number,
+            lsa_header,
+        };
+        let decoded = round_trip(&packet);
+        prop_assert_eq!((decoded.resync, decoded.init, decoded.more, decoded.master), (resync, init, more, master));
+        prop_assert_eq!(decoded.lsa_header.len(), packet.lsa_header.len());
+    }
+
+    #[test]
+    fn lsr_round_trip(ls_type in any::<u32>(), ls_id in ipv4(), advertising_router in ipv4()) {
+        let packet = LSRequest { ls_type, ls_id, advertising_router };
+        let decoded = round_trip(&packet);
+        prop_assert_eq!((decoded.ls_type, decoded.ls_id), (ls_type, ls_id));
+    }
+
+    #[test]
+    fn lsu_and_ack_round_trip(lsa in prop::collection::vec(lsa(), 0..4)) {
+        let packet = LSUpdate { num_lsa: lsa.len() as u32, lsa };
+        let decoded = round_trip(&packet);
+        prop_assert_eq!(decoded.lsa.len(), packet.lsa.len());
+        let packet = LSAcknowledge {
+            lsa_header: packet.lsa.iter().map(|lsa| lsa.header).collect(),
+        };
+        round_trip(&packet);
+    }
+
+    #[test]
+    fn message_decode(requests in prop::collection::vec((any::<u32>(), ipv4(), ipv4()), 0..8), padding in 0usize..8) {
+        let mut payload: Vec<u8> = requests
+            .iter()
+            .flat_map(|&(ls_type, ls_id, advertising_router)| {
+                LSRequest { ls_type, ls_id, advertising_router }.to_bytes()
+            })
+            .collect();
+        let length = 24 + payload.len() as u16;
+        // 报头长度之后的填充不属于报文
+        payload.extend(std::iter::repeat_n(0, padding));
+        let packet = Ospf {
+            version: 2,
+            message_type: ospf_packet::packet::types::LS_REQUEST,
+            length,
+            router_id: 0,
+            area_id: 0,
+            checksum: 0,
+            au_type: 0,
+            authentication: 0,
+            payload,
+        };
+        match Message::decode(&packet) {
+            Ok(Message::LsRequest(decoded)) => prop_assert_eq!(decoded.len(), requests.len()),
+            other => prop_assert!(false, "unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bits_round_trip(fields in prop::collection::vec((1u32..=32, any::<u32>()), 0..16)) {
+        let fields: Vec<_> = fields
+            .into_iter()
+            .map(|(n, v)| (n, (v as u64 & ((1u64 << n) - 1)) as u32))
+            .collect();
+        let mut buf = BitsMut::new();
+        let mut total = 0;
+        for &(n, v) in fields.iter() {
+            // 按字节写入的字段必须对齐
+            let n = if n % 8 == 0 && total % 8 != 0 { n - 1 } else { n };
+            buf.put_un(v & ((1u64 << n) - 1) as u32, n);
+            total += n;
+        }
+        // 补齐最后一个字节
+        buf.put_un(0u8, (8 - total % 8) % 8);
+        let bytes: bytes::BytesMut = buf.into();
+        let mut bits = Bits::from(bytes.as_ref());
+        total = 0;
+        for &(n, v) in fields.iter() {
+            let n = if n % 8 == 0 && total % 8 != 0 { n - 1 } else { n };
+            prop_assert_eq!(bits.try_get_un::<u32>(n).unwrap(), v & ((1u64 << n) - 1) as u32);
+            total += n;
+        }
+    }
+
+    #[test]
+    fn decode_never_panics(data in prop::collection::vec(any::<u8>(), 0..128)) {
+        let _ = Lsa::try_from_slice(&data);
+        let _ = HelloPacket::try_from_slice(&data);
+        let _ = DBDescription::try_from_slice(&data);
+        let _ = LSRequest::try_from_slice(&data);
+        let _ = LSUpdate::try_from_slice(&data);
+        let _ = LSAcknowledge::try_from_slice(&data);
+    }
+}
@@ -51,6 +51,8 @@ mod raw {
     extern "C" {
         pub fn add_route(r: *const routing_item_t) -> libc::c_int;
         pub fn delete_route(r: *const routing_item_t) -> libc::c_int;
+        pub fn add_reject_route(r: *const routing_item_t) -> libc::c_int;
+        pub fn delete_reject_route(r: *const routing_item_t) -> libc::c_int;
         pub fn get_route_table(arr: *mut routing_item_t, size: libc::c_int) -> libc::c_int;
     }
 }
@@ -71,6 +73,24 @@ pub fn delete_route(r: RoutingItem) -> Result<(), io::Error> {
     }
 }
 
+/// 添加丢弃路由，忽略下一跳
+pub fn add_discard_route(r: RoutingItem) -> Result<(), io::Error> {
+    if unsafe { raw::add_reject_route(&r.into()) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// 删除丢弃路由，忽略下一跳
+pub fn delete_discard_route(r: RoutingItem) -> Result<(), io::Error> {
+    if unsafe { raw::delete_reject_route(&r.into()) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
 pub fn get_route_table() -> Result<Vec<RoutingItem>, io::Error> {
     let mut arr = [unsafe { std::mem::zeroed::<routing_item_t>() }; 128];
     let size = unsafe { raw::get_route_table(arr.as_mut_ptr(), arr.len() as libc::c_int) };
@@ -105,5 +125,13 @@ mod test {
         delete_route(item).unwrap();
         let items = get_route_table().unwrap();
         assert!(!items.contains(&item));
+        let discard = RoutingItem {
+            nexthop: Ipv4Addr::UNSPECIFIED,
+            ..item
+        };
+        add_discard_route(discard).unwrap();
+        assert!(get_route_table().unwrap().contains(&discard));
+        delete_discard_route(discard).unwrap();
+        assert!(!get_route_table().unwrap().contains(&discard));
     }
 }
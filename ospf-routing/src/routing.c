@@ -58,6 +58,54 @@ int delete_route(const routing_item_t *_r)
     return ret;
 }
 
+int add_reject_route(const routing_item_t *_r)
+{
+    int fd = socket(PF_INET, SOCK_DGRAM, IPPROTO_IP);
+    if (fd < 0) return -1;
+
+    struct rtentry route;
+    struct sockaddr_in *addr;
+    memset(&route, 0, sizeof(route));
+
+    addr = (struct sockaddr_in *)&route.rt_dst;
+    addr->sin_family = AF_INET;
+    addr->sin_addr.s_addr = _r->dest;
+
+    addr = (struct sockaddr_in *)&route.rt_genmask;
+    addr->sin_family = AF_INET;
+    addr->sin_addr.s_addr = _r->mask;
+
+    route.rt_flags = RTF_UP | RTF_REJECT;
+
+    int ret = ioctl(fd, SIOCADDRT, &route);
+    close(fd);
+    return ret;
+}
+
+int delete_reject_route(const routing_item_t *_r)
+{
+    int fd = socket(PF_INET, SOCK_DGRAM, IPPROTO_IP);
+    if (fd < 0) return -1;
+
+    struct rtentry route;
+    struct sockaddr_in *addr;
+    memset(&route, 0, sizeof(route));
+
+    addr = (struct sockaddr_in *)&route.rt_dst;
+    addr->sin_family = AF_INET;
+    addr->sin_addr.s_addr = _r->dest;
+
+    addr = (struct sockaddr_in *)&route.rt_genmask;
+    addr->sin_family = AF_INET;
+    addr->sin_addr.s_addr = _r->mask;
+
+    route.rt_flags = RTF_REJECT;
+
+    int ret = ioctl(fd, SIOCDELRT, &route);
+    close(fd);
+    return ret;
+}
+
 int get_route_table(routing_item_t *_arr, int _size)
 {
     FILE *fp;
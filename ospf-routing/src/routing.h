@@ -22,6 +22,16 @@ int add_route(const routing_item_t *);
 /// @return 通常而言 -1 表示失败
 int delete_route(const routing_item_t *);
 
+/// @brief 添加丢弃路由（unreachable），不需要下一跳
+/// @param  路由表项（nexthop不填）
+/// @return 通常而言 -1 表示失败
+int add_reject_route(const routing_item_t *);
+
+/// @brief 删除丢弃路由，不会删除相同目的地址的普通路由
+/// @param  路由表项（nexthop不填）
+/// @return 通常而言 -1 表示失败
+int delete_reject_route(const routing_item_t *);
+
 /// @brief 获取当前路由表（目前版本可能只支持Ubuntu）
 /// @param  路由表项数组
 /// @param  数组大小
@@ -0,0 +1,72 @@
+//! 链路范围的不透明 LSA（类型 9）只在一个接口所连接的网络上洪泛，按接口分别保存。
+
+use std::{collections::HashMap, net::Ipv4Addr, time::Instant};
+
+use ospf_packet::lsa::{Lsa, LsaHeader, LsaIndex};
+
+use super::{lsa::LsaTimer, LsaDB};
+use crate::{constant::LsaMaxAge, database::ProtocolDB, guard, must};
+
+pub struct LinkDB {
+    /// 所属接口的地址
+    pub ip_addr: Ipv4Addr,
+    lsa_database: LsaDB,
+}
+
+impl LinkDB {
+    pub fn new(ip_addr: Ipv4Addr) -> Self {
+        Self {
+            ip_addr,
+            lsa_database: HashMap::new(),
+        }
+    }
+
+    pub async fn contains_lsa(&self, key: LsaIndex) -> bool {
+        self.lsa_database.contains_key(&key)
+    }
+
+    pub async fn get_lsa(&self, key: LsaIndex) -> Option<(Lsa, Instant, Instant)> {
+        self.lsa_database
+            .get(&key)
+            .map(|(lsa, timer, up)| (timer.update_lsa_age(lsa.clone()), timer.get_created(), *up))
+    }
+
+    pub fn get_all_lsa(&self) -> Vec<LsaHeader> {
+        self.lsa_database
+            .values()
+            .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header))
+            .filter(|header| header.ls_age != LsaMaxAge)
+            .collect()
+    }
+
+    pub async fn need_update(&self, header: LsaHeader) -> bool {
+        match self.get_lsa(header.into()).await {
+            Some((lsa, ..)) => lsa.header < header,
+            None => true,
+        }
+    }
+
+    pub async fn insert_lsa(&mut self, value: Lsa) {
+        must!(self.need_update(value.header).await);
+        let t = (LsaMaxAge - value.header.ls_age) as u64;
+        let timer = LsaTimer::new(t, refresh_lsa(self.ip_addr, value.clone()));
+        self.lsa_database
+            .insert(value.header.into(), (value, timer, Instant::now()));
+    }
+
+    pub async fn remove_lsa(&mut self, key: LsaIndex) {
+        self.lsa_database.remove(&key);
+    }
+
+    pub async fn lsa_has_sent(&mut self, lsa: &Lsa) {
+        guard!(Some(db) = self.lsa_database.get_mut(&lsa.header.into()));
+        db.2 = Instant::now();
+    }
+}
+
+async fn refresh_lsa(ip_addr: Ipv4Addr, lsa: Lsa) {
+    crate::log_warning!("lsa on {ip_addr} expired: {:?}", lsa.header);
+    let mut db = ProtocolDB::get().await;
+    guard!(Some(link) = db.links.get_mut(&ip_addr));
+    link.remove_lsa(lsa.header.into()).await;
+}
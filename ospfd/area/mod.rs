@@ -1,7 +1,9 @@
 mod backbone;
+mod link;
 mod lsa;
 mod tree;
 pub use backbone::BackboneDB;
+pub use link::LinkDB;
 pub use tree::ShortPathTree;
 
 use std::{
@@ -11,22 +13,24 @@ use std::{
 };
 
 use lazy_static::lazy_static;
-use ospf_packet::lsa::{types::AS_EXTERNAL_LSA, AsExternalLSA, Lsa, LsaHeader, LsaIndex};
+use ospf_packet::lsa::{types, AsExternalLSA, Lsa, LsaHeader, LsaIndex};
+use pnet::ipnetwork::Ipv4Network;
 use tokio::sync::Mutex;
 
 use lsa::LsaTimer;
 
 use crate::{
+    config::Config,
     constant::LsaMaxAge,
     database::{ProtocolDB, RoutingTableItem},
-    guard, must,
+    guard, must, prefix_list,
 };
 
 /// (lsa, created_at, updated_at)
 type LsaDB = HashMap<LsaIndex, (Lsa, LsaTimer, Instant)>;
 
 lazy_static! {
-    /// AS External LSA database
+    /// AS External LSA 和 AS 范围的不透明 LSA
     static ref STATIC_DB: Mutex<LsaDB> =
         Mutex::const_new(HashMap::new());
 }
@@ -35,6 +39,10 @@ pub struct Area {
     pub area_id: Ipv4Addr,
     /// ［地址、掩码］-> 宣告状态
     pub addr_range: BTreeMap<(Ipv4Addr, Ipv4Addr), bool>,
+    /// 过滤进入本区域的 Summary-LSA 的前缀列表
+    pub filter_in: Option<String>,
+    /// 过滤离开本区域的 Summary-LSA 的前缀列表
+    pub filter_out: Option<String>,
     lsa_database: LsaDB,
     short_path_tree: ShortPathTree,
     pub transit_capability: bool,
@@ -44,9 +52,24 @@ pub struct Area {
 
 impl Area {
     pub fn new(area_id: Ipv4Addr) -> Self {
+        let addr_range = Config::get()
+            .area_ranges
+            .iter()
+            .filter(|(id, ..)| *id == area_id)
+            .map(|(_, net, advertise)| ((net.network(), net.mask()), *advertise))
+            .collect();
+        let filter = |inbound: bool| {
+            let filters = Config::get().area_filters.iter();
+            filters
+                .filter(|(id, _, dir)| *id == area_id && *dir == inbound)
+                .map(|(_, name, _)| name.clone())
+                .next_back()
+        };
         Self {
             area_id,
-            addr_range: BTreeMap::new(),
+            addr_range,
+            filter_in: filter(true),
+            filter_out: filter(false),
             lsa_database: LsaDB::new(),
             short_path_tree: ShortPathTree::new(),
             transit_capability: false,
@@ -57,6 +80,23 @@ impl Area {
 }
 
 impl Area {
+    /// 包含该网络的最长区域范围：（［地址、掩码］，宣告状态）
+    pub fn range_of(&self, addr: Ipv4Addr, mask: Ipv4Addr) -> Option<((Ipv4Addr, Ipv4Addr), bool)> {
+        self.addr_range
+            .iter()
+            .filter(|((r_addr, r_mask), _)| addr & *r_mask == *r_addr && mask & *r_mask == *r_mask)
+            .max_by_key(|((_, r_mask), _)| u32::from(*r_mask))
+            .map(|(range, advertise)| (*range, *advertise))
+    }
+
+    /// 区域间前缀过滤：inbound 为 true 时检查进入本区域的网段，否则检查离开本区域的网段
+    pub fn filter_permits(&self, net: Ipv4Network, inbound: bool) -> bool {
+        let filter = if inbound { &self.filter_in } else { &self.filter_out };
+        filter
+            .as_deref()
+            .is_none_or(|name| prefix_list::permits(name, net))
+    }
+
     pub async fn get_all_external_lsa() -> Vec<(LsaHeader, AsExternalLSA)> {
         let db = STATIC_DB.lock().await;
         db.values()
@@ -67,6 +107,21 @@ impl Area {
             .collect()
     }
 
+    /// AS 范围的所有 LSA（包括不透明 LSA）
+    pub async fn get_all_as_lsa() -> Vec<LsaHeader> {
+        let db = STATIC_DB.lock().await;
+        db.values()
+            .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header))
+            .filter(|header| header.ls_age != LsaMaxAge)
+            .collect()
+    }
+
+    /// AS 范围的 LSA（包括不透明 LSA）
+    pub async fn get_as_lsa(key: LsaIndex) -> Option<Lsa> {
+        let db = STATIC_DB.lock().await;
+        db.get(&key).map(|(lsa, timer, _)| timer.update_lsa_age(lsa.clone()))
+    }
+
     fn m_external_db<T>(&self, db: T) -> Option<T> {
         if self.external_routing_capability {
             Some(db)
@@ -85,10 +140,10 @@ impl Area {
     fn m_insert_lsa(&mut self, db: &mut LsaDB, key: LsaIndex, value: Lsa) {
         let t = (LsaMaxAge - value.header.ls_age) as u64;
         let timer = LsaTimer::new(t, refresh_lsa(self.area_id, value.clone()));
-        if self.external_routing_capability && matches!(key.ls_type, AS_EXTERNAL_LSA) {
+        if self.external_routing_capability && types::is_as_scope(key.ls_type) {
             db.insert(key, (value, timer, Instant::now()));
         } else {
-            assert!(!matches!(key.ls_type, AS_EXTERNAL_LSA));
+            assert!(!types::is_as_scope(key.ls_type));
             self.lsa_database
                 .insert(key, (value, timer, Instant::now()));
         }
@@ -115,13 +170,13 @@ impl Area {
         let my = self
             .lsa_database
             .values()
-            .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header.clone()))
+            .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header))
             .filter(|header| header.ls_age != LsaMaxAge);
         if self.external_routing_capability {
             let db = STATIC_DB.lock().await;
             my.chain(
                 db.values()
-                    .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header.clone()))
+                    .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header))
                     .filter(|header| header.ls_age != LsaMaxAge),
             )
             .collect()
@@ -133,7 +188,7 @@ impl Area {
     pub fn get_all_area_lsa(&self) -> Vec<LsaHeader> {
         self.lsa_database
             .values()
-            .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header.clone()))
+            .map(|(lsa, timer, _)| timer.update_lsa_age_header(lsa.header))
             .filter(|header| header.ls_age != LsaMaxAge)
             .collect()
     }
@@ -151,11 +206,13 @@ impl Area {
 
     pub async fn insert_lsa(&mut self, value: Lsa) {
         must!(self.need_update(value.header).await);
+        crate::opaque::on_insert(self.area_id, &value);
         let mut db = STATIC_DB.lock().await;
         self.m_insert_lsa(&mut db, value.header.into(), value);
     }
 
     pub async fn remove_lsa(&mut self, key: LsaIndex) {
+        crate::opaque::on_remove(self.area_id, key);
         let mut db = STATIC_DB.lock().await;
         self.m_remove_lsa(&mut db, key);
     }
@@ -161,6 +161,7 @@ impl ShortPathTree {
                     lsa_origin: node.lsa.header.into(),
                     next_hop,
                     ad_router: node.lsa.header.advertising_router,
+                    tag: 0,
                 })
             })
             .collect()
@@ -199,6 +200,7 @@ impl ShortPathTree {
                     lsa_origin: header.into(),
                     next_hop: br.next_hops[0],
                     ad_router: header.advertising_router,
+                    tag: 0,
                 })
             }).collect()
     }
@@ -2,7 +2,7 @@
 
 use std::net::{IpAddr, Ipv4Addr};
 
-use ospf_packet::OspfPacket;
+use ospf_packet::{DecodeError, OspfPacket};
 use pnet::datalink::Channel::Ethernet; // 导入以太网通道
 use pnet::datalink::{self, DataLinkReceiver, NetworkInterface}; // 导入datalink模块中的相关项
 use pnet::packet::ethernet::{EtherTypes, EthernetPacket}; // 导入以太网数据包相关项
@@ -12,12 +12,9 @@ use pnet::packet::Packet; // 导入数据包trait
 
 use crate::constant::{AllDRouters, AllSPFRouters};
 use crate::daemon::Runnable;
-use crate::{log_error, log_success};
-
-#[cfg(debug_assertions)]
-use crate::log;
-#[cfg(debug_assertions)]
-use ospf_packet::{message_type_string, packet, FromBuf};
+use crate::{logging::Subsystem, statistics};
+use crate::{guard, log_error, log_success};
+use ospf_packet::{message_type_string, packet, TryFromBuf};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ChannelError {
@@ -32,6 +29,7 @@ pub type OspfHandler = Box<dyn FnMut(Ipv4Addr, Ipv4Addr, OspfPacket) + Send>;
 
 #[doc = "CaptureOspfDaemon: OSPF数据包捕获守护协程"]
 pub struct CaptureOspfDaemon {
+    name: String,
     ips: Vec<Ipv4Addr>,
     receiver: Receiver,
     handler: OspfHandler,
@@ -65,6 +63,7 @@ impl CaptureOspfDaemon {
         };
 
         Ok(Self {
+            name: iface.name.clone(),
             ips,
             receiver,
             handler,
@@ -75,7 +74,12 @@ impl CaptureOspfDaemon {
         ip == AllSPFRouters || ip == AllDRouters || ips.iter().any(|&i| i == ip)
     }
 
-    fn handle_packet(ips: &Vec<Ipv4Addr>, handler: &mut OspfHandler, ethernet: &EthernetPacket) {
+    fn handle_packet(
+        name: &str,
+        ips: &Vec<Ipv4Addr>,
+        handler: &mut OspfHandler,
+        ethernet: &EthernetPacket,
+    ) {
         // 对Ipv4的包按层解析
         match ethernet.get_ethertype() {
             EtherTypes::Ipv4 => {
@@ -91,17 +95,24 @@ impl CaptureOspfDaemon {
                     match header.get_next_level_protocol() {
                         IpNextHeaderProtocols::OspfigP => {
                             // 如果是OSPF协议
-                            let packet =
-                                OspfPacket::new(header.payload()).expect("Bad Ospf Packet");
-                            // debug
-                            #[cfg(debug_assertions)]
-                            log!(
+                            let Some(packet) = OspfPacket::new(header.payload()) else {
+                                // 连 OSPF 报头都不完整
+                                let e = DecodeError::Truncated {
+                                    need: OspfPacket::minimum_packet_size(),
+                                    remaining: header.payload().len(),
+                                };
+                                statistics::discarded(name, (&e).into());
+                                return;
+                            };
+                            crate::log_at!(
+                                Debug, Subsystem::of_packet(packet.get_message_type());
                                 "interface({}) receives packet from {}: {} ({} bytes)",
                                 header.get_destination(),
                                 header.get_source(),
                                 message_type_string(packet.get_message_type()),
                                 packet.get_length(),
                             );
+                            statistics::received(name, packet.get_message_type());
                             handler(header.get_source(), header.get_destination(), packet);
                         }
                         _ => (), // 忽略其他协议
@@ -117,8 +128,8 @@ impl Runnable for CaptureOspfDaemon {
     fn run(&mut self) {
         match self.receiver.next() {
             Ok(packet) => {
-                let packet = EthernetPacket::new(packet).expect("Bad Ethernet Packet"); // 解析以太网数据包
-                Self::handle_packet(&self.ips, &mut self.handler, &packet); // 处理接收到的数据包
+                guard!(Some(packet) = EthernetPacket::new(packet)); // 解析以太网数据包
+                Self::handle_packet(&self.name, &self.ips, &mut self.handler, &packet); // 处理接收到的数据包
             }
             Err(e) => {
                 // 如果读取数据包时发生错误，打印错误消息
@@ -129,7 +140,6 @@ impl Runnable for CaptureOspfDaemon {
 }
 
 #[allow(unused)]
-#[cfg(debug_assertions)]
 #[doc = "打印接收到的数据包"]
 pub fn echo_handler(source: Ipv4Addr, destination: Ipv4Addr, packet: OspfPacket) {
     let pkg_str = format!(
@@ -138,27 +148,27 @@ pub fn echo_handler(source: Ipv4Addr, destination: Ipv4Addr, packet: OspfPacket)
     );
     match packet.get_message_type() {
         packet::types::HELLO_PACKET => {
-            let hello_packet = packet::HelloPacket::from_buf(&mut packet.payload());
-            log!("{pkg_str} Hello packet: {:#?}", hello_packet);
+            let hello_packet = packet::HelloPacket::try_from_buf(&mut packet.payload());
+            crate::log_at!(Debug, Subsystem::of_packet(packet.get_message_type()); "{pkg_str} Hello packet: {:#?}", hello_packet);
         }
         packet::types::DB_DESCRIPTION => {
-            let db_description = packet::DBDescription::from_buf(&mut packet.payload());
-            log!("{pkg_str} DB Description packet: {:#?}", db_description);
+            let db_description = packet::DBDescription::try_from_buf(&mut packet.payload());
+            crate::log_at!(Debug, Subsystem::of_packet(packet.get_message_type()); "{pkg_str} DB Description packet: {:#?}", db_description);
         }
         packet::types::LS_REQUEST => {
-            let ls_request = packet::LSRequest::from_buf(&mut packet.payload());
-            log!("{pkg_str} LS Request packet: {:#?}", ls_request);
+            let ls_request = packet::LSRequest::try_from_buf(&mut packet.payload());
+            crate::log_at!(Debug, Subsystem::of_packet(packet.get_message_type()); "{pkg_str} LS Request packet: {:#?}", ls_request);
         }
         packet::types::LS_UPDATE => {
-            let ls_update = packet::LSUpdate::from_buf(&mut packet.payload());
-            log!("{pkg_str} LS Update packet: {:#?}", ls_update);
+            let ls_update = packet::LSUpdate::try_from_buf(&mut packet.payload());
+            crate::log_at!(Debug, Subsystem::of_packet(packet.get_message_type()); "{pkg_str} LS Update packet: {:#?}", ls_update);
         }
         packet::types::LS_ACKNOWLEDGE => {
-            let ls_acknowledge = packet::LSAcknowledge::from_buf(&mut packet.payload());
-            log!("{pkg_str} LS Acknowledge packet: {:#?}", ls_acknowledge);
+            let ls_acknowledge = packet::LSAcknowledge::try_from_buf(&mut packet.payload());
+            crate::log_at!(Debug, Subsystem::of_packet(packet.get_message_type()); "{pkg_str} LS Acknowledge packet: {:#?}", ls_acknowledge);
         }
         _ => {
-            log!("{pkg_str} Unknown packet type");
+            crate::log_at!(Debug, Subsystem::of_packet(packet.get_message_type()); "{pkg_str} Unknown packet type");
         }
     }
 }
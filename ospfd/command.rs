@@ -12,10 +12,18 @@ use crossterm::{
     terminal::{self, Clear, ClearType},
 };
 use lazy_static::lazy_static;
+use ospf_packet::lsa::{self, LsaHeader};
+use pnet::ipnetwork::Ipv4Network;
 use trie_rs::{Trie, TrieBuilder};
 
 use crate::{
-    area::Area, database::ProtocolDB, guard, interface::InterfaceEvent, log, log_success, must,
+    area::Area,
+    config::{self, Config},
+    database::{Ipv4AddrMask, ProtocolDB},
+    gen_lsa, gr, guard,
+    interface, json,
+    json::{Json, ToJson},
+    echo, log_error, log_success, logging, must, router_info, sr, statistics, stub_router, te,
 };
 
 use tokio::signal;
@@ -71,11 +79,15 @@ macro_rules! command {
         $(arg: $ka:literal ($va:literal) => $fa:expr;)?
         $($k:literal ($v:literal) => $f:expr;)*
     ) => {{
-        #[allow(unused_mut, unused_assignments)]
+        // 没有对应的命令时这些变量不会被修改
+        #[allow(unused_mut)]
         let mut desc = HashMap::<&str, &str>::new();
+        #[allow(unused_mut)]
         let mut handlers =
             HashMap::<&str, Box<dyn Fn() -> &'static CommandSet + Sync>>::new();
+        #[allow(unused_mut, unused_assignments)]
         let mut handle_enter = Option::<Box<dyn Fn() + Sync>>::None;
+        #[allow(unused_mut, unused_assignments)]
         let mut arbitrary =
             Option::<Box<dyn Fn(&str) -> &'static CommandSet + Sync>>::None;
         $(
@@ -94,11 +106,25 @@ macro_rules! command {
     }};
 }
 
+/// 读取命令解析时保存在 static mut 中的值，通过裸指针访问，不产生对可变静态变量的引用。
+/// 命令只在输入线程中依次解析，不会同时读写
+macro_rules! saved {
+    ($s:ident) => {
+        (*std::ptr::addr_of!($s))
+    };
+}
+
 lazy_static! {
     static ref ROOT: CommandSet = command! {
         enter: ("run nothing") => || {};
         "display"("display something...") => parse_display;
         "interface"("interface setting...") => parse_interface;
+        "router-id"("router id setting") => parse_router_id;
+        "area"("area setting...") => parse_area;
+        "max-metric"("stub router advertisement") => parse_max_metric;
+        "debug"("turn on debug logs...") => parse_debug;
+        "undo"("turn off debug logs...") => parse_undo;
+        "clear"("clear something...") => parse_clear;
         "exit"("exit ospfd") => parse_exit;
     };
 }
@@ -121,14 +147,14 @@ fn display_help(desc: &HashMap<&str, &str>) {
     let mut vec: Vec<_> = desc.iter().collect();
     vec.sort_by_key(|(&k, _)| k);
     for (k, v) in vec {
-        crate::log!("  {:<width$} - {}", k, v, width = max_key_len);
+        crate::echo!("  {:<width$} - {}", k, v, width = max_key_len);
     }
 }
 
 /// 根据用户输入的命令字符串进行解析并执行对应命令
 pub fn parse_cmd(raw: String) {
     if !raw.ends_with('\n') {
-        log!();
+        echo!();
     }
     let raw = raw.trim().to_lowercase();
     let mut list = raw.split_ascii_whitespace();
@@ -179,6 +205,21 @@ macro_rules! block_on {
     };
 }
 
+/// 各 display 命令的 json 修饰：输出一行 JSON
+fn parse_display_json(f: fn() -> Json) -> &'static CommandSet {
+    static mut JSON: Option<CommandSet> = None;
+    unsafe {
+        JSON = Some(command! {
+            enter: ("output in json") => move || {
+                if let Err(e) = json::write_line(&mut stdout(), &f()) {
+                    log_error!("failed to write json: {}", e);
+                }
+            };
+        });
+        saved!(JSON).as_ref().unwrap()
+    }
+}
+
 /// display 相关命令
 fn parse_display() -> &'static CommandSet {
     lazy_static! {
@@ -186,6 +227,8 @@ fn parse_display() -> &'static CommandSet {
             "routing"("display routing table") => parse_display_routing;
             "peer"("display ospf neighbors") => parse_display_peer;
             "lsdb"("display ospf link state database") => parse_display_lsdb;
+            "debugging"("display log level and debugged subsystems") => parse_display_debugging;
+            "ospf"("display ospf information...") => parse_display_ospf;
         };
     }
     &DISPLAY
@@ -194,13 +237,32 @@ fn parse_display() -> &'static CommandSet {
 fn parse_display_routing() -> &'static CommandSet {
     lazy_static! {
         static ref DISPLAY: CommandSet = command! {
-            enter: ("display routing table") => || log!("{}", block_on!(ProtocolDB::get()).routing_table);
+            enter: ("display routing table") => display_routing;
             "system" ("display system routing table") => parse_display_routing_system;
+            "raw"("show router ids instead of hostnames") => parse_display_routing_raw;
+            "json"("output in json") => || parse_display_json(display_routing_json);
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_routing_raw() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display routing table with router ids") => || router_info::raw(display_routing);
         };
     }
     &DISPLAY
 }
 
+fn display_routing() {
+    echo!("{}", block_on!(ProtocolDB::get()).routing_table);
+}
+
+fn display_routing_json() -> Json {
+    block_on!(ProtocolDB::get()).routing_table.to_json()
+}
+
 fn parse_display_routing_system() -> &'static CommandSet {
     lazy_static! {
         static ref DISPLAY: CommandSet = command! {
@@ -213,45 +275,719 @@ fn parse_display_routing_system() -> &'static CommandSet {
 fn parse_display_peer() -> &'static CommandSet {
     lazy_static! {
         static ref DISPLAY: CommandSet = command! {
-            enter: ("display ospf neighbors") => || {
-                log!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
-                log!("\t\tNeighbors");
-                ProtocolDB::get_interfaces_impl().iter().for_each(|iface| {
-                    log!("Area {} interface {}({})'s neighbors", iface.area_id, iface.ip_addr, iface.interface_name);
-                    iface.neighbors.values().for_each(|n| log!("{}", n));
-                });
-            };
+            enter: ("display ospf neighbors") => display_peer;
+            "raw"("show router ids instead of hostnames") => parse_display_peer_raw;
+            "json"("output in json") => || parse_display_json(display_peer_json);
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_peer_raw() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display ospf neighbors with router ids") => || router_info::raw(display_peer);
         };
     }
     &DISPLAY
 }
 
+fn display_peer() {
+    echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+    echo!("\t\tNeighbors");
+    ProtocolDB::get_interfaces_impl().iter().for_each(|iface| {
+        echo!("Area {} interface {}({})'s neighbors", iface.area_id, iface.ip_addr, iface.interface_name);
+        iface.neighbors.values().for_each(|n| echo!("{}", n));
+    });
+}
+
+fn display_peer_json() -> Json {
+    let interfaces: Vec<_> = ProtocolDB::get_interfaces_impl()
+        .iter()
+        .map(|iface| {
+            let mut neighbors: Vec<_> = iface.neighbors.values().collect();
+            neighbors.sort_by_key(|n| n.ip_addr);
+            json!({
+                "interface": &iface.interface_name,
+                "address": iface.ip_addr,
+                "area": iface.area_id,
+                "neighbors": neighbors.into_iter().collect::<Json>(),
+            })
+        })
+        .collect();
+    json!({ "router_id": ProtocolDB::get_router_id(), "interfaces": interfaces })
+}
+
 fn parse_display_lsdb() -> &'static CommandSet {
     lazy_static! {
         static ref DISPLAY: CommandSet = command! {
-            enter: ("display ospf link state database") => || {
-                log!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
-                log!("\t\tLink State Database");
-                block_on!(ProtocolDB::get()).areas.values().for_each(|area| {
-                    let mut lsa = area.get_all_area_lsa();
-                    must!(lsa.len() > 0);
-                    log!("\t\t\tArea: {}", area.area_id);
-                    log!("Type      LinkState ID    AdvRouter       Age   Len   Sequence");
-                    lsa.sort_by_key(|lsa| lsa.ls_type);
-                    lsa.into_iter().for_each(|lsa| log!("{}", lsa));
-                });
-                let mut lsa = block_on!(Area::get_all_external_lsa());
-                must!(lsa.len() > 0);
-                log!("\t\tAS External Database");
-                log!("Type      LinkState ID    AdvRouter       Age   Len   Sequence");
-                lsa.sort_by_key(|(lsa, _)| lsa.ls_type);
-                lsa.into_iter().for_each(|(lsa, _)| log!("{}", lsa));
+            enter: ("display ospf link state database") => display_lsdb;
+            "raw"("show router ids instead of hostnames") => parse_display_lsdb_raw;
+            "json"("output in json with decoded lsa bodies") => || parse_display_json(display_lsdb_json);
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_lsdb_raw() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display ospf link state database with router ids") => || router_info::raw(display_lsdb);
+        };
+    }
+    &DISPLAY
+}
+
+/// 与 LsaHeader 的显示相同，但生成路由器显示为主机名
+fn display_lsa(lsa: &LsaHeader) {
+    echo!(
+        "{:<9} {:<15} {:<15} {:<5} {:<5} {:<10X}",
+        lsa::types::to_string(lsa.ls_type),
+        lsa.link_state_id,
+        router_info::name(lsa.advertising_router),
+        lsa.ls_age,
+        lsa.length,
+        lsa.ls_sequence_number,
+    );
+}
+
+fn display_lsdb() {
+    echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+    echo!("\t\tLink State Database");
+    block_on!(ProtocolDB::get()).areas.values().for_each(|area| {
+        let mut lsa = area.get_all_area_lsa();
+        must!(lsa.len() > 0);
+        echo!("\t\t\tArea: {}", area.area_id);
+        echo!("Type      LinkState ID    AdvRouter       Age   Len   Sequence");
+        lsa.sort_by_key(|lsa| lsa.ls_type);
+        lsa.iter().for_each(display_lsa);
+    });
+    block_on!(ProtocolDB::get()).links.values().for_each(|link| {
+        let mut lsa = link.get_all_lsa();
+        must!(!lsa.is_empty());
+        echo!("\t\t\tLink Local: {}", link.ip_addr);
+        echo!("Type      LinkState ID    AdvRouter       Age   Len   Sequence");
+        lsa.sort_by_key(|lsa| lsa.link_state_id);
+        lsa.iter().for_each(display_lsa);
+    });
+    let mut lsa = block_on!(Area::get_all_as_lsa());
+    must!(lsa.len() > 0);
+    echo!("\t\tAS External Database");
+    echo!("Type      LinkState ID    AdvRouter       Age   Len   Sequence");
+    lsa.sort_by_key(|lsa| lsa.ls_type);
+    lsa.iter().for_each(display_lsa);
+}
+
+fn display_lsdb_json() -> Json {
+    let sorted = |mut headers: Vec<LsaHeader>| {
+        headers.sort_by_key(|h| (h.ls_type, h.link_state_id, h.advertising_router));
+        headers
+    };
+    let db = block_on!(ProtocolDB::get());
+    let mut areas: Vec<_> = db.areas.values().collect();
+    areas.sort_by_key(|area| area.area_id);
+    let areas: Vec<_> = areas
+        .into_iter()
+        .map(|area| {
+            let lsa: Vec<_> = sorted(area.get_all_area_lsa())
+                .into_iter()
+                .filter_map(|h| block_on!(area.get_lsa(h.into())))
+                .map(|(lsa, ..)| lsa.to_json())
+                .collect();
+            json!({ "area": area.area_id, "lsas": lsa })
+        })
+        .collect();
+    let mut links: Vec<_> = db.links.values().collect();
+    links.sort_by_key(|link| link.ip_addr);
+    let links: Vec<_> = links
+        .into_iter()
+        .map(|link| {
+            let lsa: Vec<_> = sorted(link.get_all_lsa())
+                .into_iter()
+                .filter_map(|h| block_on!(link.get_lsa(h.into())))
+                .map(|(lsa, ..)| lsa.to_json())
+                .collect();
+            json!({ "interface_address": link.ip_addr, "lsas": lsa })
+        })
+        .collect();
+    drop(db);
+    let external: Vec<_> = sorted(block_on!(Area::get_all_as_lsa()))
+        .into_iter()
+        .filter_map(|h| block_on!(Area::get_as_lsa(h.into())))
+        .map(|lsa| lsa.to_json())
+        .collect();
+    json!({
+        "router_id": ProtocolDB::get_router_id(),
+        "areas": areas,
+        "links": links,
+        "as_external": external,
+    })
+}
+
+fn parse_display_ospf() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            "te-database"("display traffic engineering database") => parse_display_te;
+            "segment-routing"("display segment routing database") => parse_display_sr;
+            "graceful-restart"("display graceful restart status") => parse_display_gr;
+            "max-metric"("display stub router status") => parse_display_max_metric;
+            "range"("display area address ranges") => parse_display_range;
+            "external"("display originated external routes") => parse_display_external;
+            "prefix-list"("display prefix lists and area filters") => parse_display_prefix_list;
+            "route-map"("display route policies") => parse_display_route_map;
+            "interface"("display ospf interfaces") => parse_display_interface;
+            "statistics"("display protocol statistics") => parse_display_statistics;
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_statistics() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display protocol statistics") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tStatistics");
+                echo!("{}", statistics::get());
+            };
+            "json"("output in json") => || parse_display_json(|| statistics::get().to_json());
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_te() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display traffic engineering database") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tTraffic Engineering Database");
+                let mut area = None;
+                for router in te::database() {
+                    if area != Some(router.area_id) {
+                        area = Some(router.area_id);
+                        echo!("\t\t\tArea: {}", router.area_id);
+                    }
+                    echo!("{}", router);
+                }
+            };
+            "json"("output in json") => || parse_display_json(|| te::database().iter().collect());
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_gr() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display graceful restart status") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tGraceful Restart");
+                let config = Config::get().graceful_restart;
+                echo!("Grace period {}s, helper {}", config.grace_period, if config.helper { "enabled" } else { "disabled" });
+                gr::status().iter().for_each(|line| echo!("{}", line));
+            };
+            "json"("output in json") => || parse_display_json(gr::status_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_max_metric() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display stub router status") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tStub Router");
+                stub_router::status().iter().for_each(|line| echo!("{}", line));
             };
+            "json"("output in json") => || parse_display_json(stub_router::status_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_range() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display area address ranges") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tArea Address Ranges");
+                let db = block_on!(ProtocolDB::get());
+                let mut areas: Vec<_> = db.areas.values().collect();
+                areas.sort_by_key(|area| area.area_id);
+                for area in areas.into_iter().filter(|area| !area.addr_range.is_empty()) {
+                    echo!("\t\t\tArea: {}", area.area_id);
+                    for (&(addr, mask), advertise) in area.addr_range.iter() {
+                        let status = if *advertise { "advertise" } else { "not-advertise" };
+                        echo!("{:<20}{}", Ipv4AddrMask::from(addr, mask).to_string(), status);
+                    }
+                }
+            };
+            "json"("output in json") => || parse_display_json(display_range_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn display_range_json() -> Json {
+    let db = block_on!(ProtocolDB::get());
+    let mut areas: Vec<_> = db.areas.values().collect();
+    areas.sort_by_key(|area| area.area_id);
+    areas
+        .into_iter()
+        .filter(|area| !area.addr_range.is_empty())
+        .map(|area| {
+            let ranges: Vec<_> = area
+                .addr_range
+                .iter()
+                .map(|(&(addr, mask), advertise)| {
+                    json!({ "range": Ipv4AddrMask::from(addr, mask).to_string(), "advertise": *advertise })
+                })
+                .collect();
+            json!({ "area": area.area_id, "ranges": ranges })
+        })
+        .collect()
+}
+
+fn parse_display_external() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display originated external routes") => || {
+                let routes = block_on!(ProtocolDB::get()).external_routes.clone();
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tOriginated External Routes");
+                echo!("Network             Metric    Type  Tag");
+                for (route, count) in gen_lsa::summarize_external(&routes).routes {
+                    let summary = if count > 0 { format!("summary of {} routes", count) } else { String::new() };
+                    echo!("{:<20}{:<10}E{:<5}{:<11}{}", route.network.to_string(), route.metric, route.metric_type, route.tag, summary);
+                }
+            };
+            "json"("output in json") => || parse_display_json(display_external_json);
+
         };
     }
     &DISPLAY
 }
 
+fn display_external_json() -> Json {
+    let routes = block_on!(ProtocolDB::get()).external_routes.clone();
+    gen_lsa::summarize_external(&routes)
+        .routes
+        .into_iter()
+        .map(|(route, count)| {
+            json!({
+                "network": route.network,
+                "metric": route.metric,
+                "metric_type": route.metric_type,
+                "tag": route.tag,
+                "summarized_routes": count,
+            })
+        })
+        .collect()
+}
+
+fn parse_display_prefix_list() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display prefix lists and area filters") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tPrefix Lists");
+                for (name, list) in Config::get().prefix_lists.iter() {
+                    echo!("prefix-list {}:\n{}", name, list);
+                }
+                let db = block_on!(ProtocolDB::get());
+                let mut areas: Vec<_> = db.areas.values().collect();
+                areas.sort_by_key(|area| area.area_id);
+                for area in areas {
+                    if let Some(ref name) = area.filter_in {
+                        echo!("Area {} filter-list in: {}", area.area_id, name);
+                    }
+                    if let Some(ref name) = area.filter_out {
+                        echo!("Area {} filter-list out: {}", area.area_id, name);
+                    }
+                }
+            };
+            "json"("output in json") => || parse_display_json(display_prefix_list_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn display_prefix_list_json() -> Json {
+    let lists: Vec<_> = Config::get()
+        .prefix_lists
+        .iter()
+        .map(|(name, list)| json!({ "name": name, "entries": list.to_json() }))
+        .collect();
+    let db = block_on!(ProtocolDB::get());
+    let mut areas: Vec<_> = db.areas.values().collect();
+    areas.sort_by_key(|area| area.area_id);
+    let mut filters = vec![];
+    for area in areas {
+        for (direction, name) in [("in", &area.filter_in), ("out", &area.filter_out)] {
+            guard!(Some(name) = name; continue);
+            filters.push(json!({ "area": area.area_id, "direction": direction, "prefix_list": name }));
+        }
+    }
+    json!({ "prefix_lists": lists, "area_filters": filters })
+}
+
+fn parse_display_route_map() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display route policies") => || {
+                let config = Config::get();
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tRoute Maps");
+                for (name, map) in config.route_maps.iter() {
+                    echo!("route-map {}:\n{}", name, map);
+                }
+                if let Some(ref name) = config.redistribute_route_map {
+                    echo!("redistribute static route-map: {}", name);
+                }
+                if let Some(ref name) = config.fib_filter {
+                    echo!("fib-filter route-map: {}", name);
+                }
+            };
+            "json"("output in json") => || parse_display_json(display_route_map_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn display_route_map_json() -> Json {
+    let config = Config::get();
+    let maps: Vec<_> = config
+        .route_maps
+        .iter()
+        .map(|(name, map)| json!({ "name": name, "clauses": map.to_json() }))
+        .collect();
+    json!({
+        "route_maps": maps,
+        "redistribute": config.redistribute_route_map.clone(),
+        "fib_filter": config.fib_filter.clone(),
+    })
+}
+
+fn parse_display_interface() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display ospf interfaces") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tInterfaces (reference bandwidth {} Mbit/s)", Config::get().reference_bandwidth());
+                echo!("Interface   Address          Area             State      Cost   Speed");
+                for iface in ProtocolDB::get_interfaces_impl() {
+                    let speed = iface.speed.map_or("unknown".to_string(), |s| format!("{} Mbit/s", s));
+                    let source = if iface.cost_override.is_some() { "configured" } else { "auto" };
+                    echo!(
+                        "{:<12}{:<17}{:<17}{:<11}{:<7}{} ({})",
+                        iface.interface_name,
+                        iface.ip_addr.to_string(),
+                        iface.area_id.to_string(),
+                        format!("{:?}", iface.state),
+                        iface.cost,
+                        speed,
+                        source
+                    );
+                }
+            };
+            "json"("output in json") => || parse_display_json(display_interface_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn display_interface_json() -> Json {
+    let interfaces = ProtocolDB::get_interfaces_impl();
+    json!({
+        "router_id": ProtocolDB::get_router_id(),
+        "reference_bandwidth": Config::get().reference_bandwidth(),
+        "interfaces": interfaces.iter().map(|iface| &**iface).collect::<Json>(),
+    })
+}
+
+fn parse_display_sr() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display segment routing database") => || {
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tSegment Routing Database");
+                let mut area = None;
+                for router in sr::database() {
+                    if area != Some(router.area_id) {
+                        area = Some(router.area_id);
+                        echo!("\t\t\tArea: {}", router.area_id);
+                    }
+                    echo!("{}", router);
+                }
+            };
+            "lfib"("display label forwarding table computed from spf") => parse_display_lfib;
+            "json"("output in json") => || parse_display_json(|| sr::database().iter().collect());
+        };
+    }
+    &DISPLAY
+}
+
+fn parse_display_lfib() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display label forwarding table computed from spf") => || {
+                let interfaces = ProtocolDB::get_interfaces_impl();
+                let lfib = sr::lfib(&interfaces, &block_on!(ProtocolDB::get()).routing_table);
+                echo!("\tOSPF with Router ID: {}", ProtocolDB::get_router_id());
+                echo!("\t\tLabel Forwarding Table");
+                echo!("In Label  Action      FEC                  Next Hop        Interface");
+                lfib.iter().for_each(|entry| echo!("{}", entry));
+            };
+            "json"("output in json") => || parse_display_json(display_lfib_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn display_lfib_json() -> Json {
+    let interfaces = ProtocolDB::get_interfaces_impl();
+    let lfib = sr::lfib(&interfaces, &block_on!(ProtocolDB::get()).routing_table);
+    lfib.iter().collect()
+}
+
+/// router-id 相关命令
+fn parse_router_id() -> &'static CommandSet {
+    lazy_static! {
+        static ref ROUTER_ID: CommandSet = command! {
+            arg: "<router_id>"("change router id, self-originated lsa will be flushed") => parse_router_id_set;
+        };
+    }
+    &ROUTER_ID
+}
+
+fn parse_router_id_set(arg: &str) -> &'static CommandSet {
+    static mut ROUTER_ID: Option<CommandSet> = None;
+    let arg = arg.to_string();
+    unsafe {
+        ROUTER_ID = Some(command! {
+            enter: ("changing router id") => move || {
+                guard!(Ok(id) = arg.parse(); error: "bad router_id: {arg}");
+                block_on!(ProtocolDB::set_router_id(id));
+            };
+        });
+        saved!(ROUTER_ID).as_ref().unwrap()
+    }
+}
+
+fn parse_display_debugging() -> &'static CommandSet {
+    lazy_static! {
+        static ref DISPLAY: CommandSet = command! {
+            enter: ("display log level and debugged subsystems") => || {
+                echo!("Log level: {}", logging::level());
+                let debugging: Vec<_> = logging::debugging().iter().map(|s| s.name()).collect();
+                echo!("Debugging: {}", if debugging.is_empty() { "none".to_string() } else { debugging.join(" ") });
+            };
+            "json"("output in json") => || parse_display_json(display_debugging_json);
+
+        };
+    }
+    &DISPLAY
+}
+
+fn display_debugging_json() -> Json {
+    let debugging: Vec<_> = logging::debugging().iter().map(|s| s.name()).collect();
+    json!({ "level": logging::level().to_string(), "debugging": debugging })
+}
+
+/// clear 相关命令
+fn parse_clear() -> &'static CommandSet {
+    lazy_static! {
+        static ref CLEAR: CommandSet = command! {
+            "ospf"("clear ospf information...") => parse_clear_ospf;
+        };
+    }
+    &CLEAR
+}
+
+fn parse_clear_ospf() -> &'static CommandSet {
+    lazy_static! {
+        static ref CLEAR: CommandSet = command! {
+            "statistics"("reset protocol statistics") => parse_clear_statistics;
+        };
+    }
+    &CLEAR
+}
+
+fn parse_clear_statistics() -> &'static CommandSet {
+    lazy_static! {
+        static ref CLEAR: CommandSet = command! {
+            enter: ("reset protocol statistics") => || {
+                statistics::clear();
+                log_success!("statistics are cleared");
+            };
+        };
+    }
+    &CLEAR
+}
+
+/// debug 相关命令
+fn parse_debug() -> &'static CommandSet {
+    lazy_static! {
+        static ref DEBUG: CommandSet = command! {
+            "ospf"("turn on debug logs of a subsystem") => || parse_debug_ospf(true);
+        };
+    }
+    &DEBUG
+}
+
+fn parse_undo() -> &'static CommandSet {
+    lazy_static! {
+        static ref UNDO: CommandSet = command! {
+            "debug"("turn off debug logs...") => parse_undo_debug;
+        };
+    }
+    &UNDO
+}
+
+fn parse_undo_debug() -> &'static CommandSet {
+    lazy_static! {
+        static ref UNDO: CommandSet = command! {
+            "ospf"("turn off debug logs of a subsystem") => || parse_debug_ospf(false);
+        };
+    }
+    &UNDO
+}
+
+fn parse_debug_ospf(on: bool) -> &'static CommandSet {
+    static mut DEBUG: Option<CommandSet> = None;
+    static mut ON: bool = false;
+    unsafe {
+        ON = on;
+        DEBUG = Some(command! {
+            arg: "<subsystem>"("hello, dd, lsu, flooding, spf, fib, ism, nsm, general or all") => |arg| parse_debug_ospf_set(ON, arg);
+        });
+        saved!(DEBUG).as_ref().unwrap()
+    }
+}
+
+fn parse_debug_ospf_set(on: bool, arg: &str) -> &'static CommandSet {
+    static mut DEBUG: Option<CommandSet> = None;
+    let arg = arg.to_string();
+    unsafe {
+        DEBUG = Some(command! {
+            enter: ("changing debug logs") => move || {
+                let subsystem = match arg.as_str() {
+                    "all" => None,
+                    _ => {
+                        guard!(Ok(subsystem) = arg.parse(); error: "bad subsystem: {arg}");
+                        Some(subsystem)
+                    }
+                };
+                logging::set_debug(subsystem, on);
+                log_success!("debug logs of {} are {}", arg, if on { "on" } else { "off" });
+            };
+        });
+        saved!(DEBUG).as_ref().unwrap()
+    }
+}
+
+fn parse_max_metric() -> &'static CommandSet {
+    lazy_static! {
+        static ref MAX_METRIC: CommandSet = command! {
+            "on"("advertise max metric to drain traffic") => || parse_max_metric_set(true);
+            "off"("advertise normal metric") => || parse_max_metric_set(false);
+        };
+    }
+    &MAX_METRIC
+}
+
+fn parse_max_metric_set(on: bool) -> &'static CommandSet {
+    static mut MAX_METRIC: Option<CommandSet> = None;
+    unsafe {
+        MAX_METRIC = Some(command! {
+            enter: ("changing max metric mode") => move || {
+                stub_router::set(on);
+                log_success!("max metric is {}", if on { "on" } else { "off" });
+            };
+        });
+        saved!(MAX_METRIC).as_ref().unwrap()
+    }
+}
+
+/// area 相关命令
+fn parse_area() -> &'static CommandSet {
+    lazy_static! {
+        static ref AREA: CommandSet = command! {
+            arg: "<area_id>"("area setting...") => parse_area_id;
+        };
+    }
+    &AREA
+}
+
+fn parse_area_id(area_id: &str) -> &'static CommandSet {
+    static mut AREA: Option<CommandSet> = None;
+    static mut AREA_ID: String = String::new();
+    unsafe {
+        AREA_ID = area_id.to_string();
+        AREA = Some(command! {
+            "range"("area address range setting") => || parse_area_range(saved!(AREA_ID).clone());
+        });
+        saved!(AREA).as_ref().unwrap()
+    }
+}
+
+fn parse_area_range(area_id: String) -> &'static CommandSet {
+    static mut AREA: Option<CommandSet> = None;
+    static mut AREA_ID: String = String::new();
+    unsafe {
+        AREA_ID = area_id;
+        AREA = Some(command! {
+            arg: "<prefix>"("address range, such as 10.1.0.0/16") => |arg| parse_area_range_set(saved!(AREA_ID).clone(), arg);
+        });
+        saved!(AREA).as_ref().unwrap()
+    }
+}
+
+fn parse_area_range_set(area_id: String, prefix: &str) -> &'static CommandSet {
+    static mut AREA: Option<CommandSet> = None;
+    static mut AREA_ID: String = String::new();
+    static mut PREFIX: String = String::new();
+    unsafe {
+        AREA_ID = area_id;
+        PREFIX = prefix.to_string();
+        AREA = Some(command! {
+            "advertise"("summarize networks in the range") => || parse_area_range_apply(saved!(AREA_ID).clone(), saved!(PREFIX).clone(), Some(true));
+            "not-advertise"("hide networks in the range") => || parse_area_range_apply(saved!(AREA_ID).clone(), saved!(PREFIX).clone(), Some(false));
+            "delete"("remove the range") => || parse_area_range_apply(saved!(AREA_ID).clone(), saved!(PREFIX).clone(), None);
+        });
+        saved!(AREA).as_ref().unwrap()
+    }
+}
+
+fn parse_area_range_apply(area_id: String, prefix: String, advertise: Option<bool>) -> &'static CommandSet {
+    static mut AREA: Option<CommandSet> = None;
+    unsafe {
+        AREA = Some(command! {
+            enter: ("changing area address range") => move || {
+                guard!(Some(id) = config::parse_area_id(&area_id); error: "bad area_id: {area_id}");
+                guard!(Ok(net) = prefix.parse::<Ipv4Network>(); error: "bad prefix: {prefix}");
+                let range = (net.network(), net.mask());
+                guard!(true = block_on!(ProtocolDB::set_area_range(id, range, advertise)); error: "no such area: {id}");
+                match advertise {
+                    Some(true) => log_success!("area {} range {} is advertised", id, net),
+                    Some(false) => log_success!("area {} range {} is not advertised", id, net),
+                    None => log_success!("area {} range {} is deleted", id, net),
+                }
+            };
+        });
+        saved!(AREA).as_ref().unwrap()
+    }
+}
+
 /// interface 相关命令
 fn parse_interface() -> &'static CommandSet {
     lazy_static! {
@@ -268,10 +1004,16 @@ fn parse_interface_name(name: &str) -> &'static CommandSet {
     unsafe {
         NAME = name.to_string();
         IFACE = Some(command! {
-            "area_id"("interface area setting") => || parse_interface_area(NAME.clone());
-            "cost"("interface cost setting") => || parse_interface_cost(NAME.clone());
+            "area_id"("interface area setting") => || parse_interface_area(saved!(NAME).clone());
+            "cost"("interface cost setting") => || parse_interface_cost(saved!(NAME).clone());
+            "priority"("interface router priority setting") => || parse_interface_priority(saved!(NAME).clone());
+            "hello-interval"("interface hello interval setting") => || parse_interface_timer(saved!(NAME).clone(), true);
+            "dead-interval"("interface dead interval setting") => || parse_interface_timer(saved!(NAME).clone(), false);
+            "passive"("interface passive setting") => || parse_interface_passive(saved!(NAME).clone());
+            "enable"("run ospf on the interface") => || parse_interface_enable(saved!(NAME).clone(), true);
+            "disable"("stop running ospf on the interface") => || parse_interface_enable(saved!(NAME).clone(), false);
         });
-        IFACE.as_ref().unwrap()
+        saved!(IFACE).as_ref().unwrap()
     }
 }
 
@@ -281,9 +1023,9 @@ fn parse_interface_area(name: String) -> &'static CommandSet {
     unsafe {
         NAME = name;
         IFACE = Some(command! {
-            arg: "<area_id>"("interface area setting") => |arg| parse_interface_area_id(NAME.clone(), arg);
+            arg: "<area_id>"("interface area setting") => |arg| parse_interface_area_id(saved!(NAME).clone(), arg);
         });
-        &IFACE.as_ref().unwrap()
+        saved!(IFACE).as_ref().unwrap()
     }
 }
 
@@ -293,16 +1035,14 @@ fn parse_interface_area_id(name: String, arg: &str) -> &'static CommandSet {
     unsafe {
         IFACE = Some(command! {
             enter: ("changing interface area id") => move || {
-                guard!(Some(mut iface) = ProtocolDB::get_interface_by_name(name.as_str()); error: "bad interface_name: {name}");
                 guard!(Ok(id) = arg.parse(); error: "bad area_id: {arg}");
-                log_success!("Interface {}'s area id is changed to {}", iface.interface_name, arg);
-                block_on!(iface.interface_down());
-                block_on!(ProtocolDB::get()).areas.insert(id, Area::new(id));
-                iface.area_id = id;
-                block_on!(iface.interface_up());
+                match block_on!(interface::set_area(&name, id)) {
+                    Ok(()) => log_success!("Interface {name}'s area id is changed to {id}"),
+                    Err(e) => log_error!("failed to change interface {name}: {e}"),
+                }
             };
         });
-        IFACE.as_ref().unwrap()
+        saved!(IFACE).as_ref().unwrap()
     }
 }
 
@@ -312,9 +1052,9 @@ fn parse_interface_cost(name: String) -> &'static CommandSet {
     unsafe {
         NAME = name;
         IFACE = Some(command! {
-            arg: "<cost>"("interface cost setting") => |arg| parse_interface_cost_set(NAME.clone(), arg);
+            arg: "<cost>"("interface cost, or auto to compute from link speed") => |arg| parse_interface_cost_set(saved!(NAME).clone(), arg);
         });
-        &IFACE.as_ref().unwrap()
+        saved!(IFACE).as_ref().unwrap()
     }
 }
 
@@ -324,12 +1064,134 @@ fn parse_interface_cost_set(name: String, arg: &str) -> &'static CommandSet {
     unsafe {
         IFACE = Some(command! {
             enter: ("changing interface cost") => move || {
-                guard!(Some(mut iface) = ProtocolDB::get_interface_by_name(name.as_str()); error: "bad interface_name: {name}");
-                guard!(Ok(cost) = arg.parse(); error: "bad cost: {arg}");
-                iface.cost = cost;
+                let cost = match arg.as_str() {
+                    "auto" => None,
+                    _ => {
+                        guard!(Ok(cost) = arg.parse(); error: "bad cost: {arg}");
+                        Some(cost)
+                    }
+                };
+                match block_on!(interface::set_cost(&name, cost)) {
+                    Ok(cost) => log_success!("Interface {name}'s cost is {cost}"),
+                    Err(e) => log_error!("failed to change interface {name}: {e}"),
+                }
+            };
+        });
+        saved!(IFACE).as_ref().unwrap()
+    }
+}
+
+fn parse_interface_priority(name: String) -> &'static CommandSet {
+    static mut IFACE: Option<CommandSet> = None;
+    static mut NAME: String = String::new();
+    unsafe {
+        NAME = name;
+        IFACE = Some(command! {
+            arg: "<priority>"("router priority, 0 means never becoming DR") => |arg| parse_interface_priority_set(saved!(NAME).clone(), arg);
+        });
+        saved!(IFACE).as_ref().unwrap()
+    }
+}
+
+fn parse_interface_priority_set(name: String, arg: &str) -> &'static CommandSet {
+    static mut IFACE: Option<CommandSet> = None;
+    let arg = arg.to_string();
+    unsafe {
+        IFACE = Some(command! {
+            enter: ("changing interface router priority") => move || {
+                guard!(Ok(priority) = arg.parse(); error: "bad priority: {arg}");
+                match block_on!(interface::set_priority(&name, priority)) {
+                    Ok(()) => log_success!("Interface {name}'s router priority is {priority}"),
+                    Err(e) => log_error!("failed to change interface {name}: {e}"),
+                }
+            };
+        });
+        saved!(IFACE).as_ref().unwrap()
+    }
+}
+
+fn parse_interface_timer(name: String, hello: bool) -> &'static CommandSet {
+    static mut IFACE: Option<CommandSet> = None;
+    static mut NAME: String = String::new();
+    static mut HELLO: bool = false;
+    unsafe {
+        NAME = name;
+        HELLO = hello;
+        IFACE = Some(command! {
+            arg: "<seconds>"("interval in seconds, adjacencies on the interface are reset") => |arg| parse_interface_timer_set(saved!(NAME).clone(), HELLO, arg);
+        });
+        saved!(IFACE).as_ref().unwrap()
+    }
+}
+
+fn parse_interface_timer_set(name: String, hello: bool, arg: &str) -> &'static CommandSet {
+    static mut IFACE: Option<CommandSet> = None;
+    let arg = arg.to_string();
+    unsafe {
+        IFACE = Some(command! {
+            enter: ("changing interface timers") => move || {
+                guard!(Ok(seconds) = arg.parse(); error: "bad interval: {arg}");
+                let (kind, result) = if hello {
+                    ("hello", block_on!(interface::set_timers(&name, Some(seconds), None)))
+                } else {
+                    ("dead", block_on!(interface::set_timers(&name, None, Some(seconds))))
+                };
+                match result {
+                    Ok(()) => log_success!("Interface {name}'s {kind} interval is {seconds}"),
+                    Err(e) => log_error!("failed to change interface {name}: {e}"),
+                }
+            };
+        });
+        saved!(IFACE).as_ref().unwrap()
+    }
+}
+
+fn parse_interface_passive(name: String) -> &'static CommandSet {
+    static mut IFACE: Option<CommandSet> = None;
+    static mut NAME: String = String::new();
+    unsafe {
+        NAME = name;
+        IFACE = Some(command! {
+            "on"("advertise the interface without running ospf on it") => || parse_interface_passive_set(saved!(NAME).clone(), true);
+            "off"("run ospf on the interface") => || parse_interface_passive_set(saved!(NAME).clone(), false);
+        });
+        saved!(IFACE).as_ref().unwrap()
+    }
+}
+
+fn parse_interface_passive_set(name: String, passive: bool) -> &'static CommandSet {
+    static mut IFACE: Option<CommandSet> = None;
+    unsafe {
+        IFACE = Some(command! {
+            enter: ("changing interface passive mode") => move || {
+                let mut instances = ProtocolDB::get_interfaces_by_name(name.as_str());
+                must!(!instances.is_empty(); error: "bad interface_name: {name}");
+                for iface in instances.iter_mut() {
+                    block_on!(iface.set_passive(passive));
+                }
+                log_success!("Interface {}'s passive mode is {}", name, if passive { "on" } else { "off" });
+            };
+        });
+        saved!(IFACE).as_ref().unwrap()
+    }
+}
+
+fn parse_interface_enable(name: String, enable: bool) -> &'static CommandSet {
+    static mut IFACE: Option<CommandSet> = None;
+    unsafe {
+        IFACE = Some(command! {
+            enter: ("enabling or disabling interface") => move || {
+                let result = if enable {
+                    block_on!(interface::enable(&name))
+                } else {
+                    block_on!(interface::disable(&name))
+                };
+                if let Err(e) = result {
+                    log_error!("failed to change interface {name}: {e}");
+                }
             };
         });
-        IFACE.as_ref().unwrap()
+        saved!(IFACE).as_ref().unwrap()
     }
 }
 
@@ -353,6 +1215,25 @@ fn parse_exit() -> &'static CommandSet {
                 // 直接退出程序
                 std::process::exit(0);
             };
+            "graceful"("notify neighbors and exit, keep routes for restart") => parse_exit_graceful;
+        };
+    }
+    &EXIT
+}
+
+fn parse_exit_graceful() -> &'static CommandSet {
+    lazy_static! {
+        static ref EXIT: CommandSet = command! {
+            enter: ("notify neighbors and exit, keep routes for restart") => || {
+                if let Err(e) = block_on!(gr::prepare_restart()) {
+                    log_error!("failed to save restart state: {}", e);
+                    return;
+                }
+                // 等待 Grace LSA 发出，不删除路由
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                log_success!("graceful restart prepared, routes are kept");
+                std::process::exit(0);
+            };
         };
     }
     &EXIT
@@ -416,26 +1297,20 @@ impl LineEditor {
                     }
 
                     // 退格键（支持中间删除）
-                    KeyCode::Backspace => {
-                        if self.cursor_position > 0 {
-                            self.buffer.remove(self.cursor_position - 1);
-                            self.cursor_position -= 1;
-                            self.redraw_line(&mut stdout);
-                        }
+                    KeyCode::Backspace if self.cursor_position > 0 => {
+                        self.buffer.remove(self.cursor_position - 1);
+                        self.cursor_position -= 1;
+                        self.redraw_line(&mut stdout);
                     }
 
                     // 方向键处理
-                    KeyCode::Left => {
-                        if self.cursor_position > 0 {
-                            self.cursor_position -= 1;
-                            self.update_cursor(&mut stdout);
-                        }
+                    KeyCode::Left if self.cursor_position > 0 => {
+                        self.cursor_position -= 1;
+                        self.update_cursor(&mut stdout);
                     }
-                    KeyCode::Right => {
-                        if self.cursor_position < self.buffer.len() {
-                            self.cursor_position += 1;
-                            self.update_cursor(&mut stdout);
-                        }
+                    KeyCode::Right if self.cursor_position < self.buffer.len() => {
+                        self.cursor_position += 1;
+                        self.update_cursor(&mut stdout);
                     }
 
                     // 上下方向键（保持原有历史逻辑）
@@ -0,0 +1,803 @@
+//! 配置文件解析
+//!
+//! 配置文件按行书写，`#` 之后的内容为注释，例如：
+//!
+//! ```text
+//! # 只在这些接口上运行协议（支持通配符 * 和 ?）
+//! interfaces include eth* ens3
+//! interfaces exclude docker0 veth*
+//! # 只启用地址落在网段内的接口，并划入对应区域
+//! network 10.0.0.0/8 area 0
+//! # 只宣告 eth1 的网段，不在其上运行协议
+//! interface eth1 passive
+//! # 接口开销默认为参考带宽除以链路速率（Mbit/s），单独配置的开销优先
+//! auto-cost reference-bandwidth 100000
+//! interface eth1 cost 10
+//! # 固定路由器标识，不配置时沿用上次保存的标识
+//! router-id 10.255.0.1
+//! # 保存路由器标识的状态文件
+//! state-file /var/lib/ospfd/router-id
+//! # 把 dummy0 当作环回接口，以 /32 主机路由宣告其地址
+//! interface dummy0 loopback
+//! # eth2 上的每个网段单独运行一个实例（默认只在主地址上运行，其余网段作为存根网络宣告）
+//! interface eth2 secondary instance
+//! # 流量工程：在 eth0 上宣告 TE 链路属性（带宽单位为 Mbit/s）
+//! interface eth0 te metric 20
+//! interface eth0 te bandwidth 1000 reservable 800
+//! interface eth0 te admin-group 0x5
+//! te router-address 10.255.0.1
+//! # 在路由器信息 LSA 中宣告的主机名
+//! hostname core-1
+//! # 分段路由：全局标签块和本地标签块（起始标签 大小），只写 segment-routing 时使用默认值
+//! segment-routing global-block 16000 8000
+//! segment-routing local-block 15000 1000
+//! # 为环回接口的地址分配前缀 SID，no-php 要求倒数第二跳不弹出标签
+//! interface dummy0 prefix-sid index 1
+//! # 平滑重启的宽限期（秒），以及是否帮助重启的邻居
+//! graceful-restart grace-period 120
+//! graceful-restart helper disable
+//! # 存根路由器：启动后 300 秒内宣告最大度量，wait-for-full 表示所有邻接完全建立后提前结束
+//! max-metric on-startup 300
+//! max-metric on-startup wait-for-full 600
+//! # 一直宣告最大度量（维护前引走流量），以及同时在汇总和外部 LSA 中宣告的度量
+//! max-metric router-lsa
+//! max-metric summary-lsa 16711680
+//! max-metric external-lsa
+//! # 区域边界路由器把区域 1 内的网络汇总为一条 Summary-LSA，not-advertise 表示不宣告这些网络
+//! area 0.0.0.1 range 10.1.0.0/16
+//! area 0.0.0.1 range 10.2.0.0/16 not-advertise
+//! # 引入静态外部路由（默认度量 20、类型 2），作为 AS 边界路由器宣告 AS-external-LSA
+//! redistribute static 192.168.10.0/24 metric 30 metric-type 1 tag 100
+//! # 把落在网段内的外部路由汇总为一条 AS-external-LSA，not-advertise 表示不宣告这些路由
+//! summary-address 192.168.0.0/16 tag 100
+//! summary-address 172.16.0.0/12 not-advertise
+//! # 前缀列表：按序号匹配，ge/le 指定匹配的前缀长度范围，不写序号时自动编号
+//! prefix-list lab seq 10 deny 10.99.0.0/16 le 32
+//! prefix-list lab seq 20 permit 0.0.0.0/0 le 32
+//! # 区域边界路由器过滤区域间路由：out 过滤离开区域 1 的 Summary-LSA，in 过滤进入区域 1 的
+//! area 0.0.0.1 filter-list prefix lab out
+//! # 路由策略：子句按序号检查，match 条件都满足时执行 set，deny 子句拒绝路由
+//! route-map edge 10 deny
+//! route-map edge 10 match prefix-list lab
+//! route-map edge 20 permit
+//! route-map edge 20 match tag 100
+//! route-map edge 20 set metric 50
+//! route-map edge 20 set metric-type 1
+//! # 引入外部路由时应用策略
+//! redistribute static route-map edge
+//! # 策略拒绝的路由照常计算，但不写入内核
+//! fib-filter route-map no-lab
+//! # 日志级别（error/warning/notice/info/debug），以及启动时就打开调试的子系统
+//! log level notice
+//! debug ospf spf
+//! # 日志文件：单个文件超过 10 MB 时轮转，保留 5 个旧文件
+//! log file /var/log/ospfd.log size 10 rotate 5
+//! # 同时发送到 syslog（默认 /dev/log，systemd 下由 journald 接收）
+//! log syslog
+//! # 在 HTTP 端点 /metrics 上以 Prometheus 格式导出状态和计数
+//! metrics listen 127.0.0.1:9400
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use pnet::ipnetwork::{IpNetwork, Ipv4Network};
+
+use crate::{
+    constant::{BackboneArea, LSInfinity},
+    guard, log_warning,
+    logging::{self, Level, Subsystem},
+    must,
+    prefix_list::{PrefixList, PrefixListEntry},
+    route_map::{self, Clause, Match, RouteMap, Set},
+    util::{glob_match, hex2ip},
+};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+const DEFAULT_STATE_FILE: &str = "/var/lib/ospfd/router-id";
+
+/// 默认参考带宽（Mbit/s）
+const DEFAULT_REFERENCE_BANDWIDTH: u32 = 100;
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// 接口名 -> 接口配置
+    pub interfaces: HashMap<String, InterfaceConfig>,
+    /// 接口名白名单，为空表示不限制
+    pub include: Vec<String>,
+    /// 接口名黑名单
+    pub exclude: Vec<String>,
+    /// network 语句：（网段，区域）
+    pub networks: Vec<(Ipv4Network, Ipv4Addr)>,
+    /// 指定的路由器标识
+    pub router_id: Option<Ipv4Addr>,
+    /// 计算接口开销的参考带宽（Mbit/s）
+    pub reference_bandwidth: Option<u32>,
+    /// 保存路由器标识的状态文件
+    pub state_file: Option<PathBuf>,
+    /// TE 路由器地址，不配置时使用路由器标识
+    pub te_router_address: Option<Ipv4Addr>,
+    /// 宣告的主机名
+    pub hostname: Option<String>,
+    /// 分段路由，为 None 时不启用
+    pub segment_routing: Option<SrConfig>,
+    pub graceful_restart: GrConfig,
+    pub max_metric: MaxMetricConfig,
+    /// 区域范围：（区域，网段，是否宣告）
+    pub area_ranges: Vec<(Ipv4Addr, Ipv4Network, bool)>,
+    /// 引入的外部路由
+    pub redistribute: Vec<ExternalRoute>,
+    /// 外部路由的汇总地址
+    pub summary_addresses: Vec<SummaryAddress>,
+    /// 名称 -> 前缀列表
+    pub prefix_lists: BTreeMap<String, PrefixList>,
+    /// 区域间前缀过滤：（区域，前缀列表名，是否过滤进入区域的路由）
+    pub area_filters: Vec<(Ipv4Addr, String, bool)>,
+    /// 名称 -> 路由策略
+    pub route_maps: BTreeMap<String, RouteMap>,
+    /// 引入外部路由时应用的策略
+    pub redistribute_route_map: Option<String>,
+    /// 决定路由是否写入内核的策略
+    pub fib_filter: Option<String>,
+    pub log: LogConfig,
+    /// Prometheus 指标的监听地址
+    pub metrics_listen: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct InterfaceConfig {
+    /// 被动接口：宣告网段，但不收发 OSPF 报文
+    pub passive: bool,
+    /// 环回接口（如 dummy 接口）：以主机路由宣告接口地址
+    pub loopback: bool,
+    /// 接口开销，不配置时按参考带宽计算
+    pub cost: Option<u16>,
+    /// 每个网段单独运行一个实例，而不是只在主地址上运行
+    pub secondary_instance: bool,
+    /// 流量工程属性，配置后在该接口上宣告 TE 链路
+    pub te: Option<TeConfig>,
+    /// 前缀 SID，只对环回接口有效
+    pub prefix_sid: Option<PrefixSidConfig>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TeConfig {
+    /// TE 度量，不配置时使用接口开销
+    pub metric: Option<u32>,
+    /// 最大带宽（Mbit/s）
+    pub bandwidth: Option<f32>,
+    /// 最大可预留带宽（Mbit/s），不配置时等于最大带宽
+    pub reservable: Option<f32>,
+    /// 管理组（颜色）
+    pub admin_group: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrConfig {
+    /// 全局标签块（SRGB）：（起始标签，大小）
+    pub global_block: (u32, u32),
+    /// 本地标签块（SRLB），用于邻接 SID
+    pub local_block: (u32, u32),
+}
+
+impl Default for SrConfig {
+    fn default() -> Self {
+        Self {
+            global_block: (16000, 8000),
+            local_block: (15000, 1000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrConfig {
+    /// 自己平滑重启时请求的宽限期（秒）
+    pub grace_period: u32,
+    /// 帮助者模式：邻居平滑重启时保持邻接
+    pub helper: bool,
+}
+
+impl Default for GrConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: 120,
+            helper: true,
+        }
+    }
+}
+
+/// 汇总和外部 LSA 的默认最大度量
+pub const DEFAULT_MAX_METRIC: u32 = 0xff0000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MaxMetricConfig {
+    /// 启动时就一直宣告最大度量
+    pub administrative: bool,
+    /// 启动后宣告最大度量的时长（秒）
+    pub on_startup: Option<u32>,
+    /// 所有邻接完全建立后提前结束
+    pub wait_for_full: bool,
+    /// 最大度量期间汇总 LSA 使用的度量
+    pub summary: Option<u32>,
+    /// 最大度量期间外部 LSA 使用的度量
+    pub external: Option<u32>,
+}
+
+/// 外部路由的默认度量
+pub const DEFAULT_EXTERNAL_METRIC: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExternalRoute {
+    pub network: Ipv4Network,
+    pub metric: u32,
+    /// 外部度量类型：1 或 2
+    pub metric_type: u8,
+    pub tag: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SummaryAddress {
+    pub network: Ipv4Network,
+    pub tag: u32,
+    /// 为 false 时隐藏范围内的外部路由
+    pub advertise: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LogConfig {
+    /// 没有打开调试的子系统输出的最低级别，不配置时为 info
+    pub level: Option<Level>,
+    /// 启动时就打开调试的子系统
+    pub debug: Vec<Subsystem>,
+    pub file: Option<LogFileConfig>,
+    /// syslog 套接字
+    pub syslog: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFileConfig {
+    pub path: PathBuf,
+    /// 单个文件的大小（MB）
+    pub size: u64,
+    /// 保留的旧文件数
+    pub rotate: u32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixSidConfig {
+    /// SRGB 中的索引
+    pub index: u32,
+    pub no_php: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("IO Error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("line {0}: {1}")]
+    BadLine(usize, String),
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for (no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<_> = line.split_ascii_whitespace().collect();
+            must!(!words.is_empty(); continue);
+            config
+                .parse_line(&words)
+                .map_err(|e| ConfigError::BadLine(no + 1, e))?;
+        }
+        Ok(config)
+    }
+
+    fn parse_line(&mut self, words: &[&str]) -> Result<(), String> {
+        match words {
+            ["interface", name, rest @ ..] => {
+                let iface = self.interfaces.entry(name.to_string()).or_default();
+                match rest {
+                    ["passive"] => iface.passive = true,
+                    ["loopback"] => iface.loopback = true,
+                    ["secondary", "instance"] => iface.secondary_instance = true,
+                    ["cost", cost] => {
+                        let cost = cost.parse().ok().filter(|c| *c > 0);
+                        iface.cost = Some(cost.ok_or(format!("bad cost: {}", rest[1]))?);
+                    }
+                    ["te", rest @ ..] => parse_te(iface.te.get_or_insert_with(Default::default), rest)?,
+                    ["prefix-sid", "index", index, rest @ ..] => {
+                        let index = index.parse().map_err(|_| format!("bad sid index: {index}"))?;
+                        let no_php = match rest {
+                            [] => false,
+                            ["no-php"] => true,
+                            _ => return Err(format!("bad prefix-sid setting: {}", rest.join(" "))),
+                        };
+                        iface.prefix_sid = Some(PrefixSidConfig { index, no_php });
+                    }
+                    _ => return Err(format!("bad interface setting: {}", rest.join(" "))),
+                }
+            }
+            ["interfaces", "include", names @ ..] if !names.is_empty() => {
+                self.include.extend(names.iter().map(|s| s.to_string()))
+            }
+            ["interfaces", "exclude", names @ ..] if !names.is_empty() => {
+                self.exclude.extend(names.iter().map(|s| s.to_string()))
+            }
+            ["network", net, "area", area] => {
+                let net = net.parse().map_err(|_| format!("bad network: {net}"))?;
+                let area = parse_area_id(area).ok_or(format!("bad area id: {area}"))?;
+                self.networks.push((net, area));
+            }
+            ["router-id", id] => {
+                self.router_id = Some(id.parse().map_err(|_| format!("bad router id: {id}"))?)
+            }
+            ["auto-cost", "reference-bandwidth", bw] => {
+                let bw = bw.parse().ok().filter(|bw| *bw > 0);
+                self.reference_bandwidth = Some(bw.ok_or("bad reference bandwidth".to_string())?);
+            }
+            ["state-file", path] => self.state_file = Some(path.into()),
+            ["hostname", name] => self.hostname = Some(name.to_string()),
+            ["te", "router-address", ip] => {
+                self.te_router_address = Some(ip.parse().map_err(|_| format!("bad address: {ip}"))?)
+            }
+            ["segment-routing", rest @ ..] => {
+                let sr = self.segment_routing.get_or_insert_with(Default::default);
+                match rest {
+                    [] => (),
+                    ["global-block", base, size] => sr.global_block = parse_block(base, size)?,
+                    ["local-block", base, size] => sr.local_block = parse_block(base, size)?,
+                    _ => return Err(format!("bad segment-routing setting: {}", rest.join(" "))),
+                }
+                let ((g, gs), (l, ls)) = (sr.global_block, sr.local_block);
+                must!(g + gs <= l || l + ls <= g; ret: Err("global and local block overlap".into()));
+            }
+            ["graceful-restart", "grace-period", period] => {
+                // 宽限期不能超过 LSRefreshTime
+                let period = period.parse().ok().filter(|p| (1..=1800).contains(p));
+                self.graceful_restart.grace_period = period.ok_or("bad grace period".to_string())?;
+            }
+            ["graceful-restart", "helper", "enable"] => self.graceful_restart.helper = true,
+            ["graceful-restart", "helper", "disable"] => self.graceful_restart.helper = false,
+            ["max-metric", rest @ ..] => parse_max_metric(&mut self.max_metric, rest)?,
+            ["area", area, "range", net, rest @ ..] => {
+                let area = parse_area_id(area).ok_or(format!("bad area id: {area}"))?;
+                let net = net.parse().map_err(|_| format!("bad network: {net}"))?;
+                let advertise = match rest {
+                    [] | ["advertise"] => true,
+                    ["not-advertise"] => false,
+                    _ => return Err(format!("bad range setting: {}", rest.join(" "))),
+                };
+                self.area_ranges.push((area, net, advertise));
+            }
+            ["prefix-list", name, rest @ ..] => {
+                let list = self.prefix_lists.entry(name.to_string()).or_default();
+                list.insert(parse_prefix_list_entry(list.next_seq(), rest)?);
+            }
+            ["area", area, "filter-list", "prefix", name, dir @ ("in" | "out")] => {
+                let area = parse_area_id(area).ok_or(format!("bad area id: {area}"))?;
+                self.area_filters.push((area, name.to_string(), *dir == "in"));
+            }
+            ["route-map", name, seq, rest @ ..] => {
+                let seq = seq.parse().map_err(|_| format!("bad seq: {seq}"))?;
+                let map = self.route_maps.entry(name.to_string()).or_default();
+                parse_route_map_clause(map.clause(seq), rest)?;
+            }
+            ["redistribute", "static", "route-map", name] => self.redistribute_route_map = Some(name.to_string()),
+            ["fib-filter", "route-map", name] => self.fib_filter = Some(name.to_string()),
+            ["redistribute", "static", net, rest @ ..] => self.redistribute.push(parse_external(net, rest)?),
+            ["summary-address", net, rest @ ..] => {
+                self.summary_addresses.push(parse_summary_address(net, rest)?)
+            }
+            ["log", rest @ ..] => parse_log(&mut self.log, rest)?,
+            ["debug", "ospf", sub] => self.log.debug.push(sub.parse()?),
+            ["metrics", "listen", addr] => {
+                self.metrics_listen = Some(addr.parse().map_err(|_| format!("bad listen address: {addr}"))?)
+            }
+            _ => return Err(format!("unknown setting: {}", words.join(" "))),
+        }
+        Ok(())
+    }
+
+    /// 只能在启动时调用一次
+    pub fn init(config: Config) {
+        CONFIG.get_or_init(|| config);
+    }
+
+    pub fn get() -> &'static Config {
+        CONFIG.get_or_init(Config::default)
+    }
+
+    pub fn reference_bandwidth(&self) -> u32 {
+        self.reference_bandwidth.unwrap_or(DEFAULT_REFERENCE_BANDWIDTH)
+    }
+
+    pub fn interface(&self, name: &str) -> InterfaceConfig {
+        self.interfaces.get(name).cloned().unwrap_or_default()
+    }
+
+    fn state_file(&self) -> &Path {
+        self.state_file
+            .as_deref()
+            .unwrap_or(Path::new(DEFAULT_STATE_FILE))
+    }
+
+    /// 平滑重启时保存状态的文件，与路由器标识的状态文件放在一起
+    pub fn restart_file(&self) -> PathBuf {
+        self.state_file().with_extension("restart")
+    }
+
+    /// 读取上次保存的路由器标识
+    pub fn load_router_id(&self) -> Option<Ipv4Addr> {
+        let text = std::fs::read_to_string(self.state_file()).ok()?;
+        text.trim().parse().ok()
+    }
+
+    /// 保存路由器标识，下次启动时沿用
+    pub fn save_router_id(&self, id: Ipv4Addr) {
+        let path = self.state_file();
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, format!("{id}\n")));
+        if let Err(e) = result {
+            log_warning!("failed to save router id to {}: {}", path.display(), e);
+        }
+    }
+
+    /// 按 network 语句（最长匹配）决定接口所在区域，没有 network 语句时为骨干区域
+    pub fn area_of(&self, ips: &[IpNetwork]) -> Option<Ipv4Addr> {
+        must!(!self.networks.is_empty(); ret: Some(BackboneArea));
+        self.networks
+            .iter()
+            .filter(|(net, _)| {
+                ips.iter().any(|ip| match ip {
+                    IpNetwork::V4(ip) => net.contains(ip.ip()),
+                    _ => false,
+                })
+            })
+            .max_by_key(|(net, _)| net.prefix())
+            .map(|(_, area)| *area)
+    }
+
+    /// 启动时筛选接口，返回接口所在区域；不启用的接口返回 None
+    pub fn select(&self, name: &str, ips: &[IpNetwork]) -> Option<Ipv4Addr> {
+        must!(!self.exclude.iter().any(|p| glob_match(p, name)); ret: None);
+        must!(self.include.is_empty() || self.include.iter().any(|p| glob_match(p, name)); ret: None);
+        self.area_of(ips)
+    }
+}
+
+fn parse_te(te: &mut TeConfig, words: &[&str]) -> Result<(), String> {
+    let bandwidth = |s: &str| match s.parse::<f32>() {
+        Ok(bw) if bw.is_finite() && bw >= 0.0 => Ok(bw),
+        _ => Err(format!("bad bandwidth: {s}")),
+    };
+    match words {
+        [] => (),
+        ["metric", metric] => te.metric = Some(metric.parse().map_err(|_| format!("bad metric: {metric}"))?),
+        ["bandwidth", bw] => te.bandwidth = Some(bandwidth(bw)?),
+        ["bandwidth", bw, "reservable", r] => {
+            te.bandwidth = Some(bandwidth(bw)?);
+            te.reservable = Some(bandwidth(r)?);
+        }
+        ["reservable", r] => te.reservable = Some(bandwidth(r)?),
+        ["admin-group", group] => {
+            let value = match group.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => group.parse(),
+            };
+            te.admin_group = Some(value.map_err(|_| format!("bad admin group: {group}"))?);
+        }
+        _ => return Err(format!("bad te setting: {}", words.join(" "))),
+    }
+    Ok(())
+}
+
+/// 解析 `max-metric` 之后的部分，未给出的度量使用 DEFAULT_MAX_METRIC
+fn parse_max_metric(mm: &mut MaxMetricConfig, words: &[&str]) -> Result<(), String> {
+    let seconds = |s: &str| {
+        let secs = s.parse().ok().filter(|s| (1..=86400).contains(s));
+        secs.ok_or(format!("bad seconds: {s}"))
+    };
+    // 度量不能超过 LSInfinity
+    let metric = |s: Option<&&str>| match s {
+        None => Ok(DEFAULT_MAX_METRIC),
+        Some(s) => s.parse().ok().filter(|m| (1..=LSInfinity).contains(m)).ok_or(format!("bad metric: {s}")),
+    };
+    match words {
+        ["router-lsa"] => mm.administrative = true,
+        ["on-startup", "wait-for-full", rest @ ..] => {
+            mm.wait_for_full = true;
+            mm.on_startup = Some(match rest {
+                [] => 600,
+                [secs] => seconds(secs)?,
+                _ => return Err(format!("bad max-metric setting: {}", words.join(" "))),
+            });
+        }
+        ["on-startup", secs] => mm.on_startup = Some(seconds(secs)?),
+        ["summary-lsa", rest @ ..] if rest.len() <= 1 => mm.summary = Some(metric(rest.first())?),
+        ["external-lsa", rest @ ..] if rest.len() <= 1 => mm.external = Some(metric(rest.first())?),
+        _ => return Err(format!("bad max-metric setting: {}", words.join(" "))),
+    }
+    Ok(())
+}
+
+fn parse_log(log: &mut LogConfig, words: &[&str]) -> Result<(), String> {
+    match words {
+        ["level", level] => log.level = Some(level.parse()?),
+        ["file", path, rest @ ..] => {
+            let mut file = LogFileConfig {
+                path: path.into(),
+                size: logging::DEFAULT_FILE_SIZE,
+                rotate: logging::DEFAULT_FILE_ROTATE,
+            };
+            let mut words = rest;
+            loop {
+                words = match words {
+                    [] => break,
+                    ["size", size, rest @ ..] => {
+                        let size = size.parse().ok().filter(|s| *s > 0);
+                        file.size = size.ok_or(format!("bad log file size: {}", words[1]))?;
+                        rest
+                    }
+                    ["rotate", n, rest @ ..] => {
+                        file.rotate = n.parse().map_err(|_| format!("bad rotate count: {n}"))?;
+                        rest
+                    }
+                    _ => return Err(format!("bad log file setting: {}", words.join(" "))),
+                };
+            }
+            log.file = Some(file);
+        }
+        ["syslog"] => log.syslog = Some(logging::DEFAULT_SYSLOG.into()),
+        ["syslog", path] => log.syslog = Some(path.into()),
+        _ => return Err(format!("bad log setting: {}", words.join(" "))),
+    }
+    Ok(())
+}
+
+fn parse_external(net: &str, mut words: &[&str]) -> Result<ExternalRoute, String> {
+    let mut route = ExternalRoute {
+        network: net.parse().map_err(|_| format!("bad network: {net}"))?,
+        metric: DEFAULT_EXTERNAL_METRIC,
+        metric_type: 2,
+        tag: 0,
+    };
+    loop {
+        words = match words {
+            [] => return Ok(route),
+            ["metric", metric, rest @ ..] => {
+                let metric = metric.parse().ok().filter(|m| (0..LSInfinity).contains(m));
+                route.metric = metric.ok_or(format!("bad metric: {}", words[1]))?;
+                rest
+            }
+            ["metric-type", t @ ("1" | "2"), rest @ ..] => {
+                route.metric_type = t.parse().unwrap();
+                rest
+            }
+            ["tag", tag, rest @ ..] => {
+                route.tag = tag.parse().map_err(|_| format!("bad tag: {tag}"))?;
+                rest
+            }
+            _ => return Err(format!("bad redistribute setting: {}", words.join(" "))),
+        }
+    }
+}
+
+fn parse_prefix_list_entry(seq: u32, words: &[&str]) -> Result<PrefixListEntry, String> {
+    let (seq, words) = match words {
+        ["seq", seq, rest @ ..] => (seq.parse().map_err(|_| format!("bad seq: {seq}"))?, rest),
+        _ => (seq, words),
+    };
+    guard!([action @ ("permit" | "deny"), prefix, rest @ ..] = words;
+        ret: Err(format!("bad prefix-list setting: {}", words.join(" "))));
+    let mut words = rest;
+    let prefix: Ipv4Network = prefix.parse().map_err(|_| format!("bad prefix: {prefix}"))?;
+    let prefix = Ipv4Network::new(prefix.network(), prefix.prefix()).unwrap();
+    let mut entry = PrefixListEntry {
+        seq,
+        permit: *action == "permit",
+        prefix,
+        ge: None,
+        le: None,
+    };
+    // ge 和 le 必须比前缀本身长
+    let length = |s: &str| {
+        let len = s.parse().ok().filter(|l| (prefix.prefix() + 1..=32).contains(l));
+        len.ok_or(format!("bad prefix length: {s}"))
+    };
+    loop {
+        words = match words {
+            [] => break,
+            ["ge", len, rest @ ..] => {
+                entry.ge = Some(length(len)?);
+                rest
+            }
+            ["le", len, rest @ ..] => {
+                entry.le = Some(length(len)?);
+                rest
+            }
+            _ => return Err(format!("bad prefix-list setting: {}", words.join(" "))),
+        }
+    }
+    must!(entry.ge.zip(entry.le).is_none_or(|(ge, le)| ge <= le); ret: Err("ge is greater than le".into()));
+    Ok(entry)
+}
+
+fn parse_route_map_clause(clause: &mut Clause, words: &[&str]) -> Result<(), String> {
+    let number = |s: &str| s.parse().map_err(|_| format!("bad number: {s}"));
+    match words {
+        ["permit"] => clause.permit = true,
+        ["deny"] => clause.permit = false,
+        ["match", "prefix-list", name] => clause.matches.push(Match::PrefixList(name.to_string())),
+        ["match", "tag", tag] => clause.matches.push(Match::Tag(number(tag)?)),
+        ["match", "path-type", t] => {
+            let path_type = route_map::parse_path_type(t).ok_or(format!("bad path type: {t}"))?;
+            clause.matches.push(Match::PathType(path_type));
+        }
+        ["match", "area", area] => {
+            let area = parse_area_id(area).ok_or(format!("bad area id: {area}"))?;
+            clause.matches.push(Match::Area(area));
+        }
+        ["match", "next-hop", ip] => {
+            let ip = ip.parse().map_err(|_| format!("bad address: {ip}"))?;
+            clause.matches.push(Match::NextHop(ip));
+        }
+        ["set", "metric", metric] => {
+            let metric = metric.parse().ok().filter(|m| (0..LSInfinity).contains(m));
+            clause.sets.push(Set::Metric(metric.ok_or(format!("bad metric: {}", words[2]))?));
+        }
+        ["set", "metric-type", t @ ("1" | "2")] => clause.sets.push(Set::MetricType(t.parse().unwrap())),
+        ["set", "tag", tag] => clause.sets.push(Set::Tag(number(tag)?)),
+        _ => return Err(format!("bad route-map setting: {}", words.join(" "))),
+    }
+    Ok(())
+}
+
+fn parse_summary_address(net: &str, mut words: &[&str]) -> Result<SummaryAddress, String> {
+    let mut summary = SummaryAddress {
+        network: net.parse().map_err(|_| format!("bad network: {net}"))?,
+        tag: 0,
+        advertise: true,
+    };
+    loop {
+        words = match words {
+            [] => return Ok(summary),
+            ["tag", tag, rest @ ..] => {
+                summary.tag = tag.parse().map_err(|_| format!("bad tag: {tag}"))?;
+                rest
+            }
+            ["not-advertise", rest @ ..] => {
+                summary.advertise = false;
+                rest
+            }
+            _ => return Err(format!("bad summary-address setting: {}", words.join(" "))),
+        }
+    }
+}
+
+/// 标签块必须在 16 到 2^20 之间，0 到 15 是保留标签
+fn parse_block(base: &str, size: &str) -> Result<(u32, u32), String> {
+    let block = base.parse::<u32>().ok().zip(size.parse::<u32>().ok());
+    match block {
+        Some((base, size)) if base >= 16 && size > 0 && base + size <= 1 << 20 => Ok((base, size)),
+        _ => Err(format!("bad label block: {base} {size}")),
+    }
+}
+
+/// 区域号可以写成点分十进制，也可以写成整数
+pub fn parse_area_id(s: &str) -> Option<Ipv4Addr> {
+    s.parse().ok().or_else(|| s.parse().ok().map(hex2ip))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let config = Config::parse("# comment\n\ninterface eth1 passive # tail\n").unwrap();
+        assert!(config.interface("eth1").passive);
+        assert!(!config.interface("eth0").passive);
+        let config = Config::parse("interface eth2 secondary instance").unwrap();
+        assert!(config.interface("eth2").secondary_instance);
+        assert!(!config.interface("eth1").secondary_instance);
+        let config = Config::parse(
+            "interfaces include eth* ens3\ninterfaces exclude eth9\n\
+             network 10.0.0.0/8 area 0\nnetwork 10.1.0.0/16 area 0.0.0.1",
+        )
+        .unwrap();
+        let ips = |s: &str| vec![s.parse::<IpNetwork>().unwrap()];
+        assert_eq!(config.select("eth0", &ips("10.1.2.3/24")), Some(hex2ip(1)));
+        assert_eq!(
+            config.select("eth1", &ips("10.2.2.3/24")),
+            Some(BackboneArea)
+        );
+        assert_eq!(config.select("eth1", &ips("192.168.0.1/24")), None);
+        assert_eq!(config.select("eth9", &ips("10.2.2.3/24")), None);
+        assert_eq!(config.select("docker0", &ips("10.2.2.3/24")), None);
+        let config = Config::parse(
+            "interface eth0 te\ninterface eth1 te bandwidth 1000 reservable 800\n\
+             interface eth1 te admin-group 0x5",
+        )
+        .unwrap();
+        assert_eq!(config.interface("eth0").te, Some(TeConfig::default()));
+        let te = config.interface("eth1").te.unwrap();
+        assert_eq!((te.bandwidth, te.reservable), (Some(1000.0), Some(800.0)));
+        assert_eq!((te.metric, te.admin_group), (None, Some(5)));
+        assert!(Config::parse("interface eth1 te bandwidth -1").is_err());
+        let config = Config::parse(
+            "segment-routing global-block 20000 1000\ninterface lo prefix-sid index 1 no-php",
+        )
+        .unwrap();
+        let sr = config.segment_routing.unwrap();
+        assert_eq!((sr.global_block, sr.local_block), ((20000, 1000), (15000, 1000)));
+        assert_eq!(config.interface("lo").prefix_sid, Some(PrefixSidConfig { index: 1, no_php: true }));
+        assert!(Config::parse("segment-routing local-block 20500 100").is_err());
+        assert!(Config::parse("segment-routing global-block 1048000 1000").is_err());
+        let config = Config::parse("graceful-restart grace-period 60\ngraceful-restart helper disable").unwrap();
+        assert_eq!(config.graceful_restart, GrConfig { grace_period: 60, helper: false });
+        assert!(Config::parse("graceful-restart grace-period 0").is_err());
+        let config = Config::parse(
+            "max-metric on-startup wait-for-full\nmax-metric summary-lsa\nmax-metric external-lsa 100",
+        )
+        .unwrap();
+        let mm = config.max_metric;
+        assert_eq!((mm.administrative, mm.on_startup, mm.wait_for_full), (false, Some(600), true));
+        assert_eq!((mm.summary, mm.external), (Some(DEFAULT_MAX_METRIC), Some(100)));
+        assert!(Config::parse("max-metric external-lsa 16777216").is_err());
+        let config = Config::parse("area 1 range 10.1.0.0/16\narea 1 range 10.2.0.0/16 not-advertise").unwrap();
+        let net = |s: &str| s.parse::<Ipv4Network>().unwrap();
+        assert_eq!(config.area_ranges, vec![(hex2ip(1), net("10.1.0.0/16"), true), (hex2ip(1), net("10.2.0.0/16"), false)]);
+        assert!(Config::parse("area 1 range 10.1.0.0/16 hidden").is_err());
+        let config = Config::parse("redistribute static 192.168.1.0/24 metric 30 metric-type 1 tag 7\nredistribute static 192.168.2.0/24\nsummary-address 192.168.0.0/16 not-advertise tag 5").unwrap();
+        assert_eq!(config.redistribute[0], ExternalRoute { network: net("192.168.1.0/24"), metric: 30, metric_type: 1, tag: 7 });
+        assert_eq!(config.redistribute[1], ExternalRoute { network: net("192.168.2.0/24"), metric: 20, metric_type: 2, tag: 0 });
+        assert_eq!(config.summary_addresses, vec![SummaryAddress { network: net("192.168.0.0/16"), tag: 5, advertise: false }]);
+        assert!(Config::parse("redistribute static 192.168.1.0/24 metric-type 3").is_err());
+        assert!(Config::parse("redistribute static 192.168.1.0/24 metric 16777215").is_err());
+        let config = Config::parse("prefix-list lab deny 10.99.1.0/16 le 32\nprefix-list lab permit 0.0.0.0/0 le 32\narea 1 filter-list prefix lab out").unwrap();
+        let lab = &config.prefix_lists["lab"];
+        assert!(!lab.permits(net("10.99.2.0/24")) && lab.permits(net("10.98.2.0/24")));
+        assert_eq!(lab.next_seq(), 15);
+        assert_eq!(config.area_filters, vec![(hex2ip(1), "lab".to_string(), false)]);
+        assert!(Config::parse("prefix-list lab permit 10.0.0.0/8 ge 8").is_err());
+        assert!(Config::parse("prefix-list lab permit 10.0.0.0/8 ge 24 le 16").is_err());
+        let config = Config::parse("route-map edge 10 deny\nroute-map edge 10 match path-type external-2\nroute-map edge 20 set tag 5\nredistribute static route-map edge\nfib-filter route-map edge").unwrap();
+        let mut edge = RouteMap::default();
+        edge.clause(10).permit = false;
+        edge.clause(10).matches.push(Match::PathType(crate::database::RoutingTablePathType::AsExternalT2));
+        edge.clause(20).sets.push(Set::Tag(5));
+        assert_eq!(config.route_maps["edge"], edge);
+        assert_eq!(config.redistribute_route_map.as_deref(), Some("edge"));
+        assert_eq!(config.fib_filter.as_deref(), Some("edge"));
+        assert!(Config::parse("route-map edge 10 match path-type external").is_err());
+        let config = Config::parse("auto-cost reference-bandwidth 100000\ninterface eth0 cost 10").unwrap();
+        assert_eq!((config.reference_bandwidth(), config.interface("eth0").cost), (100000, Some(10)));
+        assert_eq!(Config::default().reference_bandwidth(), 100);
+        assert!(Config::parse("interface eth0 cost 0").is_err());
+        assert!(Config::parse("interface eth0 cost 65536").is_err());
+        let config = Config::parse("log level notice\ndebug ospf spf\nlog file /tmp/ospfd.log rotate 2\nlog syslog").unwrap();
+        assert_eq!((config.log.level, config.log.debug.as_slice()), (Some(Level::Notice), &[Subsystem::Spf][..]));
+        let file = config.log.file.unwrap();
+        assert_eq!((file.size, file.rotate), (logging::DEFAULT_FILE_SIZE, 2));
+        assert_eq!(config.log.syslog, Some(logging::DEFAULT_SYSLOG.into()));
+        assert!(Config::parse("debug ospf bgp").is_err());
+        let config = Config::parse("metrics listen 127.0.0.1:9400").unwrap();
+        assert_eq!(config.metrics_listen, Some("127.0.0.1:9400".parse().unwrap()));
+        assert!(Config::parse("metrics listen 127.0.0.1").is_err());
+        assert!(matches!(
+            Config::parse("interface eth1 active"),
+            Err(ConfigError::BadLine(1, _))
+        ));
+    }
+}
@@ -9,6 +9,7 @@ pub const LsaMaxAge: u16 = 3600;
 pub const CheckAge: u32 = 300;
 pub const MaxAgeDiff: u16 = 900;
 pub const LSInfinity: u32 = 0xffffff;
+pub const MaxLinkMetric: u16 = 0xffff;
 pub const DefaultDestination: u32 = 0;
 pub const InitialSequenceNumber: i32 = -0x7fffffff;
 pub const MaxSequenceNumber: i32 = 0x7fffffff;
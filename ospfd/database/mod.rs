@@ -3,84 +3,250 @@ mod vlink;
 pub use routing::*;
 pub use vlink::VirtualLink;
 
-use std::{collections::HashMap, net::Ipv4Addr, sync::OnceLock, time::Instant};
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
 
 use lazy_static::lazy_static;
 pub use ospf_packet::lsa::LsaIndex;
-use ospf_packet::lsa::{Lsa, LsaHeader};
-use tokio::sync::{Mutex, MutexGuard};
+use ospf_packet::{
+    lsa::{types::OPAQUE_LINK_LSA, Lsa, LsaHeader},
+    packet::LSUpdate,
+};
+use tokio::sync::{Mutex, MutexGuard, OwnedMutexGuard};
 
 use crate::{
-    area::{Area, BackboneDB},
-    interface::{AInterface, Interface},
+    area::{Area, BackboneDB, LinkDB},
+    config::{Config, ExternalRoute},
+    gen_lsa, guard,
+    interface::{self, AInterface, Interface, InterfaceEvent, InterfaceState},
+    log_success, must,
+    neighbor::NeighborState,
+    route_map,
+    sender::send_packet,
 };
 
-static ROUTER_ID: OnceLock<Ipv4Addr> = OnceLock::new();
-static INTERFACES: OnceLock<Vec<AInterface>> = OnceLock::new();
+static ROUTER_ID: RwLock<Ipv4Addr> = RwLock::new(Ipv4Addr::UNSPECIFIED);
+/// 正在修改路由器标识：等待邻居确认清除的 LSA，期间不生成新的 LSA
+static CHANGING_ROUTER_ID: AtomicBool = AtomicBool::new(false);
+/// 修改路由器标识时，清除的 LSA 最多重传的次数
+const FLUSH_RETRIES: usize = 4;
+/// 所有启用的接口，加锁时必须按此列表的顺序
+static INTERFACES: RwLock<Vec<AInterface>> = RwLock::new(Vec::new());
 
 pub struct ProtocolDB {
     pub areas: HashMap<Ipv4Addr, Area>,
+    /// 接口地址 -> 链路范围的 LSA
+    pub links: HashMap<Ipv4Addr, LinkDB>,
     pub backbone: BackboneDB,
     pub virtual_links: Vec<VirtualLink>,
-    pub external_routes: Vec<Ipv4Addr>,
+    /// 引入的外部路由
+    pub external_routes: Vec<ExternalRoute>,
     pub routing_table: RoutingTable,
 }
 
 lazy_static! {
     static ref DATABASE: Mutex<ProtocolDB> = Mutex::new(ProtocolDB {
         areas: HashMap::new(),
+        links: HashMap::new(),
         backbone: BackboneDB::new(),
         virtual_links: Vec::new(),
-        external_routes: Vec::new(),
+        external_routes: route_map::redistribute(),
         routing_table: RoutingTable::new(),
     });
 }
 
 type Guard<T> = MutexGuard<'static, T>;
+pub type InterfaceGuard = OwnedMutexGuard<Interface>;
 
 /// # Safety
 /// 锁的使用要求：
 /// 只有 INTERFACES 和 DATABASE 有锁。
 /// 1. DATABASE 的锁必须在持有任何 INTERFACE 的锁时才能获取。
 /// 2. 任何异步调用，要么持有一把 INTERFACE 的锁，要么通过 get_interfaces 获取所有 INTERFACE 的锁。
-/// 3. 在已经持有一把 INTERFACE 的锁的情况下，可以通过 upgrade_lock 升级锁。
+/// 3. 在已经持有一把 INTERFACE 的锁的情况下，可以通过 upgrade_lock 升级锁；
+///    升级时会短暂释放锁，接口可能在此期间被删除。
 impl ProtocolDB {
     pub fn init(interfaces: &Vec<AInterface>) {
         use tokio::task::block_in_place;
-        INTERFACES.get_or_init(|| interfaces.clone());
-        ROUTER_ID.get_or_init(|| {
-            interfaces
-                .iter()
-                .map(|i| block_in_place(|| i.blocking_lock().ip_addr))
-                .min()
-                .unwrap()
-        });
+        INTERFACES.write().unwrap().extend(interfaces.iter().cloned());
+        // 优先使用配置的标识，其次是上次保存的标识，最后选择最小的接口地址
+        let config = Config::get();
+        let id = config
+            .router_id
+            .or_else(|| config.load_router_id())
+            .unwrap_or_else(|| {
+                interfaces
+                    .iter()
+                    .map(|i| block_in_place(|| i.blocking_lock().ip_addr))
+                    .min()
+                    .unwrap()
+            });
+        *ROUTER_ID.write().unwrap() = id;
+        config.save_router_id(id);
     }
 
     pub fn get_router_id() -> Ipv4Addr {
-        *ROUTER_ID.get().unwrap()
+        *ROUTER_ID.read().unwrap()
+    }
+
+    /// 是否正在修改路由器标识
+    pub fn changing_router_id() -> bool {
+        CHANGING_ROUTER_ID.load(Ordering::Relaxed)
+    }
+
+    /// 运行时修改路由器标识：清除自身在各个范围生成的 LSA，等待邻居确认后重置所有邻接，
+    /// 再立即重新生成各区域的 LSA
+    ///
+    /// # Safety
+    /// This function should be awaited when caller hasn't have any locks.
+    pub async fn set_router_id(id: Ipv4Addr) {
+        let old = Self::get_router_id();
+        must!(old != id);
+        let mut interfaces = tokio::task::block_in_place(Self::get_interfaces_impl);
+        must!(!interfaces.is_empty(); else: *ROUTER_ID.write().unwrap() = id);
+        CHANGING_ROUTER_ID.store(true, Ordering::Relaxed);
+        // 接口地址 -> 在该接口上洪泛的清除的 LSA
+        let mut flushed: HashMap<Ipv4Addr, Vec<Lsa>> = HashMap::new();
+        let areas: Vec<_> = Self::get().await.areas.keys().copied().collect();
+        for area_id in areas.iter() {
+            guard!(Some(ip) = interfaces.iter().find(|i| i.area_id == *area_id).map(|i| i.ip_addr); continue);
+            let mut guard = InterfacesGuard::from(interfaces, ip).unwrap();
+            let lsas = gen_lsa::flush_lsa(&mut guard, old).await;
+            for iface in guard.iter().filter(|i| i.area_id == *area_id) {
+                flushed.entry(iface.ip_addr).or_default().extend(lsas.iter().cloned());
+            }
+            interfaces = guard.into_iter().collect();
+        }
+        let ips: Vec<_> = interfaces.iter().map(|i| i.ip_addr).collect();
+        for ip in ips {
+            let mut guard = InterfacesGuard::from(interfaces, ip).unwrap();
+            let lsas = gen_lsa::flush_link_lsa(&mut guard, old).await;
+            flushed.entry(ip).or_default().extend(lsas);
+            interfaces = guard.into_iter().collect();
+        }
+        let mut guard: InterfacesGuard = interfaces.into();
+        let lsas = gen_lsa::flush_as_lsa(&mut guard, old).await;
+        for iface in guard.iter().filter(|i| i.external_routing) {
+            flushed.entry(iface.ip_addr).or_default().extend(lsas.iter().cloned());
+        }
+        drop(guard);
+        // 重置邻接会清空重传列表，必须先等邻居确认清除的 LSA
+        Self::wait_flushed(&flushed).await;
+        let mut interfaces = tokio::task::block_in_place(Self::get_interfaces_impl);
+        *ROUTER_ID.write().unwrap() = id;
+        CHANGING_ROUTER_ID.store(false, Ordering::Relaxed);
+        Config::get().save_router_id(id);
+        log_success!("router id is changed: {} -> {}", old, id);
+        for iface in interfaces.iter_mut() {
+            must!(!matches!(iface.state, InterfaceState::Down | InterfaceState::Loopback); continue);
+            iface.interface_down().await;
+            iface.interface_up().await;
+        }
+        // 等待期间接口可能已经全部删除
+        must!(!interfaces.is_empty());
+        interface::originate_areas(interfaces.into()).await;
+    }
+
+    /// 按重传间隔向还没有确认的邻居重新发送清除的 LSA，直到全部确认或者达到重传次数
+    ///
+    /// 清除的 LSA 已经从数据库中删除，接口的重传定时器找不到它们，所以在这里重传
+    async fn wait_flushed(flushed: &HashMap<Ipv4Addr, Vec<Lsa>>) {
+        must!(flushed.values().any(|lsas| !lsas.is_empty()));
+        // 邻居通常立即确认，第一次只等待一秒
+        let mut wait = Duration::from_secs(1);
+        for _ in 0..FLUSH_RETRIES {
+            tokio::time::sleep(wait).await;
+            let mut interfaces = tokio::task::block_in_place(Self::get_interfaces_impl);
+            let mut pending = false;
+            for iface in interfaces.iter_mut() {
+                guard!(Some(lsas) = flushed.get(&iface.ip_addr); continue);
+                let packets: Vec<_> = iface
+                    .neighbors
+                    .values()
+                    .filter(|n| n.state >= NeighborState::Exchange)
+                    .filter_map(|n| {
+                        let lsa: Vec<_> = lsas
+                            .iter()
+                            .filter(|lsa| n.ls_retransmission_list.contains(&lsa.header.into()))
+                            .cloned()
+                            .collect();
+                        must!(!lsa.is_empty(); ret: None);
+                        Some((LSUpdate { num_lsa: lsa.len() as u32, lsa }, n.ip_addr))
+                    })
+                    .collect();
+                pending |= !packets.is_empty();
+                for (packet, ip) in packets {
+                    send_packet(iface, &packet, ip).await;
+                }
+            }
+            must!(pending; break);
+            wait = Duration::from_secs(interfaces.iter().map(|i| i.rxmt_interval).max().unwrap_or(1) as u64);
+        }
+    }
+
+    /// 修改区域范围，advertise 为 None 时删除范围，区域不存在时返回 false
+    ///
+    /// # Safety
+    /// This function should be awaited when caller hasn't have any locks.
+    pub async fn set_area_range(
+        area_id: Ipv4Addr,
+        range: (Ipv4Addr, Ipv4Addr),
+        advertise: Option<bool>,
+    ) -> bool {
+        let interfaces = tokio::task::block_in_place(Self::get_interfaces_impl);
+        let mut db = Self::get().await;
+        guard!(Some(area) = db.areas.get_mut(&area_id); ret: false);
+        match advertise {
+            Some(advertise) => area.addr_range.insert(range, advertise),
+            None => area.addr_range.remove(&range),
+        };
+        db.recalc_routing().await;
+        drop(db);
+        drop(interfaces);
+        // 由接口检查重新生成 Summary-LSA
+        interface::notify_changed();
+        true
+    }
+
+    pub fn get_interface_list() -> Vec<AInterface> {
+        INTERFACES.read().unwrap().clone()
+    }
+
+    pub fn add_interface(iface: AInterface) {
+        INTERFACES.write().unwrap().push(iface);
+    }
+
+    pub fn remove_interface(iface: &AInterface) {
+        INTERFACES.write().unwrap().retain(|i| !Arc::ptr_eq(i, iface));
     }
 
     /// # Safety
     /// This function should be awaited when caller hasn't have any locks.
-    pub fn get_interfaces_impl() -> Vec<Guard<Interface>> {
-        INTERFACES
-            .get()
-            .unwrap()
-            .iter()
-            .map(|iface| iface.blocking_lock())
+    pub fn get_interfaces_impl() -> Vec<InterfaceGuard> {
+        Self::get_interface_list()
+            .into_iter()
+            .map(|iface| iface.blocking_lock_owned())
             .collect()
     }
 
     /// # Safety
     /// This function should be awaited when caller hasn't have any locks.
-    pub fn get_interface_by_name(name: &str) -> Option<Guard<Interface>> {
+    pub fn get_interfaces_by_name(name: &str) -> Vec<InterfaceGuard> {
         Self::get_interfaces_impl()
             .into_iter()
-            .find(|iface| iface.interface_name == name)
+            .filter(|iface| iface.interface_name == name)
+            .collect()
     }
 
-    pub async fn upgrade_lock(iface: MutexGuard<'_, Interface>) -> InterfacesGuard {
+    /// 接口在释放锁的期间已经被删除时返回 None
+    pub async fn upgrade_lock(iface: MutexGuard<'_, Interface>) -> Option<InterfacesGuard> {
         let ip = iface.ip_addr;
         drop(iface);
         let interfaces = tokio::task::block_in_place(Self::get_interfaces_impl);
@@ -100,21 +266,60 @@ impl ProtocolDB {
     }
 
     pub async fn recalc_routing(&mut self) {
+        // 平滑重启期间保持内核中的路由不变
+        must!(!crate::gr::restarting());
         self.routing_table
             .recalculate(self.areas.values_mut().collect())
             .await;
     }
 }
 
+trait LsType {
+    fn ls_type(&self) -> u8;
+}
+
+impl LsType for LsaIndex {
+    fn ls_type(&self) -> u8 {
+        self.ls_type
+    }
+}
+
+impl LsType for LsaHeader {
+    fn ls_type(&self) -> u8 {
+        self.ls_type
+    }
+}
+
+impl LsType for Lsa {
+    fn ls_type(&self) -> u8 {
+        self.header.ls_type
+    }
+}
+
+impl LsType for &Lsa {
+    fn ls_type(&self) -> u8 {
+        self.header.ls_type
+    }
+}
+
+/// 按 LSA 的洪泛范围选择数据库：链路范围的保存在接口上，其余的保存在接口所属的区域中
 macro_rules! delegating {
     ($func:ident, $param:ty $(,$ret:ty)?) => {
-        pub async fn $func(&self, area_id: Ipv4Addr, key: $param) $(-> $ret)? {
-            self.areas.get(&area_id).unwrap().$func(key).await
+        pub async fn $func(&self, iface: &Interface, key: $param) $(-> $ret)? {
+            if key.ls_type() == OPAQUE_LINK_LSA {
+                let empty = LinkDB::new(iface.ip_addr);
+                return self.links.get(&iface.ip_addr).unwrap_or(&empty).$func(key).await;
+            }
+            self.areas.get(&iface.area_id).unwrap().$func(key).await
         }
     };
     ($func:ident, mut, $param:ty $(,$ret:ty)?) => {
-        pub async fn $func(&mut self, area_id: Ipv4Addr, key: $param) $(-> $ret)? {
-            self.areas.get_mut(&area_id).unwrap().$func(key).await
+        pub async fn $func(&mut self, iface: &Interface, key: $param) $(-> $ret)? {
+            if key.ls_type() == OPAQUE_LINK_LSA {
+                let ip = iface.ip_addr;
+                return self.links.entry(ip).or_insert_with(|| LinkDB::new(ip)).$func(key).await;
+            }
+            self.areas.get_mut(&iface.area_id).unwrap().$func(key).await
         }
     };
 }
@@ -128,12 +333,12 @@ impl ProtocolDB {
 }
 
 pub struct InterfacesGuard {
-    pub me: Guard<Interface>,
-    pub other: Vec<Guard<Interface>>,
+    pub me: InterfaceGuard,
+    pub other: Vec<InterfaceGuard>,
 }
 
-impl From<Vec<Guard<Interface>>> for InterfacesGuard {
-    fn from(value: Vec<Guard<Interface>>) -> Self {
+impl From<Vec<InterfaceGuard>> for InterfacesGuard {
+    fn from(value: Vec<InterfaceGuard>) -> Self {
         let mut iter = value.into_iter();
         Self {
             me: iter.next().unwrap(),
@@ -143,22 +348,23 @@ impl From<Vec<Guard<Interface>>> for InterfacesGuard {
 }
 
 impl InterfacesGuard {
-    fn from(mut vec: Vec<MutexGuard<'static, Interface>>, ip: Ipv4Addr) -> Self {
-        let me = vec.swap_remove(vec.iter().position(|i| i.ip_addr == ip).unwrap());
-        Self { me, other: vec }
+    /// vec 中没有地址为 ip 的接口时返回 None
+    pub fn from(mut vec: Vec<InterfaceGuard>, ip: Ipv4Addr) -> Option<Self> {
+        let me = vec.swap_remove(vec.iter().position(|i| i.ip_addr == ip)?);
+        Some(Self { me, other: vec })
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &Guard<Interface>> {
+    pub fn iter(&self) -> impl Iterator<Item = &InterfaceGuard> {
         std::iter::once(&self.me).chain(self.other.iter())
     }
 
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Guard<Interface>> {
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut InterfaceGuard> {
         std::iter::once(&mut self.me).chain(self.other.iter_mut())
     }
 }
 
 impl IntoIterator for InterfacesGuard {
-    type Item = Guard<Interface>;
+    type Item = InterfaceGuard;
     type IntoIter = std::iter::Chain<std::iter::Once<Self::Item>, std::vec::IntoIter<Self::Item>>;
 
     fn into_iter(self) -> Self::IntoIter {
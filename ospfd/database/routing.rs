@@ -1,30 +1,64 @@
-use std::{collections::HashMap, net::Ipv4Addr};
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::Ipv4Addr,
+    time::Instant,
+};
 
 use ospf_packet::lsa::LsaIndex;
-use ospf_routing::{add_route as lib_add_route, delete_route as lib_delete_route, RoutingItem};
+use ospf_routing::{
+    add_discard_route, add_route as lib_add_route, delete_discard_route,
+    delete_route as lib_delete_route, RoutingItem,
+};
 
 use crate::{
     area::Area,
     constant::{BackboneArea, LSInfinity},
     database::ProtocolDB,
-    guard, log_error, must,
+    guard, json,
+    json::{Json, ToJson},
+    log_debug, log_error, must, route_map, router_info, statistics,
     util::ip2hex,
 };
 
 #[derive(Debug, Clone)]
 pub struct RoutingTable {
     table: HashMap<RoutingTableIndex, RoutingTableItem>,
+    /// 宣告的区域范围对应的丢弃路由
+    discards: BTreeSet<Ipv4AddrMask>,
+    /// 外部路由汇总地址对应的丢弃路由
+    external_discards: BTreeSet<Ipv4AddrMask>,
+    /// 平滑重启前写入内核、重启后还没有重新计算过的路由
+    kept: Vec<RoutingItem>,
 }
 
 impl RoutingTable {
     pub fn new() -> Self {
         RoutingTable {
             table: HashMap::new(),
+            discards: BTreeSet::new(),
+            external_discards: BTreeSet::new(),
+            kept: vec![],
         }
     }
 
+    /// 平滑重启后恢复上一次运行写入内核的路由和丢弃路由，
+    /// 之后第一次计算时只修改有变化的路由，不重复写入没有变化的路由
+    pub fn restore(
+        &mut self,
+        routes: Vec<RoutingItem>,
+        discards: BTreeSet<Ipv4AddrMask>,
+        external_discards: BTreeSet<Ipv4AddrMask>,
+    ) {
+        self.kept = routes;
+        self.discards = discards;
+        self.external_discards = external_discards;
+    }
+
     pub async fn recalculate(&mut self, mut areas: Vec<&mut Area>) {
+        log_debug!(Spf; "recalculating routing table for {} areas", areas.len());
+        let start = Instant::now();
         let old_table = std::mem::take(&mut self.table);
+        let kept = std::mem::take(&mut self.kept);
         for area in areas.iter_mut() {
             area.recalc_routing();
             area.get_routing().into_iter().for_each(|item| {
@@ -82,6 +116,7 @@ impl RoutingTable {
                 lsa_origin: header.into(),
                 next_hop: forwarding.next_hop,
                 ad_router: header.advertising_router,
+                tag: lsa.external_router_tag,
             };
             self.table
                 .entry(item.into())
@@ -92,20 +127,58 @@ impl RoutingTable {
                 })
                 .or_insert(item);
         }
+        statistics::spf(start.elapsed());
+        log_debug!(Spf; "routing table has {} entries", self.table.len());
         old_table.iter().for_each(|(k, old)| {
-            guard!(Ok(old) = RoutingItem::try_from(old));
+            guard!(Some(old) = kernel_route(old));
             let new = self.table.get(k);
-            if !new.is_some_and(|new| new.try_into().is_ok_and(|new| old == new)) {
-                delete_route(old).unwrap_or_else(|e| log_error!("Error(delete route): {:?}", e));
+            if !new.and_then(kernel_route).is_some_and(|new| old == new) {
+                log_debug!(Fib; "delete route {:?}", old);
+                delete_route(old).unwrap_or_else(|e| fib_error("delete", e));
             }
         });
+        // 重启前留下、现在已不在路由表中的路由
+        if !kept.is_empty() {
+            let current = self.kernel_routes();
+            for r in kept.iter().filter(|r| !current.contains(r)) {
+                log_debug!(Fib; "delete route {:?}", r);
+                delete_route(*r).unwrap_or_else(|e| fib_error("delete", e));
+            }
+        }
         self.table.iter().for_each(|(k, new)| {
-            guard!(Ok(new) = RoutingItem::try_from(new));
+            guard!(Some(new) = kernel_route(new));
+            must!(!kept.contains(&new));
             let old = old_table.get(k);
-            if !old.is_some_and(|old| old.try_into().is_ok_and(|old| new == old)) {
-                add_route(new).unwrap_or_else(|e| log_error!("Error(add route): {:?}", e));
+            if !old.and_then(kernel_route).is_some_and(|old| new == old) {
+                log_debug!(Fib; "add route {:?}", new);
+                add_route(new).unwrap_or_else(|e| fib_error("add", e));
             }
         });
+        let discards = self.active_ranges(&areas);
+        update_discards(&self.discards, &discards);
+        self.discards = discards;
+    }
+
+    /// 更新外部路由汇总地址的丢弃路由
+    pub fn set_external_discards(&mut self, discards: BTreeSet<Ipv4AddrMask>) {
+        update_discards(&self.external_discards, &discards);
+        self.external_discards = discards;
+    }
+
+    /// 正在宣告的区域范围：作为区域边界路由器时，范围内至少有一条区域内网络路由
+    fn active_ranges(&self, areas: &[&mut Area]) -> BTreeSet<Ipv4AddrMask> {
+        let mut ranges = BTreeSet::new();
+        must!(areas.len() > 1; ret: ranges);
+        for item in self.table.values() {
+            must!(item.dest_type == RoutingTableItemType::Network; continue);
+            must!(item.path_type == RoutingTablePathType::AreaInternal; continue);
+            guard!(Some(area) = areas.iter().find(|a| a.area_id == item.area_id); continue);
+            guard!(Some(((addr, mask), true)) = area.range_of(item.dest_id, item.addr_mask); continue);
+            ranges.insert(Ipv4AddrMask::from(addr, mask));
+        }
+        // 和路由表中已有的网络相同时不需要丢弃路由
+        ranges.retain(|r| !self.table.contains_key(&RoutingTableIndex::Network(*r)));
+        ranges
     }
 
     pub fn get_routing(&self, ip: Ipv4Addr) -> Option<&RoutingTableItem> {
@@ -119,14 +192,61 @@ impl RoutingTable {
         self.table.values().collect()
     }
 
+    /// 已经写入内核的路由
+    pub fn kernel_routes(&self) -> Vec<RoutingItem> {
+        self.table
+            .values()
+            .filter_map(kernel_route)
+            .filter(|r| r.nexthop != Ipv4Addr::UNSPECIFIED)
+            .collect()
+    }
+
+    /// 宣告的区域范围对应的丢弃路由
+    pub fn discards(&self) -> &BTreeSet<Ipv4AddrMask> {
+        &self.discards
+    }
+
+    /// 外部路由汇总地址对应的丢弃路由
+    pub fn external_discards(&self) -> &BTreeSet<Ipv4AddrMask> {
+        &self.external_discards
+    }
+
     pub fn delete_all_routing(&self) {
         for item in self.table.values() {
-            guard!(Ok(r) = RoutingItem::try_from(item); continue);
-            delete_route(r).unwrap_or_else(|e| log_error!("Error(delete route): {:?}", e));
+            guard!(Some(r) = kernel_route(item); continue);
+            delete_route(r).unwrap_or_else(|e| fib_error("delete", e));
+        }
+        for r in self.kept.iter() {
+            delete_route(*r).unwrap_or_else(|e| fib_error("delete", e));
+        }
+        for r in self.discards.iter().chain(self.external_discards.iter()) {
+            delete_discard_route(r.into()).unwrap_or_else(|e| fib_error("delete", e));
         }
     }
 }
 
+/// 网络路由经过 fib-filter 策略允许后才写入内核
+fn kernel_route(item: &RoutingTableItem) -> Option<RoutingItem> {
+    let r = RoutingItem::try_from(item).ok()?;
+    must!(route_map::fib_permits(item); ret: None);
+    Some(r)
+}
+
+/// 写入内核失败时记录日志并计数
+fn fib_error(op: &str, e: impl std::fmt::Debug) {
+    statistics::fib_error();
+    log_error!(Fib; "Error({} route): {:?}", op, e);
+}
+
+fn update_discards(old: &BTreeSet<Ipv4AddrMask>, new: &BTreeSet<Ipv4AddrMask>) {
+    for r in old.difference(new) {
+        delete_discard_route(r.into()).unwrap_or_else(|e| fib_error("delete", e));
+    }
+    for r in new.difference(old) {
+        add_discard_route(r.into()).unwrap_or_else(|e| fib_error("add", e));
+    }
+}
+
 /// insert a route into the routing table
 /// if the route already exists, delete it first
 fn add_route(r: RoutingItem) -> Result<(), std::io::Error> {
@@ -181,6 +301,16 @@ impl Ipv4AddrMask {
     }
 }
 
+impl From<&Ipv4AddrMask> for RoutingItem {
+    fn from(value: &Ipv4AddrMask) -> Self {
+        RoutingItem {
+            dest: value.network(),
+            mask: value.mask(),
+            nexthop: Ipv4Addr::UNSPECIFIED,
+        }
+    }
+}
+
 impl Default for Ipv4AddrMask {
     fn default() -> Self {
         Ipv4AddrMask(Ipv4Addr::UNSPECIFIED, 0)
@@ -228,6 +358,8 @@ pub struct RoutingTableItem {
     pub next_hop: Ipv4Addr,
     /// 宣告路由器/Advertising router
     pub ad_router: Ipv4Addr,
+    /// 外部路由标记/External route tag
+    pub tag: u32,
 }
 
 impl RoutingTableItem {
@@ -279,12 +411,62 @@ impl std::fmt::Display for RoutingTable {
         writeln!(f, "OSPF Routing Table")?;
         for item in self.table.values() {
             guard!(Ok(r) = RoutingItem::try_from(item); continue);
+            let filtered = if route_map::fib_permits(item) { "" } else { ", not installed" };
             writeln!(
                 f,
-                "{}, cost: {}/{}, area: {}, type: {:?}",
-                r, item.cost, item.cost_t2, item.area_id, item.path_type
+                "{}, cost: {}/{}, area: {}, type: {:?}, adv: {}{}",
+                r, item.cost, item.cost_t2, item.area_id, item.path_type, router_info::name(item.ad_router), filtered
             )?;
         }
+        for r in self.discards.iter() {
+            writeln!(f, "{}, discard (area range)", r)?;
+        }
+        for r in self.external_discards.iter() {
+            writeln!(f, "{}, discard (summary address)", r)?;
+        }
         Ok(())
     }
 }
+
+impl ToJson for RoutingTableItem {
+    fn to_json(&self) -> Json {
+        let dest_type = match self.dest_type {
+            RoutingTableItemType::Network => "network",
+            RoutingTableItemType::Router => "router",
+        };
+        json!({
+            "dest_type": dest_type,
+            "destination": self.dest_id,
+            "mask": self.addr_mask,
+            "path_type": route_map::path_type_name(self.path_type),
+            "cost": self.cost,
+            "type2_cost": self.cost_t2,
+            "area": self.area_id,
+            "next_hop": self.next_hop,
+            "advertising_router": self.ad_router,
+            "tag": self.tag,
+            "lsa_origin": json!({
+                "type": self.lsa_origin.ls_type,
+                "link_state_id": self.lsa_origin.ls_id,
+                "advertising_router": self.lsa_origin.ad_router,
+            }),
+            "installed": self.dest_type == RoutingTableItemType::Network && route_map::fib_permits(self),
+        })
+    }
+}
+
+/// 网络路由和到区域边界路由器、AS 边界路由器的路由，以及丢弃路由
+impl ToJson for RoutingTable {
+    fn to_json(&self) -> Json {
+        let mut items: Vec<_> = self.table.values().collect();
+        items.sort_by_key(|item| (item.dest_type == RoutingTableItemType::Router, item.dest_id, item.addr_mask));
+        let discards = |set: &BTreeSet<Ipv4AddrMask>, reason: &str| {
+            set.iter()
+                .map(|r| json!({ "destination": r.to_string(), "reason": reason }))
+                .collect::<Vec<_>>()
+        };
+        let mut all = discards(&self.discards, "area range");
+        all.extend(discards(&self.external_discards, "summary address"));
+        json!({ "routes": items.into_iter().collect::<Json>(), "discards": all })
+    }
+}
@@ -1,28 +1,27 @@
 use std::{cmp::Ordering, net::Ipv4Addr};
 
 use ospf_packet::{
-    lsa::{types::AS_EXTERNAL_LSA, Lsa, LsaIndex},
-    packet::LSUpdate,
+    lsa::{types, Lsa, LsaIndex},
+    packet::{options, LSUpdate},
 };
 
 use crate::{
     constant::{AllDRouters, AllSPFRouters, LsaMaxAge},
     database::InterfacesGuard,
     interface::Interface,
-    must,
+    log_debug, must,
     sender::send_packet,
+    statistics,
 };
 
 pub async fn flooding(interfaces: &mut InterfacesGuard, src_ip: Ipv4Addr, lsa: &Lsa) -> bool {
     let lsa_area = interfaces.me.area_id;
     let me = interfaces.me.ip_addr;
     // 合格接口
-    let ac_iface = interfaces.iter_mut().filter(|iface| {
-        if lsa.header.ls_type == AS_EXTERNAL_LSA {
-            iface.external_routing
-        } else {
-            iface.area_id == lsa_area
-        }
+    let ac_iface = interfaces.iter_mut().filter(|iface| match lsa.header.ls_type {
+        types::OPAQUE_LINK_LSA => iface.ip_addr == me,
+        ls_type if types::is_as_scope(ls_type) => iface.external_routing,
+        _ => iface.area_id == lsa_area,
     });
     // 逐个接口处理
     let rt = tokio::runtime::Handle::current();
@@ -31,7 +30,9 @@ pub async fn flooding(interfaces: &mut InterfacesGuard, src_ip: Ipv4Addr, lsa: &
             .map(|mut i| rt.block_on(flooding_on(&mut i, me, src_ip, lsa)))
             .collect()
     });
-    result.into_iter().any(|b| b)
+    let flooded = result.into_iter().any(|b| b);
+    log_debug!(Flooding; "flooding {:?} from {}: {}", lsa.header, src_ip, if flooded { "sent" } else { "not sent" });
+    flooded
 }
 
 async fn flooding_on(iface: &mut Interface, me: Ipv4Addr, src: Ipv4Addr, lsa: &Lsa) -> bool {
@@ -40,6 +41,8 @@ async fn flooding_on(iface: &mut Interface, me: Ipv4Addr, src: Ipv4Addr, lsa: &L
     for neighbor in iface.neighbors.values_mut() {
         // （a）如果邻居状态小于 Exchange，它不参与洪泛，检查下一个邻居。
         must!(neighbor.state >= crate::neighbor::NeighborState::Exchange; continue);
+        // 不透明 LSA 只发给支持的邻居（O 位）
+        must!(!types::is_opaque(lsa.header.ls_type) || neighbor.option & options::O != 0; continue);
         // （b）如果邻接还没有完全，检查邻接所关联的连接状态请求列表。如果存在有该 LSA 的实例，表示邻居已经有了该 LSA。
         if let Some(index) = neighbor
             .ls_request_list
@@ -97,5 +100,6 @@ async fn flooding_on(iface: &mut Interface, me: Ipv4Addr, src: Ipv4Addr, lsa: &L
         AllSPFRouters
     };
     send_packet(iface, &packet, dest).await;
+    statistics::lsa_flooded(&iface.interface_name);
     true
 }
@@ -1,17 +1,29 @@
-use std::{marker::PhantomData, net::Ipv4Addr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    net::Ipv4Addr,
+};
 
 use ospf_packet::{
     lsa::{link_types::*, types::*, *},
     packet::options,
 };
+use pnet::ipnetwork::Ipv4Network;
 
 use crate::{
-    constant::{BackboneArea, InitialSequenceNumber, LSInfinity, LsRefreshTime, MaxSequenceNumber},
-    database::{InterfacesGuard, ProtocolDB, RoutingTableItemType, RoutingTablePathType},
+    area::Area,
+    config::{Config, ExternalRoute},
+    constant::{
+        BackboneArea, InitialSequenceNumber, LSInfinity, LsRefreshTime, LsaMaxAge,
+        MaxSequenceNumber,
+    },
+    database::{
+        InterfacesGuard, Ipv4AddrMask, ProtocolDB, RoutingTableItemType, RoutingTablePathType,
+    },
     flooding::flooding,
+    gr, guard,
     interface::{InterfaceState, NetType},
-    log_warning, must,
-    neighbor::NeighborState,
+    must, statistics, stub_router,
 };
 
 pub async fn gen_router_lsa(interfaces: &mut InterfacesGuard) {
@@ -27,7 +39,27 @@ pub async fn gen_router_lsa(interfaces: &mut InterfacesGuard) {
         must!(iface.area_id == interfaces.me.area_id; continue);
         must!(iface.state != InterfaceState::Down; continue);
         if iface.state == InterfaceState::Loopback {
-            log_warning!("todo: loopback interface");
+            // 环回接口加入类型 3 连接，以主机路由宣告接口地址，距离值为 0
+            let secondary = iface.secondary.iter().map(|n| n.ip());
+            for ip in std::iter::once(iface.ip_addr).chain(secondary) {
+                lsa.links.push(RouterLSALink {
+                    link_id: ip,
+                    link_data: Ipv4Addr::BROADCAST,
+                    link_type: STUB_LINK,
+                    tos: 0,
+                    metric: 0,
+                });
+            }
+            continue;
+        } else if iface.passive {
+            // 被动接口没有邻居，只加入类型 3 连接（存根网络）
+            lsa.links.push(RouterLSALink {
+                link_id: network_number(iface.ip_addr, iface.ip_mask),
+                link_data: iface.ip_mask,
+                link_type: STUB_LINK,
+                tos: 0,
+                metric: iface.cost,
+            });
         } else {
             assert!(matches!(iface.net_type, NetType::Broadcast | NetType::NBMA));
             if iface.is_dr() && !iface.neighbors.is_empty()
@@ -37,19 +69,20 @@ pub async fn gen_router_lsa(interfaces: &mut InterfacesGuard) {
                 || iface
                     .neighbors
                     .get(&iface.dr)
-                    .is_some_and(|n| n.state == NeighborState::Full)
+                    .is_some_and(|n| gr::is_adjacent(iface, n.router_id))
             {
+                // 最大度量状态下只改变非存根连接的度量
                 lsa.links.push(RouterLSALink {
                     link_id: iface.dr,
                     link_data: iface.ip_addr,
                     link_type: TRANSIT_LINK,
                     tos: 0,
-                    metric: iface.cost,
+                    metric: stub_router::link_metric(iface.cost),
                 });
             } else {
                 // 否则，加入类型 3 连接（存根网络）
                 lsa.links.push(RouterLSALink {
-                    link_id: iface.ip_addr,
+                    link_id: network_number(iface.ip_addr, iface.ip_mask),
                     link_data: iface.ip_mask,
                     link_type: STUB_LINK,
                     tos: 0,
@@ -57,12 +90,27 @@ pub async fn gen_router_lsa(interfaces: &mut InterfacesGuard) {
                 });
             }
         }
+        // 从地址的网段作为存根网络宣告
+        for net in iface.secondary.iter() {
+            lsa.links.push(RouterLSALink {
+                link_id: net.network(),
+                link_data: net.mask(),
+                link_type: STUB_LINK,
+                tos: 0,
+                metric: iface.cost,
+            });
+        }
     }
     lsa.num_links = lsa.links.len() as u16;
     let router_id = ProtocolDB::get_router_id();
     gen_lsa_impl(interfaces, ROUTER_LSA, router_id, router_id, lsa).await;
 }
 
+/// 存根网络的连接标识为网络号
+fn network_number(ip: Ipv4Addr, mask: Ipv4Addr) -> Ipv4Addr {
+    Ipv4Network::with_netmask(ip, mask).map_or(ip, |net| net.network())
+}
+
 pub async fn gen_network_lsa(interfaces: &mut InterfacesGuard) {
     must!(interfaces.me.is_dr());
     let lsa = NetworkLSA {
@@ -71,7 +119,7 @@ pub async fn gen_network_lsa(interfaces: &mut InterfacesGuard) {
             .me
             .neighbors
             .values()
-            .filter(|n| n.state == NeighborState::Full)
+            .filter(|n| gr::is_adjacent(&interfaces.me, n.router_id))
             .map(|n| n.router_id)
             .chain(std::iter::once(ProtocolDB::get_router_id()))
             .collect(),
@@ -83,6 +131,8 @@ pub async fn gen_network_lsa(interfaces: &mut InterfacesGuard) {
 }
 
 pub async fn gen_summary_lsa(interfaces: &mut InterfacesGuard) {
+    // 平滑重启期间不生成 LSA
+    must!(!gr::restarting());
     let i_areas = interfaces
         .iter()
         .map(|i| i.area_id)
@@ -106,13 +156,32 @@ pub async fn gen_summary_lsa(interfaces: &mut InterfacesGuard) {
         .filter(|item| item.cost < LSInfinity)
         .collect();
     let mut packets = vec![];
+    // 区域间前缀过滤：离开源区域和进入本区域都必须允许
+    let me_area = db.areas.get(&interfaces.me.area_id);
+    let permits = |from: Ipv4Addr, addr: Ipv4Addr, mask: Ipv4Addr| {
+        guard!(Ok(net) = Ipv4Network::with_netmask(addr, mask); ret: true);
+        db.areas.get(&from).is_none_or(|a| a.filter_permits(net, false))
+            && me_area.is_none_or(|a| a.filter_permits(net, true))
+    };
+    // 落在区域范围内的区域内网络：(区域, 范围) -> 最大距离值
+    let mut ranges = BTreeMap::new();
     for item in &routings {
         must!(interfaces.me.area_id != item.area_id; continue);
         must!(interfaces.me.area_id != BackboneArea || item.path_type == AreaInternal; continue);
+        if item.dest_type == RoutingTableItemType::Network && item.path_type == AreaInternal {
+            let range = db.areas.get(&item.area_id).and_then(|a| a.range_of(item.dest_id, item.addr_mask));
+            if let Some((range, advertise)) = range {
+                // 不宣告的范围内的网络被隐藏
+                must!(advertise; continue);
+                let cost = ranges.entry((item.area_id, range)).or_insert(0);
+                *cost = item.cost.max(*cost);
+                continue;
+            }
+        }
         let lsa = SummaryLSA {
             network_mask: item.addr_mask,
             _zeros: PhantomData,
-            metric: item.cost,
+            metric: stub_router::summary_metric(item.cost),
         };
         packets.push(match item.dest_type {
             RoutingTableItemType::Router => {
@@ -120,14 +189,209 @@ pub async fn gen_summary_lsa(interfaces: &mut InterfacesGuard) {
                 (SUMMARY_ASBR_LSA, item.dest_id, lsa)
             }
             RoutingTableItemType::Network => {
+                must!(permits(item.area_id, item.dest_id, item.addr_mask); continue);
                 (SUMMARY_IP_LSA, item.dest_id, lsa)
             }
         });
     }
+    // 每个范围生成一条 Summary-LSA，距离值为其中网络的最大距离值
+    for ((area_id, (addr, mask)), cost) in ranges {
+        must!(permits(area_id, addr, mask); continue);
+        let lsa = SummaryLSA {
+            network_mask: mask,
+            _zeros: PhantomData,
+            metric: stub_router::summary_metric(cost),
+        };
+        packets.push((SUMMARY_IP_LSA, addr, lsa));
+    }
+    // 不再生成的 Summary-LSA 需要撤销
+    let mut stale = vec![];
+    if let Some(area) = db.areas.get(&interfaces.me.area_id) {
+        for header in area.get_all_area_lsa() {
+            must!(header.advertising_router == router_id; continue);
+            must!(matches!(header.ls_type, SUMMARY_IP_LSA | SUMMARY_ASBR_LSA); continue);
+            must!(!packets.iter().any(|(t, id, _)| *t == header.ls_type && *id == header.link_state_id); continue);
+            guard!(Some((lsa, ..)) = area.get_lsa(header.into()).await; continue);
+            stale.push(lsa);
+        }
+    }
     drop(db);
     for (ls_type, link_state_id, lsa) in packets {
         gen_lsa_impl(interfaces, ls_type, link_state_id, router_id, lsa).await;
     }
+    for lsa in stale {
+        flush_one(interfaces, lsa).await;
+    }
+}
+
+/// 外部路由汇总的结果
+pub struct ExternalSummary {
+    /// 要宣告的外部路由，以及其中汇总了多少条路由（0 表示没有汇总）
+    pub routes: Vec<(ExternalRoute, usize)>,
+    /// 需要安装丢弃路由的汇总地址
+    pub discards: BTreeSet<Ipv4AddrMask>,
+}
+
+/// 把落在汇总地址内的外部路由合并为一条：有类型 2 的路由时取类型 2，距离值取同类型中的最大值
+pub fn summarize_external(routes: &[ExternalRoute]) -> ExternalSummary {
+    let summaries = &Config::get().summary_addresses;
+    let mut result = ExternalSummary {
+        routes: vec![],
+        discards: BTreeSet::new(),
+    };
+    let mut aggregates = BTreeMap::new();
+    for route in routes {
+        let summary = summaries
+            .iter()
+            .filter(|s| s.network.contains(route.network.network()) && s.network.prefix() <= route.network.prefix())
+            .max_by_key(|s| s.network.prefix());
+        guard!(Some(summary) = summary; else: result.routes.push((*route, 0)); continue);
+        // 不宣告的汇总地址内的路由被隐藏
+        must!(summary.advertise; continue);
+        let (key, count) = aggregates
+            .entry(summary.network)
+            .or_insert(((0, 0), 0));
+        *key = (*key).max((route.metric_type, route.metric));
+        *count += 1;
+    }
+    for (network, ((metric_type, metric), count)) in aggregates {
+        let tag = summaries.iter().find(|s| s.network == network).map_or(0, |s| s.tag);
+        result.routes.push((ExternalRoute { network, metric, metric_type, tag }, count));
+        // 和某条外部路由相同时不需要丢弃路由
+        if !routes.iter().any(|r| r.network == network) {
+            result.discards.insert(Ipv4AddrMask::from(network.network(), network.mask()));
+        }
+    }
+    result
+}
+
+/// 作为 AS 边界路由器生成引入的外部路由的 AS-external-LSA，撤销不再宣告的
+pub async fn gen_external_lsa(interfaces: &mut InterfacesGuard) {
+    // 平滑重启期间不生成 LSA
+    must!(!gr::restarting());
+    // 外部 LSA 不能进入存根区域
+    must!(interfaces.me.external_routing);
+    let router_id = ProtocolDB::get_router_id();
+    let mut db = ProtocolDB::get().await;
+    let summary = summarize_external(&db.external_routes);
+    db.routing_table.set_external_discards(summary.discards);
+    let mut stale = vec![];
+    for (header, _) in Area::get_all_external_lsa().await {
+        must!(header.advertising_router == router_id; continue);
+        must!(!summary.routes.iter().any(|(r, _)| r.network.network() == header.link_state_id); continue);
+        guard!(Some((lsa, ..)) = db.get_lsa(&interfaces.me, header.into()).await; continue);
+        stale.push(lsa);
+    }
+    drop(db);
+    for (route, _) in summary.routes {
+        let lsa = AsExternalLSA {
+            network_mask: route.network.mask(),
+            e: (route.metric_type == 2) as u8,
+            _zeros: PhantomData,
+            metric: stub_router::external_metric(route.metric),
+            forwarding_address: Ipv4Addr::UNSPECIFIED,
+            external_router_tag: route.tag,
+        };
+        gen_lsa_impl(interfaces, AS_EXTERNAL_LSA, route.network.network(), router_id, lsa).await;
+    }
+    for lsa in stale {
+        flush_one(interfaces, lsa).await;
+    }
+}
+
+/// 提前老化并洪泛 interfaces.me 所在区域中由 router_id 生成的 LSA，使其从所有路由器中清除，返回清除的 LSA
+pub async fn flush_lsa(interfaces: &mut InterfacesGuard, router_id: Ipv4Addr) -> Vec<Lsa> {
+    let area_id = interfaces.me.area_id;
+    let db = ProtocolDB::get().await;
+    guard!(Some(area) = db.areas.get(&area_id); ret: vec![]);
+    let mut lsas = vec![];
+    for header in area.get_all_area_lsa() {
+        must!(header.advertising_router == router_id; continue);
+        guard!(Some((lsa, ..)) = area.get_lsa(header.into()).await; continue);
+        lsas.push(lsa);
+    }
+    drop(db);
+    let mut flushed = vec![];
+    for lsa in lsas {
+        flushed.push(flush_one(interfaces, lsa).await);
+    }
+    flushed
+}
+
+/// 提前老化并洪泛由 router_id 生成的自治系统范围的 LSA（类型 5 和 11），返回清除的 LSA
+pub async fn flush_as_lsa(interfaces: &mut InterfacesGuard, router_id: Ipv4Addr) -> Vec<Lsa> {
+    let mut lsas = vec![];
+    for header in Area::get_all_as_lsa().await {
+        must!(header.advertising_router == router_id; continue);
+        guard!(Some(lsa) = Area::get_as_lsa(header.into()).await; continue);
+        lsas.push(lsa);
+    }
+    let mut flushed = vec![];
+    for lsa in lsas {
+        flushed.push(flush_one(interfaces, lsa).await);
+    }
+    flushed
+}
+
+/// 提前老化并洪泛 interfaces.me 所在链路上由 router_id 生成的链路范围的 LSA（类型 9），返回清除的 LSA
+pub async fn flush_link_lsa(interfaces: &mut InterfacesGuard, router_id: Ipv4Addr) -> Vec<Lsa> {
+    let db = ProtocolDB::get().await;
+    guard!(Some(link) = db.links.get(&interfaces.me.ip_addr); ret: vec![]);
+    let mut lsas = vec![];
+    for header in link.get_all_lsa() {
+        must!(header.advertising_router == router_id; continue);
+        guard!(Some((lsa, ..)) = link.get_lsa(header.into()).await; continue);
+        lsas.push(lsa);
+    }
+    drop(db);
+    let mut flushed = vec![];
+    for lsa in lsas {
+        flushed.push(flush_one(interfaces, lsa).await);
+    }
+    flushed
+}
+
+/// 生成不透明 LSA，链路范围的在 interfaces.me 上洪泛，其余按范围洪泛
+pub async fn gen_opaque_lsa(
+    interfaces: &mut InterfacesGuard,
+    ls_type: u8,
+    opaque_type: u8,
+    opaque_id: u32,
+    data: Vec<u8>,
+) {
+    let router_id = ProtocolDB::get_router_id();
+    let link_state_id = LsaHeader::opaque_id(opaque_type, opaque_id);
+    let lsa = OpaqueLSA { data };
+    gen_lsa_impl(interfaces, ls_type, link_state_id, router_id, lsa).await;
+}
+
+/// 撤销自己生成的不透明 LSA：提前老化并洪泛
+pub async fn flush_opaque_lsa(
+    interfaces: &mut InterfacesGuard,
+    ls_type: u8,
+    opaque_type: u8,
+    opaque_id: u32,
+) {
+    let key = LsaIndex::new(
+        ls_type,
+        LsaHeader::opaque_id(opaque_type, opaque_id),
+        ProtocolDB::get_router_id(),
+    );
+    let lsa = ProtocolDB::get().await.get_lsa(&interfaces.me, key).await;
+    guard!(Some((lsa, ..)) = lsa);
+    flush_one(interfaces, lsa).await;
+    ProtocolDB::get().await.recalc_routing().await;
+}
+
+async fn flush_one(interfaces: &mut InterfacesGuard, mut lsa: Lsa) -> Lsa {
+    lsa.header.ls_age = LsaMaxAge;
+    statistics::lsa_flushed();
+    ProtocolDB::get()
+        .await
+        .insert_lsa(&interfaces.me, lsa.clone())
+        .await;
+    flooding(interfaces, interfaces.me.ip_addr, &lsa).await;
+    lsa
 }
 
 /// 这个函数是一个模板。提供了 LsaHeader 的生成，以及和数据库的比对，和洪泛。
@@ -140,6 +404,8 @@ async fn gen_lsa_impl<T>(
 ) where
     (LsaHeader, T): TryInto<Lsa, Error = ConvertError>,
 {
+    // 修改路由器标识期间不生成 LSA，以免重新生成正在清除的 LSA
+    must!(!ProtocolDB::changing_router_id());
     let mut header = LsaHeader {
         ls_age: 0,
         options: 0,
@@ -153,9 +419,12 @@ async fn gen_lsa_impl<T>(
     if interfaces.me.external_routing {
         header.options |= options::E;
     }
+    if types::is_opaque(ls_type) {
+        header.options |= options::O;
+    }
     let old = ProtocolDB::get()
         .await
-        .get_lsa(interfaces.me.area_id, header.into())
+        .get_lsa(&interfaces.me, header.into())
         .await;
     if let Some((old, ..)) = old.as_ref() {
         //todo! if ls_sequence_number == MaxSequenceNumber
@@ -174,8 +443,9 @@ async fn gen_lsa_impl<T>(
     }
     ProtocolDB::get()
         .await
-        .insert_lsa(interfaces.me.area_id, lsa.clone())
+        .insert_lsa(&interfaces.me, lsa.clone())
         .await;
+    statistics::lsa_originated();
     flooding(interfaces, interfaces.me.ip_addr, &lsa).await;
     ProtocolDB::get().await.recalc_routing().await;
 }
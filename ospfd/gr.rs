@@ -0,0 +1,389 @@
+//! 平滑重启（RFC 3623）
+//!
+//! 重启方：`exit graceful` 在每个接口上发出 Grace LSA，把完全邻接的邻居、内核路由和丢弃路由
+//! 写入状态文件后退出，不删除内核路由。下次启动时读取状态文件进入重启状态：不生成 LSA、不修改内核路由，
+//! 接受邻居发回的自己生成的 LSA。原来的邻居全部重新完全邻接或宽限期结束后退出重启状态，
+//! 重新计算路由、只修改有变化和过时的路由，并撤销 Grace LSA。
+//!
+//! 帮助方：收到完全邻居的 Grace LSA 后，在宽限期内忽略该邻居的失效和单向事件，
+//! 继续在 LSA 中把它当作完全邻接；重启完成、宽限期结束或拓扑变化时退出帮助。
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::Ipv4Addr,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ospf_packet::{
+    gr::{restart_reasons, GraceLsa, GRACE_OPAQUE_TYPE},
+    lsa::{types, Lsa, LsaData},
+    ToBytes, TryFromBuf,
+};
+use ospf_routing::RoutingItem;
+
+use crate::{
+    config::Config,
+    constant::LsaMaxAge,
+    database::{InterfacesGuard, Ipv4AddrMask, ProtocolDB},
+    gen_lsa, guard,
+    interface::{self, Interface, InterfaceState},
+    json,
+    json::Json,
+    log_error, log_success, log_warning, must,
+    neighbor::NeighborState,
+    opaque::{self, OpaqueScope},
+    router_info,
+    util::AbortHandle,
+};
+
+struct Restart {
+    deadline: SystemTime,
+    /// 重启前完全邻接的邻居：(接口地址, 路由器标识)
+    neighbors: Vec<(Ipv4Addr, Ipv4Addr)>,
+    /// 重启前写入内核的路由数
+    routes: usize,
+    _timer: AbortHandle,
+}
+
+struct Helper {
+    area_id: Ipv4Addr,
+    reason: u8,
+    deadline: Instant,
+    _timer: AbortHandle,
+}
+
+static RESTART: Mutex<Option<Restart>> = Mutex::new(None);
+
+/// (接口地址, 路由器标识) -> 正在帮助重启的邻居
+static HELPING: Mutex<BTreeMap<(Ipv4Addr, Ipv4Addr), Helper>> = Mutex::new(BTreeMap::new());
+
+/// 是否处于重启状态
+pub fn restarting() -> bool {
+    RESTART.lock().unwrap().is_some()
+}
+
+/// 是否正在帮助该邻居重启
+pub fn helping(ip: Ipv4Addr, router_id: Ipv4Addr) -> bool {
+    HELPING.lock().unwrap().contains_key(&(ip, router_id))
+}
+
+/// 生成 LSA 时是否把邻居当作完全邻接
+pub fn is_adjacent(iface: &Interface, router_id: Ipv4Addr) -> bool {
+    helping(iface.ip_addr, router_id)
+        || iface
+            .neighbors
+            .values()
+            .any(|n| n.router_id == router_id && n.is_adjacent())
+}
+
+/// 平滑重启前调用：发出 Grace LSA 并保存状态，之后退出时不删除路由
+///
+/// # Safety
+/// This function should be awaited when caller hasn't have any locks.
+pub async fn prepare_restart() -> std::io::Result<()> {
+    let period = Config::get().graceful_restart.grace_period;
+    let deadline = SystemTime::now() + Duration::from_secs(period as u64);
+    let mut text = format!("deadline {}\n", deadline.duration_since(UNIX_EPOCH).unwrap().as_secs());
+    let mut interfaces = tokio::task::block_in_place(ProtocolDB::get_interfaces_impl);
+    let ips: Vec<_> = interfaces
+        .iter()
+        .filter(|i| !i.passive && !matches!(i.state, InterfaceState::Down | InterfaceState::Loopback))
+        .map(|i| i.ip_addr)
+        .collect();
+    for ip in ips {
+        let mut guard = InterfacesGuard::from(interfaces, ip).unwrap();
+        for n in guard.me.neighbors.values().filter(|n| n.state == NeighborState::Full) {
+            text += &format!("neighbor {} {}\n", ip, n.router_id);
+        }
+        let lsa = GraceLsa {
+            grace_period: period,
+            reason: restart_reasons::SOFTWARE_RESTART,
+            interface_address: Some(ip),
+        };
+        let data = lsa.to_bytes().to_vec();
+        if let Err(e) = opaque::originate(&mut guard, OpaqueScope::Link(ip), GRACE_OPAQUE_TYPE, 0, data).await {
+            log_error!("failed to originate grace lsa on {}: {}", ip, e);
+        }
+        interfaces = guard.into_iter().collect();
+    }
+    let db = ProtocolDB::get().await;
+    for r in db.routing_table.kernel_routes() {
+        text += &format!("route {} {} {}\n", r.dest, r.mask, r.nexthop);
+    }
+    for r in db.routing_table.discards() {
+        text += &format!("discard {} {}\n", r.network(), r.mask());
+    }
+    for r in db.routing_table.external_discards() {
+        text += &format!("external-discard {} {}\n", r.network(), r.mask());
+    }
+    drop(db);
+    std::fs::write(Config::get().restart_file(), text)
+}
+
+/// 启动时调用：上次是平滑重启且宽限期未过时，进入重启状态，并记下仍在内核中的路由
+///
+/// # Safety
+/// This function should be awaited before any interface is started.
+pub async fn init() {
+    let path = Config::get().restart_file();
+    guard!(Ok(text) = std::fs::read_to_string(&path));
+    // 状态文件只用一次，重启中途再次退出时按普通启动处理
+    let _ = std::fs::remove_file(&path);
+    guard!(Some(state) = parse_state(&text);
+        else: log_warning!("bad restart state file: {}", path.display()));
+    guard!(Ok(left) = state.deadline.duration_since(SystemTime::now());
+        else: log_warning!("grace period has expired, starting normally"));
+    let (neighbors, routes) = (state.neighbors, state.routes.len());
+    log_success!(
+        "graceful restart: waiting for {} neighbors, {} routes kept, {}s left",
+        neighbors.len(),
+        routes,
+        left.as_secs()
+    );
+    ProtocolDB::get()
+        .await
+        .routing_table
+        .restore(state.routes, state.discards, state.external_discards);
+    let timer = tokio::spawn(async move {
+        tokio::time::sleep(left).await;
+        finish("grace period expired").await;
+    })
+    .into();
+    *RESTART.lock().unwrap() = Some(Restart {
+        deadline: state.deadline,
+        neighbors,
+        routes,
+        _timer: timer,
+    });
+}
+
+/// 状态文件中保存的重启前的状态
+struct RestartState {
+    deadline: SystemTime,
+    neighbors: Vec<(Ipv4Addr, Ipv4Addr)>,
+    routes: Vec<RoutingItem>,
+    discards: BTreeSet<Ipv4AddrMask>,
+    external_discards: BTreeSet<Ipv4AddrMask>,
+}
+
+fn parse_state(text: &str) -> Option<RestartState> {
+    let mut deadline = None;
+    let (mut neighbors, mut routes) = (vec![], vec![]);
+    let (mut discards, mut external_discards) = (BTreeSet::new(), BTreeSet::new());
+    let prefix = |dest: &str, mask: &str| Some(Ipv4AddrMask::from(dest.parse().ok()?, mask.parse().ok()?));
+    for line in text.lines() {
+        match line.split_ascii_whitespace().collect::<Vec<_>>()[..] {
+            ["deadline", secs] => deadline = Some(UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?)),
+            ["neighbor", ip, id] => neighbors.push((ip.parse().ok()?, id.parse().ok()?)),
+            ["route", dest, mask, nexthop] => routes.push(RoutingItem {
+                dest: dest.parse().ok()?,
+                mask: mask.parse().ok()?,
+                nexthop: nexthop.parse().ok()?,
+            }),
+            ["discard", dest, mask] => {
+                discards.insert(prefix(dest, mask)?);
+            }
+            ["external-discard", dest, mask] => {
+                external_discards.insert(prefix(dest, mask)?);
+            }
+            _ => return None,
+        }
+    }
+    Some(RestartState {
+        deadline: deadline?,
+        neighbors,
+        routes,
+        discards,
+        external_discards,
+    })
+}
+
+/// 退出重启状态：重新计算路由，只修改有变化的路由并删除过时的路由，然后重新生成 LSA
+///
+/// # Safety
+/// This function should be awaited when caller hasn't have any locks.
+async fn finish(reason: &str) {
+    must!(RESTART.lock().unwrap().take().is_some());
+    log_success!("graceful restart finished: {}", reason);
+    let interfaces = tokio::task::block_in_place(ProtocolDB::get_interfaces_impl);
+    let mut db = ProtocolDB::get().await;
+    db.recalc_routing().await;
+    // 外部路由汇总的丢弃路由在生成外部 LSA 时才会更新，这里先删除已经不需要的
+    let active = gen_lsa::summarize_external(&db.external_routes).discards;
+    let external_discards = db.routing_table.external_discards() & &active;
+    db.routing_table.set_external_discards(external_discards);
+    drop(db);
+    drop(interfaces);
+    interface::notify_changed();
+}
+
+/// 接口检查时调用：重启状态下检查邻接是否都已恢复，否则撤销自己的 Grace LSA
+pub async fn check(interfaces: &mut InterfacesGuard) {
+    let done = RESTART.lock().unwrap().as_ref().map(|r| {
+        r.neighbors.iter().all(|(ip, id)| {
+            // 已经不存在的接口不再等待
+            interfaces
+                .iter()
+                .find(|i| i.ip_addr == *ip)
+                .is_none_or(|i| i.neighbors.values().any(|n| n.router_id == *id && n.state == NeighborState::Full))
+        })
+    });
+    match done {
+        Some(true) => {
+            tokio::spawn(finish("all neighbors are fully adjacent"));
+        }
+        Some(false) => (),
+        None => {
+            let scope = OpaqueScope::Link(interfaces.me.ip_addr);
+            if let Err(e) = opaque::withdraw(interfaces, scope, GRACE_OPAQUE_TYPE, 0).await {
+                log_error!("failed to withdraw grace lsa: {}", e);
+            }
+        }
+    }
+}
+
+/// 从接口收到的 LSA 加入数据库后调用，old 为数据库中原来的副本
+pub fn received(iface: &Interface, lsa: &Lsa, old: Option<&Lsa>) {
+    let header = lsa.header;
+    if header.ls_type == types::OPAQUE_LINK_LSA && header.opaque_type() == GRACE_OPAQUE_TYPE {
+        return grace_received(iface, lsa);
+    }
+    // 只有内容变化才算拓扑变化，单纯的刷新不算
+    must!(!types::is_opaque(header.ls_type));
+    let flushed = |lsa: &Lsa| lsa.header.ls_age == LsaMaxAge;
+    must!(old.is_none_or(|old| old.data != lsa.data || flushed(old) != flushed(lsa)));
+    let mut helping = HELPING.lock().unwrap();
+    let before = helping.len();
+    helping.retain(|(_, id), helper| {
+        let affected = types::is_as_scope(header.ls_type) || helper.area_id == iface.area_id;
+        *id == header.advertising_router || !affected
+    });
+    must!(helping.len() != before);
+    drop(helping);
+    log_warning!("topology changed ({:?}), stop helping restarting neighbors", header);
+    interface::notify_changed();
+}
+
+fn grace_received(iface: &Interface, lsa: &Lsa) {
+    let id = lsa.header.advertising_router;
+    let key = (iface.ip_addr, id);
+    if lsa.header.ls_age == LsaMaxAge {
+        return stop_helping(key, "restart completed");
+    }
+    must!(Config::get().graceful_restart.helper && !restarting());
+    guard!(LsaData::Opaque(ref data) = lsa.data);
+    guard!(Ok(grace) = GraceLsa::try_from_slice(&data.data);
+        else: log_warning!("bad grace lsa from {}", id));
+    // 只帮助完全邻接的邻居，已经在帮助时更新宽限期
+    must!(is_adjacent(iface, id));
+    let left = grace.grace_period.saturating_sub(lsa.header.ls_age as u32);
+    must!(left > 0);
+    let timer = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(left as u64)).await;
+        stop_helping(key, "grace period expired");
+    })
+    .into();
+    HELPING.lock().unwrap().insert(
+        key,
+        Helper {
+            area_id: iface.area_id,
+            reason: grace.reason,
+            deadline: Instant::now() + Duration::from_secs(left as u64),
+            _timer: timer,
+        },
+    );
+    log_success!(
+        "helping neighbor {} restart ({}), grace period {}s",
+        router_info::name(id),
+        restart_reasons::to_string(grace.reason),
+        left
+    );
+}
+
+/// 退出帮助，重新生成 LSA 以反映真实的邻接
+pub fn stop_helping(key: (Ipv4Addr, Ipv4Addr), reason: &str) {
+    must!(HELPING.lock().unwrap().remove(&key).is_some());
+    log_warning!("stop helping neighbor {}: {}", router_info::name(key.1), reason);
+    interface::notify_changed();
+}
+
+/// 平滑重启的状态（JSON）
+pub fn status_json() -> Json {
+    let config = Config::get().graceful_restart;
+    let restart = RESTART.lock().unwrap().as_ref().map(|r| {
+        let left = r.deadline.duration_since(SystemTime::now()).unwrap_or_default();
+        let neighbors: Vec<_> = r
+            .neighbors
+            .iter()
+            .map(|(ip, id)| json!({ "interface_address": *ip, "router_id": *id }))
+            .collect();
+        json!({ "seconds_left": left.as_secs(), "routes_kept": r.routes, "waiting_for": neighbors })
+    });
+    let helping: Vec<_> = HELPING
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((ip, id), helper)| {
+            json!({
+                "interface_address": *ip,
+                "router_id": *id,
+                "area": helper.area_id,
+                "reason": restart_reasons::to_string(helper.reason),
+                "seconds_left": helper.deadline.saturating_duration_since(Instant::now()).as_secs(),
+            })
+        })
+        .collect();
+    json!({
+        "grace_period": config.grace_period,
+        "helper": config.helper,
+        "restarting": restart,
+        "helping": helping,
+    })
+}
+
+/// 显示平滑重启的状态
+pub fn status() -> Vec<String> {
+    let mut lines = vec![];
+    if let Some(ref r) = *RESTART.lock().unwrap() {
+        let left = r.deadline.duration_since(SystemTime::now()).unwrap_or_default();
+        lines.push(format!("Restarting, {}s left, {} routes kept", left.as_secs(), r.routes));
+        for (ip, id) in r.neighbors.iter() {
+            lines.push(format!("  waiting for {} on {}", router_info::name(*id), ip));
+        }
+    }
+    for ((ip, id), helper) in HELPING.lock().unwrap().iter() {
+        let left = helper.deadline.saturating_duration_since(Instant::now());
+        lines.push(format!(
+            "Helping {} on {} ({}), {}s left",
+            router_info::name(*id),
+            ip,
+            restart_reasons::to_string(helper.reason),
+            left.as_secs()
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let text = "deadline 100\n\
+                    neighbor 10.0.0.1 2.2.2.2\n\
+                    route 10.1.0.0 255.255.0.0 10.0.0.2\n\
+                    discard 10.2.0.0 255.255.0.0\n\
+                    external-discard 192.168.0.0 255.255.252.0\n";
+        let state = parse_state(text).unwrap();
+        assert_eq!(state.deadline, UNIX_EPOCH + Duration::from_secs(100));
+        assert_eq!(state.neighbors, [("10.0.0.1".parse().unwrap(), "2.2.2.2".parse().unwrap())]);
+        assert_eq!(state.routes.len(), 1);
+        let prefix = |dest: &str, mask: &str| Ipv4AddrMask::from(dest.parse().unwrap(), mask.parse().unwrap());
+        assert_eq!(state.discards, BTreeSet::from([prefix("10.2.0.0", "255.255.0.0")]));
+        assert_eq!(state.external_discards, BTreeSet::from([prefix("192.168.0.0", "255.255.252.0")]));
+        assert!(parse_state("discard 10.2.0.0\ndeadline 100\n").is_none());
+        assert!(parse_state("neighbor 10.0.0.1 2.2.2.2\n").is_none());
+    }
+}
@@ -1,7 +1,11 @@
 use std::{marker::PhantomData, ops::Deref};
 
 use ospf_macros::define;
-use ospf_packet::{lsa, packet::DBDescription};
+use ospf_packet::{
+    lls::Lls,
+    lsa,
+    packet::{options, DBDescription},
+};
 
 use crate::{
     database::ProtocolDB,
@@ -13,11 +17,24 @@ use crate::{
 };
 
 #[define(iface => src.get_interface(); neighbor => src.get_neighbor())]
-pub async fn handle(mut src: RefNeighbor<'_>, packet: DBDescription) {
+pub async fn handle(mut src: RefNeighbor<'_>, packet: DBDescription, lls: Option<Lls>) {
     must!(neighbor.state >= NeighborState::Init);
+    // 邻居的 MTU 超过接口能接收的大小时拒绝该 DD 包
+    must!(packet.interface_mtu <= iface.mtu);
+    if let Some(options) = lls.and_then(|lls| lls.extended_options) {
+        neighbor.extended_options = options;
+    }
     if neighbor.state == NeighborState::Init {
         src.two_way_received().await;
     }
+    // 完全邻接的邻居设置了 R 位和 I 位，开始带外重新同步（RFC 4811）
+    if neighbor.state == NeighborState::Full
+        && packet.resync != 0
+        && packet.init != 0
+        && neighbor.resync_capable()
+    {
+        src.oob_resync().await;
+    }
     let dd_cache = DdPacketCache::from(&packet);
     let prev_state = NeighborSubStruct::from(neighbor.deref());
     neighbor.dd_last_packet = dd_cache;
@@ -72,6 +89,7 @@ pub async fn handle(mut src: RefNeighbor<'_>, packet: DBDescription) {
             if dd_cache.master != prev_state.master
                 || dd_cache.init
                 || packet.options != prev_state.option
+                || (packet.resync != 0) != prev_state.oob_resync
                 || dd_cache.master && dd_cache.sequence_number != prev_state.dd_seq_num + 1
                 || !dd_cache.master && dd_cache.sequence_number != prev_state.dd_seq_num
             {
@@ -88,9 +106,9 @@ pub async fn handle(mut src: RefNeighbor<'_>, packet: DBDescription) {
     }
     // update database
     for lsa in packet.lsa_header {
-        must!((1..=5).contains(&lsa.ls_type); else: src.seq_number_mismatch().await);
-        must!(lsa.ls_type != lsa::types::AS_EXTERNAL_LSA || iface.external_routing; else: src.seq_number_mismatch().await);
-        if ProtocolDB::get().await.need_update(iface.area_id, lsa).await {
+        must!(lsa::types::is_known(lsa.ls_type); else: src.seq_number_mismatch().await);
+        must!(!lsa::types::is_as_scope(lsa.ls_type) || iface.external_routing; else: src.seq_number_mismatch().await);
+        if ProtocolDB::get().await.need_update(iface, lsa).await {
             neighbor.ls_request_list.push_back(lsa.clone());
         }
     }
@@ -106,9 +124,10 @@ pub async fn handle(mut src: RefNeighbor<'_>, packet: DBDescription) {
             len = len.min(8);
         }
         let packet = DBDescription {
-            interface_mtu: 0,
-            options: neighbor.option,
+            interface_mtu: iface.mtu,
+            options: neighbor.option | options::L,
             _zeros: PhantomData,
+            resync: neighbor.oob_resync as u8,
             init: 0,
             more,
             master: 0,
@@ -124,9 +143,10 @@ pub async fn handle(mut src: RefNeighbor<'_>, packet: DBDescription) {
         let len: usize = neighbor.db_summary_list.len().min(12);
         must!(dd_cache.more || len > 0; else: src.exchange_done().await);
         let packet = DBDescription {
-            interface_mtu: 0,
-            options: neighbor.option,
+            interface_mtu: iface.mtu,
+            options: neighbor.option | options::L,
             _zeros: PhantomData,
+            resync: neighbor.oob_resync as u8,
             init: 0,
             more: (len < neighbor.db_summary_list.len()) as u8,
             master: 1,
@@ -1,18 +1,22 @@
 use std::ops::Deref;
 
 use ospf_macros::define;
-use ospf_packet::packet::{self, options::OptionExt, HelloPacket};
+use ospf_packet::{
+    lls::{extended_options, Lls},
+    packet::{self, options::OptionExt, HelloPacket},
+};
 
 use crate::{
     database::ProtocolDB,
+    gr,
     interface::{InterfaceEvent, InterfaceState, NetType},
     must,
-    neighbor::{NeighborEvent, NeighborSubStruct, RefNeighbor},
+    neighbor::{NeighborEvent, NeighborState, NeighborSubStruct, RefNeighbor},
     util::hex2ip,
 };
 
 #[define(iface => src.get_interface(); neighbor => src.get_neighbor())]
-pub async fn handle(mut src: RefNeighbor<'_>, packet: HelloPacket) {
+pub async fn handle(mut src: RefNeighbor<'_>, packet: HelloPacket, lls: Option<Lls>) {
     // must
     must!(iface.hello_interval == packet.hello_interval);
     must!(iface.dead_interval == packet.router_dead_interval);
@@ -21,6 +25,18 @@ pub async fn handle(mut src: RefNeighbor<'_>, packet: HelloPacket) {
             || iface.ip_mask == packet.network_mask
     );
     must!(iface.external_routing == packet.is_set(packet::options::E));
+    neighbor.extended_options = lls.and_then(|lls| lls.extended_options).unwrap_or(0);
+    // 帮助重启的邻居时，或完全邻接的邻居发出重启信号（RFC 4812）时，它的 Hello 不改变邻接和 DR 选举
+    let restart_signal = neighbor.extended_options & extended_options::RS != 0
+        && neighbor.state == NeighborState::Full;
+    if restart_signal || gr::helping(iface.ip_addr, neighbor.router_id) {
+        neighbor.option = packet.options;
+        src.hello_receive().await;
+        if packet.neighbors.contains(&ProtocolDB::get_router_id()) {
+            src.two_way_received().await;
+        }
+        return;
+    }
     // neighbor structure
     let prev_state = NeighborSubStruct::from(neighbor.deref());
     neighbor.option = packet.options;
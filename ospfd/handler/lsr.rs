@@ -13,7 +13,7 @@ pub async fn handle(mut src: RefNeighbor<'_>, packet: LSRequest) {
     must!(neighbor.state >= NeighborState::Exchange);
     guard! {
         Some((lsa, ..)) = ProtocolDB::get().await.get_lsa(
-            iface.area_id,
+            iface,
             LsaIndex::new(
                 packet.ls_type as u8,
                 packet.ls_id,
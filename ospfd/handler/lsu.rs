@@ -2,7 +2,7 @@ use std::{net::Ipv4Addr, ops::ControlFlow};
 
 use ospf_packet::{
     lsa::{
-        types::{AS_EXTERNAL_LSA, NETWORK_LSA},
+        types::{self, NETWORK_LSA},
         Lsa, LsaHeader, LsaIndex,
     },
     packet::{LSAcknowledge, LSUpdate},
@@ -13,10 +13,12 @@ use crate::{
     database::{InterfacesGuard, ProtocolDB},
     flooding::flooding,
     gen_lsa::gen_summary_lsa,
+    gr,
     interface::InterfaceState,
     log_error, must,
     neighbor::{NeighborEvent, NeighborState, RefNeighbor},
     sender::send_packet,
+    statistics,
 };
 
 macro_rules! ret {
@@ -31,7 +33,10 @@ macro_rules! ret {
 pub async fn handle(interfaces: InterfacesGuard, src_ip: Ipv4Addr, packet: LSUpdate) {
     let mut delay = vec![];
     let mut meta = Metadata(interfaces, src_ip);
+    let neighbor = &meta.0.me.neighbors[&src_ip];
+    let key = (neighbor.router_id, neighbor.ip_addr);
     for lsa in packet.lsa {
+        statistics::lsa_received(key);
         match handle_one(&mut meta, lsa, &mut delay).await {
             ret!(continue) => continue,
             ret!(break) => break,
@@ -60,7 +65,7 @@ macro_rules! invoke {
     ($meta:ident.$func:ident, $param:expr) => {
         ProtocolDB::get()
             .await
-            .$func($meta.0.me.area_id, $param)
+            .$func(&$meta.0.me, $param)
             .await
     };
 }
@@ -77,11 +82,11 @@ async fn handle_one(
     vec: &mut Vec<LsaHeader>,
 ) -> ControlFlow<(), ()> {
     // 1. 确认 LSA 的 LS 校验和。
-    must!(lsa.checksum_ok(); else: log_error!("ls checksum error"); ret: ret!(continue));
+    must!(lsa.checksum_ok(); else: log_error!(Lsu; "ls checksum error"); ret: ret!(continue));
     // 2. 检查 LSA 的 LS 类型。
-    must!(matches!(lsa.header.ls_type, 1..=5); else: log_error!("ls_type error"); ret: ret!(continue));
-    // 3. 如果是一个 AS-external-LSA
-    must!(!matches!(lsa.header.ls_type, AS_EXTERNAL_LSA) || meta.0.me.external_routing; ret: ret!(continue));
+    must!(types::is_known(lsa.header.ls_type); else: log_error!(Lsu; "ls_type error"); ret: ret!(continue));
+    // 3. 如果是一个 AS-external-LSA（或 AS 范围的不透明 LSA）
+    must!(!types::is_as_scope(lsa.header.ls_type) || meta.0.me.external_routing; ret: ret!(continue));
     // special: 如果这是邻居对我的 lsr 的回应
     if let Some(header) = neighbor!(meta).ls_request_list.front() {
         if LsaIndex::from(lsa.header) == LsaIndex::from(*header) {
@@ -130,13 +135,13 @@ async fn handle_one(
         }
         // d) 将新的 LSA 加入连接状态数据库（取代当前数据库的副本），这可能导致按调度计算路由表
         invoke!(meta.insert_lsa, lsa.clone());
+        statistics::lsa_installed();
+        gr::received(&meta.0.me, &lsa, db_lsa.as_ref().map(|(lsa, ..)| lsa));
         ProtocolDB::get().await.recalc_routing().await;
         gen_summary_lsa(&mut meta.0).await;
         // e）也许需要从接收接口发送 LSAck 包以确认所收到的 LSA。这在第 13.5 节说明。
-        if !flood {
-            if meta.0.me.state != InterfaceState::Backup || neighbor!(meta).is_dr() {
-                vec.push(lsa.header);
-            }
+        if !flood && (meta.0.me.state != InterfaceState::Backup || neighbor!(meta).is_dr()) {
+            vec.push(lsa.header);
         }
         // f）如果这个新的 LSA 是由路由器自身所生成的（即被作为自生成 LSA），
         //    路由器执行特殊的操作，或许更新该 LSA，或将其从路由域中删除。
@@ -146,7 +151,8 @@ async fn handle_one(
                 && meta.0.iter().any(|i| i.ip_addr == lsa.header.link_state_id)
         {
             //todo! 目前自生成 lsa 考虑直接老化
-            if db_lsa.is_none() {
+            // 平滑重启期间接受重启前自己生成的 LSA
+            if db_lsa.is_none() && !gr::restarting() {
                 let mut lsa = lsa;
                 lsa.header.ls_age = LsaMaxAge;
                 invoke!(meta.insert_lsa, lsa.clone());
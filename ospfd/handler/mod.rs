@@ -7,8 +7,9 @@ mod lsu;
 use std::{net::Ipv4Addr, ops::DerefMut};
 
 use ospf_packet::{
-    packet::{types::*, *},
-    FromBuf, Ospf,
+    lls::Lls,
+    packet::Message,
+    DecodeError, Ospf,
 };
 
 use crate::{
@@ -16,17 +17,39 @@ use crate::{
     constant::AllDRouters,
     database::ProtocolDB,
     interface::{AInterface, NetType},
-    log_error,
+    guard, log_error, log_warning, must,
     neighbor::{Neighbor, RefNeighbor},
+    statistics::{self, DiscardReason},
     util::{hex2ip, ip2hex},
 };
 
+/// LLS 数据块有错误时只丢弃数据块，报文照常处理
+fn check_lls(lls: Option<Result<Lls, DecodeError>>) -> Option<Lls> {
+    match lls? {
+        Ok(lls) => Some(lls),
+        Err(e) => {
+            log_warning!("discard bad lls block: {}", e);
+            None
+        }
+    }
+}
+
+/// 认证类型必须与接口一致，目前只支持空认证（类型 0）
+fn authenticate(au_type: u16, packet: &Ospf) -> bool {
+    packet.au_type == au_type && au_type == 0
+}
+
 #[allow(non_upper_case_globals)]
 #[doc = "首先检查 ospf 报头，对于合法报头，发送给对应报文处理器处理"]
-pub fn ospf_handler_maker(interface: AInterface) -> OspfHandler {
+pub fn ospf_handler_maker(interface: AInterface, name: String) -> OspfHandler {
     Box::new(move |src, dest, packet| {
         // the src & dest has already checked
-        if !packet.auto_test_checksum() || packet.get_version() != 2 {
+        if !packet.auto_test_checksum() {
+            statistics::discarded(&name, DiscardReason::BadChecksum);
+            return;
+        }
+        if packet.get_version() != 2 {
+            statistics::discarded(&name, DiscardReason::BadVersion);
             return;
         }
         let hd = tokio::spawn(ospf_handle(interface.clone(), packet.into(), src, dest));
@@ -41,19 +64,34 @@ pub fn ospf_handler_maker(interface: AInterface) -> OspfHandler {
 
 async fn ospf_handle(interface: AInterface, packet: Ospf, src: Ipv4Addr, dest: Ipv4Addr) {
     let mut interface = interface.lock().await;
+    let discard = |reason| statistics::discarded(&interface.interface_name, reason);
+    // 被动接口不接收 OSPF 报文
+    must!(!interface.passive; else: discard(DiscardReason::Passive));
     match packet.area_id {
         x if x == ip2hex(interface.area_id) => (),          // ok
         0 if interface.is_dr() || interface.is_bdr() => (), // ok
-        _ => return,                                        // bad area id
+        _ => return discard(DiscardReason::BadArea),        // bad area id
+    }
+    // 广播和 NBMA 网络上，源地址必须与接收接口的主地址同网段（从地址上的邻居不处理）
+    if matches!(interface.net_type, NetType::Broadcast | NetType::NBMA)
+        && !interface.in_subnet(src)
+    {
+        // 每个网段单独运行实例时，其他网段的报文由同一接口上的其他实例处理
+        must!(!interface.instance);
+        return discard(DiscardReason::BadSource);
     }
     if dest == AllDRouters && interface.is_drother() {
-        return;
+        return discard(DiscardReason::BadDestination);
     } // bad dest
-    match packet.au_type {
-        0 => (),      // ok
-        _ => todo!(), //todo implement other au type
-    }
-    let payload = &mut packet.payload.as_slice();
+    must!(authenticate(interface.au_type, &packet); else: discard(DiscardReason::BadAuth));
+    let message = match Message::decode(&packet) {
+        Ok(message) => message,
+        Err(e) => {
+            discard((&e).into());
+            log_warning!("discard bad packet from {}: {}", src, e);
+            return;
+        }
+    };
     let mut router_id = hex2ip(packet.router_id);
     let mut ip = src;
     if matches!(interface.net_type, NetType::P2P | NetType::Virtual) {
@@ -63,20 +101,47 @@ async fn ospf_handle(interface: AInterface, packet: Ospf, src: Ipv4Addr, dest: I
     if !interface.neighbors.contains_key(&ip) {
         interface.neighbors.insert(ip, Neighbor::new(router_id, ip));
     }
+    let key = (interface.neighbors[&ip].router_id, interface.neighbors[&ip].ip_addr);
+    statistics::neighbor_received(&interface.interface_name, key, packet.message_type);
     let neighbor = RefNeighbor::from(interface.deref_mut(), ip).unwrap();
-    match packet.message_type {
-        HELLO_PACKET => hello::handle(neighbor, HelloPacket::from_buf(payload)).await,
-        DB_DESCRIPTION => dd::handle(neighbor, DBDescription::from_buf(payload)).await,
-        LS_REQUEST => lsr::handle(neighbor, LSRequest::from_buf(payload)).await,
-        LS_UPDATE => {
-            lsu::handle(
-                ProtocolDB::upgrade_lock(interface).await,
-                ip,
-                LSUpdate::from_buf(payload),
-            )
-            .await
+    match message {
+        Message::Hello(packet, lls) => hello::handle(neighbor, packet, check_lls(lls)).await,
+        Message::DbDescription(packet, lls) => dd::handle(neighbor, packet, check_lls(lls)).await,
+        Message::LsRequest(requests) => {
+            for packet in requests {
+                guard!(Some(neighbor) = RefNeighbor::from(interface.deref_mut(), ip));
+                lsr::handle(neighbor, packet).await;
+            }
+        }
+        Message::LsUpdate(packet) => {
+            guard!(Some(interfaces) = ProtocolDB::upgrade_lock(interface).await);
+            lsu::handle(interfaces, ip, packet).await
         }
-        LS_ACKNOWLEDGE => ack::handle(neighbor, LSAcknowledge::from_buf(payload)).await,
-        _ => return, // bad msg type
+        Message::LsAck(packet) => ack::handle(neighbor, packet).await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut packet = Ospf {
+            version: 2,
+            message_type: ospf_packet::packet::types::HELLO_PACKET,
+            length: 24,
+            router_id: 0,
+            area_id: 0,
+            checksum: 0,
+            au_type: 0,
+            authentication: 0,
+            payload: vec![],
+        };
+        assert!(authenticate(0, &packet));
+        packet.au_type = 1;
+        assert!(!authenticate(0, &packet));
+        assert!(!authenticate(1, &packet));
+        assert_eq!(DiscardReason::BadAuth.name(), "bad authentication");
     }
 }
@@ -1,29 +1,95 @@
-use crate::{database::ProtocolDB, gen_lsa};
+use std::net::Ipv4Addr;
+
+use tokio::sync::Notify;
+
+use crate::{
+    database::{InterfacesGuard, ProtocolDB},
+    gen_lsa, gr, guard, log_error, log_success, must, opaque, router_info, sr, stub_router, te,
+    util::AbortHandle,
+};
 
 use super::{InterfaceEvent, InterfaceState, WInterface};
 
-pub fn listen_interface(interface: WInterface) {
+/// 接口状态变化的通知，收到后立即检查接口，不必等到下一轮
+static CHANGED: Notify = Notify::const_new();
+
+pub fn notify_changed() {
+    CHANGED.notify_waiters();
+}
+
+/// 生成 interfaces.me 所在区域中自己的 LSA
+pub async fn originate(interfaces: &mut InterfacesGuard) {
+    // 平滑重启期间不生成 LSA，沿用邻居保存的重启前的 LSA
+    must!(!gr::restarting());
+    gen_lsa::gen_router_lsa(interfaces).await;
+    gen_lsa::gen_network_lsa(interfaces).await;
+    gen_lsa::gen_summary_lsa(interfaces).await;
+    gen_lsa::gen_external_lsa(interfaces).await;
+    let results = [
+        te::gen_te_lsa(interfaces).await,
+        router_info::gen_ri_lsa(interfaces).await,
+        sr::gen_sr_lsa(interfaces).await,
+    ];
+    for e in results.into_iter().filter_map(Result::err) {
+        log_error!("failed to originate opaque lsa: {}", e);
+    }
+    // 应用生成的 LSA 已经是最新的，这里只会刷新其余已记录的不透明 LSA
+    opaque::refresh(interfaces).await;
+}
+
+/// 接口离开区域前调用，撤销以它为单位生成的不透明 LSA
+pub async fn withdraw(interfaces: &mut InterfacesGuard) {
+    let results = [te::withdraw(interfaces).await, sr::withdraw(interfaces).await];
+    for e in results.into_iter().filter_map(Result::err) {
+        log_error!("failed to withdraw opaque lsa: {}", e);
+    }
+}
+
+/// 在每个区域立即重新生成 LSA
+pub async fn originate_areas(interfaces: InterfacesGuard) {
+    let mut interfaces: Vec<_> = interfaces.into_iter().collect();
+    let mut ips: Vec<(Ipv4Addr, Ipv4Addr)> = vec![];
+    for iface in interfaces.iter() {
+        must!(!ips.iter().any(|(area_id, _)| *area_id == iface.area_id); continue);
+        ips.push((iface.area_id, iface.ip_addr));
+    }
+    for (_, ip) in ips {
+        let mut guard = InterfacesGuard::from(interfaces, ip).unwrap();
+        originate(&mut guard).await;
+        interfaces = guard.into_iter().collect();
+    }
+}
+
+pub fn listen_interface(interface: WInterface) -> AbortHandle {
     tokio::spawn(async move {
         while let Some(interface) = interface.upgrade() {
             let mut interface = interface.lock().await;
             let net = interface.get_network_interface();
             if !net.is_up() {
                 interface.interface_down().await;
-            } else if net.is_loopback() {
+            } else if net.is_loopback() || interface.loopback {
                 interface.loop_ind().await;
             } else if interface.state == InterfaceState::Loopback {
                 interface.unloop_ind().await;
             } else {
                 interface.interface_up().await;
             }
+            // 链路速率变化时开销随之变化，下面重新生成路由器 LSA
+            if interface.refresh_cost() {
+                log_success!("interface {}'s cost is changed to {}", interface.interface_name, interface.cost);
+            }
             //todo! temporary generate router lsa here
-            let mut interfaces = ProtocolDB::upgrade_lock(interface).await;
-            gen_lsa::gen_router_lsa(&mut interfaces).await;
-            gen_lsa::gen_network_lsa(&mut interfaces).await;
-            gen_lsa::gen_summary_lsa(&mut interfaces).await;
+            guard!(Some(mut interfaces) = ProtocolDB::upgrade_lock(interface).await; break);
+            originate(&mut interfaces).await;
+            gr::check(&mut interfaces).await;
+            stub_router::check(&interfaces);
             drop(interfaces); // must release here, otherwise it will lock 8 secs...
-            // check interface every 8 seconds
-            tokio::time::sleep(tokio::time::Duration::from_secs(8)).await;
+            // check interface every 8 seconds, or when notified
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(8)) => (),
+                _ = CHANGED.notified() => (),
+            }
         }
-    });
+    })
+    .into()
 }
@@ -1,21 +1,34 @@
 mod listen;
+mod setting;
 mod state;
+pub use listen::{notify_changed, originate_areas};
+pub use setting::*;
 pub use state::*;
 
 use crate::{
+    capture::CaptureOspfDaemon,
+    config::{Config, PrefixSidConfig, TeConfig},
+    constant::BackboneArea,
+    daemon::Daemon,
+    database::{InterfacesGuard, ProtocolDB},
+    gen_lsa,
+    guard, handler, json,
+    json::{Json, ToJson},
+    log, log_error, log_success, must,
     neighbor::{Neighbor, NeighborState},
-    util::{AbortHandle, hex2ip},
+    sr,
+    util::{hex2ip, ip2hex, AbortHandle},
 };
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     net::Ipv4Addr,
     sync::{Arc, Weak},
 };
 
 use pnet::{
     datalink::{self, NetworkInterface},
-    ipnetwork::IpNetwork,
+    ipnetwork::{IpNetwork, Ipv4Network},
     packet::ip::IpNextHeaderProtocols::OspfigP,
     transport::{
         transport_channel, TransportChannelType::Layer4, TransportProtocol::Ipv4, TransportSender,
@@ -26,17 +39,36 @@ use tokio::sync::Mutex;
 pub struct Interface {
     pub me: WInterface,
     pub interface_name: String,
+    /// 内核中的接口序号
+    pub index: u32,
     pub sender: TransportSender,
     pub net_type: NetType,
     pub state: InterfaceState,
     pub ip_addr: Ipv4Addr,
     pub ip_mask: Ipv4Addr,
+    pub mtu: u16,
+    /// 从地址：主地址以外的 IPv4 网段（保留接口地址），只作为存根网络宣告
+    pub secondary: Vec<Ipv4Network>,
+    /// 接口上的每个网段单独运行一个实例，此时没有从地址
+    pub instance: bool,
     pub area_id: Ipv4Addr,
     pub hello_interval: u16,
     pub dead_interval: u32,
     pub inf_trans_delay: u16,
     pub router_priority: u8,
     pub external_routing: bool,
+    /// 被动接口：作为存根网络宣告，但不收发 OSPF 报文
+    pub passive: bool,
+    /// 环回接口：以 /32 主机路由宣告接口地址，不收发 OSPF 报文
+    pub loopback: bool,
+    /// 流量工程属性，为 None 时不宣告 TE 链路
+    pub te: Option<TeConfig>,
+    /// 分段路由的前缀 SID，只在环回接口上宣告
+    pub prefix_sid: Option<PrefixSidConfig>,
+    /// OSPF 报文捕获协程
+    pub capture: AbortHandle,
+    /// 接口状态监听协程
+    pub listen: AbortHandle,
     pub hello_timer: AbortHandle,
     pub wait_timer: AbortHandle,
     pub retransmission_timer: AbortHandle,
@@ -45,11 +77,29 @@ pub struct Interface {
     pub dr: Ipv4Addr,
     pub bdr: Ipv4Addr,
     pub cost: u16,
+    /// 显式配置的开销，优先于按参考带宽计算的开销
+    pub cost_override: Option<u16>,
+    /// 链路速率（Mbit/s），读取不到时为 None
+    pub speed: Option<u32>,
     pub rxmt_interval: u16,
     pub au_type: u16,
     pub au_key: u64,
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum InterfaceError {
+    #[error("No IPv4 address found on interface")]
+    NoIpv4,
+    #[error("Interface name is too long")]
+    BadName,
+    #[error("There is no interface named {0}")]
+    NotFound(String),
+    #[error("IO Error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("{0} {1} is out of range {2:?}")]
+    OutOfRange(&'static str, u32, std::ops::RangeInclusive<u32>),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NetType {
     P2P,
@@ -74,17 +124,27 @@ impl Interface {
             Mutex::new(Self {
                 me: me.clone(),
                 interface_name,
+                index: 0,
                 sender,
                 net_type: NetType::Broadcast,
                 state: InterfaceState::Down,
                 ip_addr,
                 ip_mask,
+                mtu: 1500,
+                secondary: Vec::new(),
+                instance: false,
                 area_id,
                 hello_interval: 10,
                 dead_interval: 40,
                 inf_trans_delay: 1,
                 router_priority: 1,
                 external_routing: true,
+                passive: false,
+                loopback: false,
+                te: None,
+                prefix_sid: None,
+                capture: AbortHandle::default(),
+                listen: AbortHandle::default(),
                 hello_timer: AbortHandle::default(),
                 wait_timer: AbortHandle::default(),
                 retransmission_timer: AbortHandle::default(),
@@ -92,6 +152,8 @@ impl Interface {
                 dr: hex2ip(0),
                 bdr: hex2ip(0),
                 cost: 1,
+                cost_override: None,
+                speed: None,
                 rxmt_interval: 4,
                 au_type: 0,
                 au_key: 0,
@@ -99,27 +161,11 @@ impl Interface {
         })
     }
 
-    pub fn from(iface: &NetworkInterface, area_id: Ipv4Addr) -> AInterface {
-        let ip = iface
-            .ips
-            .iter()
-            .find_map(|ip| {
-                if let IpNetwork::V4(ip) = ip {
-                    Some(ip)
-                } else {
-                    None
-                }
-            })
-            .expect("No IPv4 address found on interface");
-        let tx = match transport_channel(4096, Layer4(Ipv4(OspfigP))) {
-            Ok((tx, ..)) => tx,
-            Err(e) => panic!(
-                "An error occurred when creating the transport channel: {}",
-                e
-            ),
-        };
+    /// 在接口的地址 ip 上运行 OSPF
+    pub fn from(iface: &NetworkInterface, ip: Ipv4Network, area_id: Ipv4Addr) -> Result<AInterface, InterfaceError> {
+        let (tx, _) = transport_channel(4096, Layer4(Ipv4(OspfigP)))?;
         let mut opt: libc::ifreq = unsafe { std::mem::zeroed() };
-        assert!(iface.name.len() < std::mem::size_of_val(&opt.ifr_name));
+        must!(iface.name.len() < std::mem::size_of_val(&opt.ifr_name); ret: Err(InterfaceError::BadName));
         unsafe {
             libc::memcpy(
                 std::ptr::from_mut(&mut opt) as *mut libc::c_void,
@@ -137,13 +183,117 @@ impl Interface {
             )
         } == -1
         {
-            panic!("set socket opt failed: {}", std::io::Error::last_os_error());
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // 绑定运行 OSPF 的地址，保证发出的报文以它为源地址
+        let addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: 0,
+            sin_addr: libc::in_addr {
+                s_addr: u32::from(ip.ip()).to_be(),
+            },
+            sin_zero: [0; 8],
+        };
+        if unsafe {
+            libc::bind(
+                tx.socket.fd,
+                std::ptr::from_ref(&addr) as *const libc::sockaddr,
+                std::mem::size_of_val(&addr) as u32,
+            )
+        } == -1
+        {
+            return Err(std::io::Error::last_os_error().into());
         }
-        Self::new(area_id, iface.name.to_string(), tx, ip.ip(), ip.mask())
+        let this = Self::new(area_id, iface.name.to_string(), tx, ip.ip(), ip.mask());
+        tokio::task::block_in_place(|| {
+            let mut this = this.blocking_lock();
+            this.mtu = read_mtu(&iface.name);
+            this.index = iface.index;
+        });
+        Ok(this)
+    }
+
+    /// 创建接口，按配置设置被动模式和环回模式，并启动报文捕获（被动接口和环回接口除外）
+    pub fn create(iface: &NetworkInterface, ip: Ipv4Network, area_id: Ipv4Addr) -> Result<AInterface, InterfaceError> {
+        let interface = Self::from(iface, ip, area_id)?;
+        let config = Config::get().interface(&iface.name);
+        tokio::task::block_in_place(|| {
+            let mut interface = interface.blocking_lock();
+            interface.instance = config.secondary_instance;
+            interface.passive = config.passive;
+            interface.loopback = config.loopback || iface.is_loopback();
+            if !interface.instance {
+                interface.secondary = secondary_networks(ip, ipv4_addrs(iface).skip(1), interface.loopback);
+            }
+            interface.te = config.te;
+            interface.prefix_sid = config.prefix_sid;
+            interface.cost_override = config.cost;
+            interface.refresh_cost();
+            // 被动接口和环回接口不收发 OSPF 报文，不需要捕获
+            if !interface.passive && !interface.loopback {
+                interface.start_capture();
+            }
+        });
+        Ok(interface)
     }
 
     pub fn start(this: &AInterface) {
-        listen::listen_interface(Arc::downgrade(this));
+        let listen = listen::listen_interface(Arc::downgrade(this));
+        tokio::task::block_in_place(|| this.blocking_lock().listen = listen);
+    }
+
+    /// 在该接口上启动 OSPF 报文捕获
+    pub fn start_capture(&mut self) {
+        guard!(Some(this) = self.me.upgrade());
+        let ospf_handler = handler::ospf_handler_maker(this, self.interface_name.clone());
+        match CaptureOspfDaemon::new(&self.get_network_interface(), ospf_handler) {
+            Ok(daemon) => self.capture = tokio::spawn(daemon.run_forever()).into(),
+            Err(e) => log_error!("failed to capture on {}: {}", self.interface_name, e),
+        }
+    }
+
+    /// 切换被动模式，接口会先 down 再 up
+    pub async fn set_passive(&mut self, passive: bool) {
+        must!(self.passive != passive);
+        self.interface_down().await;
+        self.passive = passive;
+        if passive || self.loopback {
+            self.capture.abort();
+        } else {
+            self.start_capture();
+        }
+        self.interface_up().await;
+    }
+
+    /// 根据系统中的接口信息更新从地址和 MTU，运行 OSPF 的地址变化时返回 false
+    pub async fn update(&mut self, net: &NetworkInterface) -> bool {
+        let running = instance_addrs(net).iter().any(|ip| ip.ip() == self.ip_addr && ip.mask() == self.ip_mask);
+        must!(running; ret: false);
+        let secondary = match self.instance {
+            true => vec![],
+            false => secondary_networks(ipv4_addrs(net).next().unwrap(), ipv4_addrs(net).skip(1), self.loopback),
+        };
+        let mtu = read_mtu(&self.interface_name);
+        if mtu != self.mtu {
+            log!("interface {}'s mtu is changed to {}", self.interface_name, mtu);
+            self.mtu = mtu;
+            // MTU 变化后需要重新建立邻接
+            if self.state != InterfaceState::Down {
+                self.interface_down().await;
+                self.interface_up().await;
+            }
+        }
+        if secondary != self.secondary {
+            self.secondary = secondary;
+            let this = self.me.upgrade().unwrap();
+            tokio::spawn(async move {
+                let iface = this.lock().await;
+                must!(iface.state != InterfaceState::Down);
+                guard!(Some(mut interfaces) = ProtocolDB::upgrade_lock(iface).await);
+                gen_lsa::gen_router_lsa(&mut interfaces).await;
+            });
+        }
+        true
     }
 
     pub fn get_network_interface(&self) -> NetworkInterface {
@@ -166,9 +316,24 @@ impl Interface {
         self.wait_timer.abort();
         self.retransmission_timer.abort();
         self.neighbors.clear();
+        sr::release(self.ip_addr, None);
         self.dr = hex2ip(0);
         self.bdr = hex2ip(0);
-        self.cost = 0;
+    }
+
+    /// 重新读取链路速率并计算开销，开销变化时返回 true
+    pub fn refresh_cost(&mut self) -> bool {
+        self.speed = read_speed(&self.interface_name);
+        let cost = self.cost_override.unwrap_or_else(|| auto_cost(self.speed));
+        must!(self.cost != cost; ret: false);
+        self.cost = cost;
+        true
+    }
+
+    /// 地址是否在主地址所在的网段中
+    pub fn in_subnet(&self, ip: Ipv4Addr) -> bool {
+        let mask = ip2hex(self.ip_mask);
+        ip2hex(ip) & mask == ip2hex(self.ip_addr) & mask
     }
 
     pub fn is_dr(&self) -> bool {
@@ -183,3 +348,230 @@ impl Interface {
         !self.is_dr() && !self.is_bdr()
     }
 }
+
+impl ToJson for Interface {
+    fn to_json(&self) -> Json {
+        let mut neighbors: Vec<_> = self.neighbors.values().collect();
+        neighbors.sort_by_key(|n| n.ip_addr);
+        json!({
+            "name": &self.interface_name,
+            "address": self.ip_addr,
+            "mask": self.ip_mask,
+            "area": self.area_id,
+            "network_type": format!("{:?}", self.net_type),
+            "state": format!("{:?}", self.state),
+            "cost": self.cost,
+            "cost_source": if self.cost_override.is_some() { "configured" } else { "auto" },
+            "speed": self.speed,
+            "priority": self.router_priority,
+            "hello_interval": self.hello_interval,
+            "dead_interval": self.dead_interval,
+            "passive": self.passive,
+            "loopback": self.loopback,
+            "dr": self.dr,
+            "bdr": self.bdr,
+            "neighbors": neighbors.into_iter().collect::<Json>(),
+        })
+    }
+}
+
+/// 从 sysfs 读取接口的 MTU，读取失败时使用以太网的默认值
+fn read_mtu(name: &str) -> u16 {
+    std::fs::read_to_string(format!("/sys/class/net/{name}/mtu"))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(1500)
+}
+
+/// 从 sysfs 读取接口的速率（Mbit/s），虚拟接口等没有速率时为 None
+fn read_speed(name: &str) -> Option<u32> {
+    let speed = std::fs::read_to_string(format!("/sys/class/net/{name}/speed")).ok()?;
+    speed.trim().parse::<i64>().ok().filter(|s| *s > 0).map(|s| s as u32)
+}
+
+/// 开销为参考带宽除以链路速率，至少为 1；速率未知时为 1
+fn auto_cost(speed: Option<u32>) -> u16 {
+    let reference = Config::get().reference_bandwidth();
+    speed.map_or(1, |speed| (reference / speed).clamp(1, u16::MAX as u32) as u16)
+}
+
+/// 接口上可以运行 OSPF 的 IPv4 地址（不含 127.0.0.0/8）
+pub fn ipv4_addrs(net: &NetworkInterface) -> impl Iterator<Item = Ipv4Network> + '_ {
+    net.ips.iter().filter_map(|ip| match ip {
+        IpNetwork::V4(ip) if !ip.ip().is_loopback() => Some(*ip),
+        _ => None,
+    })
+}
+
+/// 接口上运行 OSPF 的地址：通常只有主地址；配置了 secondary instance 时每个网段一个
+pub fn instance_addrs(net: &NetworkInterface) -> Vec<Ipv4Network> {
+    let mut ips = ipv4_addrs(net);
+    guard!(Some(primary) = ips.next(); ret: vec![]);
+    must!(Config::get().interface(&net.name).secondary_instance; ret: vec![primary]);
+    std::iter::once(primary).chain(secondary_networks(primary, ips, false)).collect()
+}
+
+/// 选择区域时使用的地址：单独运行的实例只看自己的地址，否则看接口的所有地址
+pub fn area_ips(net: &NetworkInterface, ip: Ipv4Network) -> Vec<IpNetwork> {
+    must!(Config::get().interface(&net.name).secondary_instance; ret: net.ips.clone());
+    vec![IpNetwork::V4(ip)]
+}
+
+/// 去掉与主地址同网段的地址，并对网段去重（保留接口地址，环回接口需要宣告主机路由）；
+/// 环回接口的每个地址都以 /32 宣告，只去掉重复的地址
+fn secondary_networks(
+    primary: Ipv4Network,
+    ips: impl Iterator<Item = Ipv4Network>,
+    loopback: bool,
+) -> Vec<Ipv4Network> {
+    let mut networks: Vec<Ipv4Network> = vec![];
+    for ip in ips {
+        if loopback {
+            must!(ip.ip() != primary.ip() && !networks.iter().any(|n| n.ip() == ip.ip()); continue);
+        } else {
+            must!(!primary.contains(ip.ip()) || primary.prefix() != ip.prefix(); continue);
+            must!(!networks.iter().any(|n| n.network() == ip.network() && n.prefix() == ip.prefix()); continue);
+        }
+        networks.push(ip);
+    }
+    networks
+}
+
+/// 被管理员停用的接口，同步系统接口列表时不会自动启用
+static DISABLED: std::sync::Mutex<BTreeSet<String>> = std::sync::Mutex::new(BTreeSet::new());
+
+/// 在运行时启用接口（不受配置文件中接口筛选规则的限制）
+pub async fn enable(name: &str) -> Result<(), InterfaceError> {
+    DISABLED.lock().unwrap().remove(name);
+    guard! {
+        Some(net) = datalink::interfaces().into_iter().find(|i| i.name == name);
+        ret: Err(InterfaceError::NotFound(name.to_string()))
+    };
+    let ips = instance_addrs(&net);
+    must!(!ips.is_empty(); ret: Err(InterfaceError::NoIpv4));
+    for ip in ips {
+        must!(find(ip.ip()).await.is_none(); continue);
+        let area_id = Config::get().area_of(&area_ips(&net, ip)).unwrap_or(BackboneArea);
+        add(&net, ip, area_id).await?;
+    }
+    Ok(())
+}
+
+/// 在运行时停用接口：停止收发报文，并从路由器 LSA 中撤销该接口
+pub async fn disable(name: &str) -> Result<(), InterfaceError> {
+    let mut instances = vec![];
+    for this in ProtocolDB::get_interface_list() {
+        if this.lock().await.interface_name == name {
+            instances.push(this);
+        }
+    }
+    must!(!instances.is_empty(); ret: Err(InterfaceError::NotFound(name.to_string())));
+    DISABLED.lock().unwrap().insert(name.to_string());
+    for this in instances {
+        remove(this).await;
+    }
+    Ok(())
+}
+
+/// 与系统的接口列表同步：启用新出现的接口，移除消失的接口，处理地址和 MTU 的变化
+pub async fn sync() {
+    let nets = datalink::interfaces();
+    for this in ProtocolDB::get_interface_list() {
+        let mut iface = this.lock().await;
+        let alive = match nets.iter().find(|n| n.name == iface.interface_name) {
+            Some(net) => iface.update(net).await,
+            None => false,
+        };
+        // 接口消失或运行 OSPF 的地址变化时移除接口，地址变化的接口会在下面重新加入
+        if !alive {
+            drop(iface);
+            remove(this).await;
+        }
+    }
+    for net in nets.iter() {
+        must!(!DISABLED.lock().unwrap().contains(&net.name); continue);
+        for ip in instance_addrs(net) {
+            must!(find(ip.ip()).await.is_none(); continue);
+            guard!(Some(area_id) = Config::get().select(&net.name, &area_ips(net, ip)); continue);
+            if let Err(e) = add(net, ip, area_id).await {
+                log_error!("failed to start interface {}: {}", net.name, e);
+            }
+        }
+    }
+}
+
+async fn add(net: &NetworkInterface, ip: Ipv4Network, area_id: Ipv4Addr) -> Result<(), InterfaceError> {
+    let this = Interface::create(net, ip, area_id)?;
+    ProtocolDB::get().await.insert_area(area_id).await;
+    ProtocolDB::add_interface(this.clone());
+    Interface::start(&this);
+    log_success!("interface {} is enabled on {} in area {}", net.name, ip.ip(), area_id);
+    Ok(())
+}
+
+async fn remove(this: AInterface) {
+    // 接口已经被另一次同步删除
+    guard!(Some(mut interfaces) = ProtocolDB::upgrade_lock(this.lock().await).await);
+    listen::withdraw(&mut interfaces).await;
+    let empty_area = flush_area(&mut interfaces).await;
+    let iface = &mut interfaces.me;
+    iface.listen.abort();
+    iface.capture.abort();
+    iface.interface_down().await;
+    // 链路范围的 LSA 随接口一起删除
+    ProtocolDB::get().await.links.remove(&iface.ip_addr);
+    let name = iface.interface_name.clone();
+    drop(interfaces);
+    ProtocolDB::remove_interface(&this);
+    log_success!("interface {} is disabled", name);
+    if let Some(area_id) = empty_area {
+        remove_area(area_id).await;
+    }
+    // 用剩下的接口重新生成各区域的 LSA
+    let interfaces = tokio::task::block_in_place(ProtocolDB::get_interfaces_impl);
+    must!(!interfaces.is_empty());
+    originate_areas(interfaces.into()).await;
+}
+
+/// 接口离开区域前调用：如果它是区域中的最后一个接口，清除本路由器在该区域生成的 LSA，
+/// 返回接口离开后需要删除的区域
+async fn flush_area(interfaces: &mut InterfacesGuard) -> Option<Ipv4Addr> {
+    let area_id = interfaces.me.area_id;
+    must!(!interfaces.other.iter().any(|i| i.area_id == area_id); ret: None);
+    gen_lsa::flush_lsa(interfaces, ProtocolDB::get_router_id()).await;
+    Some(area_id)
+}
+
+/// 删除已经没有接口的区域，不再是区域边界路由器时路由器 LSA 中的 B 位随之清除
+async fn remove_area(area_id: Ipv4Addr) {
+    let mut db = ProtocolDB::get().await;
+    must!(db.areas.remove(&area_id).is_some());
+    db.recalc_routing().await;
+    log_success!("area {} is removed", area_id);
+}
+
+async fn find(ip: Ipv4Addr) -> Option<AInterface> {
+    for iface in ProtocolDB::get_interface_list() {
+        if iface.lock().await.ip_addr == ip {
+            return Some(iface);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let nets = |s: &[&str]| s.iter().map(|s| s.parse::<Ipv4Network>().unwrap()).collect::<Vec<_>>();
+        let primary = "10.0.0.1/24".parse().unwrap();
+        let ips = nets(&["10.0.0.2/24", "10.0.1.1/24", "10.0.1.2/24", "10.0.0.1/24"]);
+        assert_eq!(secondary_networks(primary, ips.clone().into_iter(), false), nets(&["10.0.1.1/24"]));
+        assert_eq!(
+            secondary_networks(primary, ips.into_iter(), true),
+            nets(&["10.0.0.2/24", "10.0.1.1/24", "10.0.1.2/24"])
+        );
+    }
+}
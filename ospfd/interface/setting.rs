@@ -0,0 +1,170 @@
+//! 运行时修改接口参数
+//!
+//! 每个设置在持有所有接口锁时完成检查、修改和相应的接口事件，然后立即重新生成 LSA，不等待接口检查：
+//! 开销变化只需要重新生成路由器 LSA；优先级变化重新选举 DR；Hello/Dead 间隔变化后邻居的参数不再一致，
+//! 接口先 down 再 up 重置邻接；区域变化时接口离开原区域（原区域不再有接口时将其删除），在新区域中重新启动。
+
+use std::{net::Ipv4Addr, ops::RangeInclusive};
+
+use crate::{
+    database::{InterfacesGuard, ProtocolDB},
+    must,
+};
+
+use super::{flush_area, listen, remove_area, Interface, InterfaceError, InterfaceEvent, InterfaceState};
+
+/// 指定名称的接口上所有实例的地址，设置对每个实例分别生效
+fn instances(name: &str) -> Result<Vec<Ipv4Addr>, InterfaceError> {
+    let ips: Vec<_> = tokio::task::block_in_place(ProtocolDB::get_interfaces_impl)
+        .iter()
+        .filter(|i| i.interface_name == name)
+        .map(|i| i.ip_addr)
+        .collect();
+    must!(!ips.is_empty(); ret: Err(InterfaceError::NotFound(name.to_string())));
+    Ok(ips)
+}
+
+/// 取得所有接口的锁，me 为运行在地址 ip 上的实例
+fn lock(name: &str, ip: Ipv4Addr) -> Result<InterfacesGuard, InterfaceError> {
+    let interfaces = tokio::task::block_in_place(ProtocolDB::get_interfaces_impl);
+    InterfacesGuard::from(interfaces, ip).ok_or_else(|| InterfaceError::NotFound(name.to_string()))
+}
+
+fn check(what: &'static str, value: u32, range: RangeInclusive<u32>) -> Result<u32, InterfaceError> {
+    must!(range.contains(&value); ret: Err(InterfaceError::OutOfRange(what, value, range)));
+    Ok(value)
+}
+
+/// 正在运行的接口先 down，修改后再 up
+async fn restart(iface: &mut Interface, f: impl FnOnce(&mut Interface)) {
+    let running = !matches!(iface.state, InterfaceState::Down | InterfaceState::Loopback);
+    if running {
+        iface.interface_down().await;
+    }
+    f(iface);
+    if running {
+        iface.interface_up().await;
+    }
+}
+
+/// 修改接口开销，None 表示按参考带宽计算，返回生效的开销
+///
+/// # Safety
+/// This function should be awaited when caller hasn't have any locks.
+pub async fn set_cost(name: &str, cost: Option<u32>) -> Result<u16, InterfaceError> {
+    let cost = cost.map(|c| check("cost", c, 1..=0xffff)).transpose()?;
+    let mut effective = 0;
+    for ip in instances(name)? {
+        let mut interfaces = lock(name, ip)?;
+        interfaces.me.cost_override = cost.map(|c| c as u16);
+        interfaces.me.refresh_cost();
+        effective = interfaces.me.cost;
+        listen::originate_areas(interfaces).await;
+    }
+    Ok(effective)
+}
+
+/// 修改路由器优先级，重新选举 DR
+///
+/// # Safety
+/// This function should be awaited when caller hasn't have any locks.
+pub async fn set_priority(name: &str, priority: u32) -> Result<(), InterfaceError> {
+    let priority = check("priority", priority, 0..=0xff)? as u8;
+    for ip in instances(name)? {
+        let mut interfaces = lock(name, ip)?;
+        must!(interfaces.me.router_priority != priority; continue);
+        // 优先级为 0 的接口直接进入 DROther，从 0 变为非 0 时需要重新等待选举
+        if priority == 0 || interfaces.me.router_priority == 0 {
+            restart(&mut interfaces.me, |iface| iface.router_priority = priority).await;
+        } else {
+            interfaces.me.router_priority = priority;
+            interfaces.me.neighbor_change().await;
+        }
+        listen::originate_areas(interfaces).await;
+    }
+    Ok(())
+}
+
+/// 修改 Hello 间隔和 Dead 间隔，None 表示不变，Dead 间隔必须大于 Hello 间隔
+///
+/// # Safety
+/// This function should be awaited when caller hasn't have any locks.
+pub async fn set_timers(name: &str, hello: Option<u32>, dead: Option<u32>) -> Result<(), InterfaceError> {
+    for ip in instances(name)? {
+        let mut interfaces = lock(name, ip)?;
+        let hello = check("hello interval", hello.unwrap_or(interfaces.me.hello_interval as u32), 1..=0xffff)?;
+        let dead = check("dead interval", dead.unwrap_or(interfaces.me.dead_interval), hello + 1..=u32::MAX)?;
+        must!(interfaces.me.hello_interval as u32 != hello || interfaces.me.dead_interval != dead; continue);
+        restart(&mut interfaces.me, |iface| {
+            iface.hello_interval = hello as u16;
+            iface.dead_interval = dead;
+        })
+        .await;
+        listen::originate_areas(interfaces).await;
+    }
+    Ok(())
+}
+
+/// 修改接口所在的区域
+///
+/// # Safety
+/// This function should be awaited when caller hasn't have any locks.
+pub async fn set_area(name: &str, area_id: Ipv4Addr) -> Result<(), InterfaceError> {
+    for ip in instances(name)? {
+        let mut interfaces = lock(name, ip)?;
+        must!(interfaces.me.area_id != area_id; continue);
+        ProtocolDB::get().await.insert_area(area_id).await;
+        listen::withdraw(&mut interfaces).await;
+        let empty_area = flush_area(&mut interfaces).await;
+        restart(&mut interfaces.me, |iface| iface.area_id = area_id).await;
+        if let Some(area_id) = empty_area {
+            remove_area(area_id).await;
+        }
+        listen::originate_areas(interfaces).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::guard;
+    use pnet::datalink;
+
+    fn out_of_range<T>(result: Result<T, InterfaceError>, what: &str) -> bool {
+        matches!(result, Err(InterfaceError::OutOfRange(w, ..)) if w == what)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test() {
+        assert!(out_of_range(set_cost("lo", Some(0)).await, "cost"));
+        assert!(out_of_range(set_cost("lo", Some(0x10000)).await, "cost"));
+        assert!(out_of_range(set_priority("lo", 0x100).await, "priority"));
+
+        // 在环回接口上运行一个实例，没有原始套接字时跳过
+        let (old_area, new_area) = (Ipv4Addr::new(0, 0, 0, 46), Ipv4Addr::new(0, 0, 0, 47));
+        guard!(Some(lo) = datalink::interfaces().into_iter().find(|i| i.is_loopback()));
+        guard!(Ok(iface) = Interface::from(&lo, "127.0.46.1/8".parse().unwrap(), old_area));
+        ProtocolDB::get().await.insert_area(old_area).await;
+        ProtocolDB::add_interface(iface.clone());
+
+        assert!(out_of_range(set_timers(&lo.name, Some(0), None).await, "hello interval"));
+        assert!(out_of_range(set_timers(&lo.name, Some(10), Some(10)).await, "dead interval"));
+        assert!(out_of_range(set_timers(&lo.name, None, Some(5)).await, "dead interval"));
+        set_timers(&lo.name, Some(5), Some(20)).await.unwrap();
+        assert_eq!(set_cost(&lo.name, Some(7)).await.unwrap(), 7);
+        set_priority(&lo.name, 3).await.unwrap();
+        let me = iface.lock().await;
+        assert_eq!((me.hello_interval, me.dead_interval, me.cost, me.router_priority), (5, 20, 7, 3));
+        drop(me);
+
+        // 最后一个接口离开后删除原来的区域
+        set_area(&lo.name, new_area).await.unwrap();
+        assert_eq!(iface.lock().await.area_id, new_area);
+        let db = ProtocolDB::get().await;
+        assert!(!db.areas.contains_key(&old_area) && db.areas.contains_key(&new_area));
+        drop(db);
+        ProtocolDB::remove_interface(&iface);
+        assert!(matches!(set_area(&lo.name, old_area).await, Err(InterfaceError::NotFound(_))));
+    }
+}
@@ -11,15 +11,13 @@ use super::{Interface, NetType};
 use crate::{
     constant::AllSPFRouters,
     database::ProtocolDB,
-    guard, log_error, log_success, must,
+    guard, log_debug, log_error, log_success, must,
     neighbor::{Neighbor, NeighborEvent, NeighborState, RefNeighbor},
     sender::send_packet,
+    statistics,
     util::hex2ip,
 };
 
-#[cfg(debug_assertions)]
-use crate::log;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterfaceState {
     Down,
@@ -42,9 +40,9 @@ pub trait InterfaceEvent: Send {
     async fn interface_down(&mut self);
 }
 
-#[cfg(debug_assertions)]
 fn log_event(event: &str, interface: &Interface) {
-    log!(
+    log_debug!(
+        Ism;
         "interface {}({:?}) recv event: {}",
         interface.interface_name,
         interface.state,
@@ -54,7 +52,9 @@ fn log_event(event: &str, interface: &Interface) {
 
 fn log_state(old: InterfaceState, interface: &Interface) {
     must!(old != interface.state);
+    statistics::interface_state_changed(&interface.interface_name);
     log_success!(
+        Ism;
         "interface {}'s state changed: {:?} -> {:?}",
         interface.interface_name,
         old,
@@ -64,7 +64,6 @@ fn log_state(old: InterfaceState, interface: &Interface) {
 
 impl InterfaceEvent for Interface {
     async fn interface_up(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("interface_up", self);
         must!(self.state == InterfaceState::Down);
         let iface = self.get_network_interface();
@@ -79,7 +78,10 @@ impl InterfaceEvent for Interface {
         } else {
             NetType::Virtual
         };
-        self.state = if matches!(
+        self.state = if self.passive {
+            // 被动接口不发送 Hello，也不参与 DR 选举
+            InterfaceState::DROther
+        } else if matches!(
             self.net_type,
             NetType::P2P | NetType::P2MP | NetType::Virtual
         ) {
@@ -96,12 +98,13 @@ impl InterfaceEvent for Interface {
             .into();
             InterfaceState::Waiting
         };
-        set_hello_timer(self);
+        if !self.passive {
+            set_hello_timer(self);
+        }
         log_state(InterfaceState::Down, self);
     }
 
     async fn wait_timer(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("wait_timer", self);
         must!(self.state == InterfaceState::Waiting);
         select_dr(self).await;
@@ -109,7 +112,6 @@ impl InterfaceEvent for Interface {
     }
 
     async fn backup_seen(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("backup_seen", self);
         must!(self.state == InterfaceState::Waiting);
         select_dr(self).await;
@@ -117,7 +119,6 @@ impl InterfaceEvent for Interface {
     }
 
     async fn neighbor_change(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("neighbor_change", self);
         let old = self.state;
         use InterfaceState::*;
@@ -127,7 +128,6 @@ impl InterfaceEvent for Interface {
     }
 
     async fn loop_ind(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("neighbor_change", self);
         let old = self.state;
         self.reset();
@@ -136,7 +136,6 @@ impl InterfaceEvent for Interface {
     }
 
     async fn unloop_ind(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("unloop_ind", self);
         let old = self.state;
         must!(old == InterfaceState::Loopback);
@@ -145,7 +144,6 @@ impl InterfaceEvent for Interface {
     }
 
     async fn interface_down(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("interface_down", self);
         let old = self.state;
         self.reset();
@@ -172,7 +170,6 @@ fn set_hello_timer(interface: &mut Interface) {
     interface.retransmission_timer = tokio::spawn(async move {
         while let Some(interface) = weak.upgrade() {
             let mut interface = interface.lock().await;
-            let area_id = interface.area_id;
             let hello_interval = interface.rxmt_interval as u64;
             let rt = tokio::runtime::Handle::current();
             let packets: Vec<_> = tokio::task::block_in_place(|| {
@@ -186,7 +183,7 @@ fn set_hello_timer(interface: &mut Interface) {
                             .iter()
                             .take(8)
                             .filter_map(|&key| {
-                                rt.block_on(rt.block_on(ProtocolDB::get()).get_lsa(area_id, key))
+                                rt.block_on(rt.block_on(ProtocolDB::get()).get_lsa(&interface, key))
                                     .map(|(lsa, ..)| lsa)
                             })
                             .collect();
@@ -224,6 +221,8 @@ async fn send_hello(interface: &mut Interface) {
     if interface.external_routing {
         packet.set(packet::options::E);
     }
+    packet.set(packet::options::O);
+    packet.set(packet::options::L);
     send_packet(interface, &packet, AllSPFRouters).await;
 }
 
@@ -0,0 +1,483 @@
+//! JSON 输出
+//!
+//! display 命令加上 `json` 时输出一行 JSON，供脚本解析。对象的键按写入的顺序输出；
+//! 以地址等为键的表输出为对象数组。路由器总是显示为路由器标识，不换成主机名。
+
+use std::{fmt, io, net::Ipv4Addr};
+
+use ospf_packet::{
+    gr::{restart_reasons, GraceLsa, GRACE_OPAQUE_TYPE},
+    lsa::{link_types, types, Lsa, LsaData, LsaHeader},
+    ri::{capabilities, RouterInfo, RI_OPAQUE_TYPE},
+    sr::{
+        ExtendedLink, ExtendedPrefix, Sid, SidLabelRange, EXTENDED_LINK_OPAQUE_TYPE,
+        EXTENDED_PREFIX_OPAQUE_TYPE,
+    },
+    te::{te_link_types, TeLink, TeLsa, TE_OPAQUE_TYPE},
+    tlv::Tlv,
+    TryFromBuf,
+};
+use pnet::ipnetwork::Ipv4Network;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// 已经格式化的数字
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+/// 按顺序构造 JSON 对象：`json!({ "key": value, ... })`
+#[macro_export]
+macro_rules! json {
+    ({ $($k:literal : $v:expr),* $(,)? }) => {
+        $crate::json::Json::Object(vec![$(($k, $crate::json::Json::from($v))),*])
+    };
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(value: $t) -> Self {
+                Json::Number(value.to_string())
+            }
+        })*
+    };
+}
+
+impl_number!(u8, u16, u32, u64, usize, i32, i64);
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        if value.is_finite() {
+            Json::Number(value.to_string())
+        } else {
+            Json::Null
+        }
+    }
+}
+
+impl From<f32> for Json {
+    fn from(value: f32) -> Self {
+        Json::from(value as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<&String> for Json {
+    fn from(value: &String) -> Self {
+        Json::String(value.clone())
+    }
+}
+
+impl From<Ipv4Addr> for Json {
+    fn from(value: Ipv4Addr) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<Ipv4Network> for Json {
+    fn from(value: Ipv4Network) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(value: Vec<T>) -> Self {
+        Json::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: ToJson> FromIterator<T> for Json {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Json::Array(iter.into_iter().map(|item| item.to_json()).collect())
+    }
+}
+
+impl ToJson for Json {
+    fn to_json(&self) -> Json {
+        self.clone()
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => f.write_str(n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// 输出一行 JSON。不使用 echo!，避免清除行的控制字符混进输出；终端处于原始模式，换行需要 \r\n
+pub fn write_line(out: &mut impl io::Write, json: &Json) -> io::Result<()> {
+    write!(out, "{json}\r\n")?;
+    out.flush()
+}
+
+/// 无法解释的数据输出为十六进制字符串
+pub fn hex(data: &[u8]) -> Json {
+    Json::String(data.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+impl ToJson for LsaHeader {
+    fn to_json(&self) -> Json {
+        json!({
+            "type": self.ls_type,
+            "type_name": types::to_string(self.ls_type),
+            "link_state_id": self.link_state_id,
+            "advertising_router": self.advertising_router,
+            "age": self.ls_age,
+            "options": self.options,
+            "sequence": format!("{:#010x}", self.ls_sequence_number),
+            "checksum": format!("{:#06x}", self.ls_checksum),
+            "length": self.length,
+        })
+    }
+}
+
+/// 首部和解码后的内容
+impl ToJson for Lsa {
+    fn to_json(&self) -> Json {
+        let mut json = self.header.to_json();
+        if let Json::Object(ref mut fields) = json {
+            fields.push(("body", lsa_body(&self.header, &self.data)));
+        }
+        json
+    }
+}
+
+fn lsa_body(header: &LsaHeader, data: &LsaData) -> Json {
+    match data {
+        LsaData::Router(lsa) => {
+            let links: Vec<_> = lsa
+                .links
+                .iter()
+                .map(|link| {
+                    json!({
+                        "type": link_types::to_string(link.link_type),
+                        "link_id": link.link_id,
+                        "link_data": link.link_data,
+                        "metric": link.metric,
+                    })
+                })
+                .collect();
+            json!({ "virtual_link": lsa.v == 1, "asbr": lsa.e == 1, "abr": lsa.b == 1, "links": links })
+        }
+        LsaData::Network(lsa) => {
+            json!({ "network_mask": lsa.network_mask, "attached_routers": lsa.attached_routers.clone() })
+        }
+        LsaData::SummaryIP(lsa) | LsaData::SummaryASBR(lsa) => {
+            json!({ "network_mask": lsa.network_mask, "metric": lsa.metric })
+        }
+        LsaData::ASExternal(lsa) => json!({
+            "network_mask": lsa.network_mask,
+            "metric_type": if lsa.e == 1 { 2 } else { 1 },
+            "metric": lsa.metric,
+            "forwarding_address": lsa.forwarding_address,
+            "tag": lsa.external_router_tag,
+        }),
+        LsaData::Opaque(lsa) => {
+            let data = &lsa.data;
+            let decoded = match header.opaque_type() {
+                TE_OPAQUE_TYPE => TeLsa::try_from_slice(data).map(|te| te.to_json()),
+                RI_OPAQUE_TYPE => RouterInfo::try_from_slice(data).map(|ri| ri.to_json()),
+                GRACE_OPAQUE_TYPE => GraceLsa::try_from_slice(data).map(|grace| grace.to_json()),
+                EXTENDED_PREFIX_OPAQUE_TYPE => ExtendedPrefix::try_from_slice(data).map(|p| p.to_json()),
+                EXTENDED_LINK_OPAQUE_TYPE => ExtendedLink::try_from_slice(data).map(|l| l.to_json()),
+                _ => Ok(json!({ "data": hex(data) })),
+            };
+            json!({
+                "opaque_type": header.opaque_type(),
+                "opaque_id": header.opaque_local_id(),
+                "decoded": decoded.unwrap_or_else(|_| json!({ "data": hex(data) })),
+            })
+        }
+    }
+}
+
+impl ToJson for Tlv {
+    fn to_json(&self) -> Json {
+        json!({ "type": self.ty, "value": hex(&self.value) })
+    }
+}
+
+/// 带宽为报文中的原始值（字节每秒）
+impl ToJson for TeLink {
+    fn to_json(&self) -> Json {
+        json!({
+            "link_type": te_link_types::to_string(self.link_type),
+            "link_id": self.link_id,
+            "local_addresses": self.local_addrs.clone(),
+            "remote_addresses": self.remote_addrs.clone(),
+            "te_metric": self.te_metric,
+            "max_bandwidth": self.max_bandwidth,
+            "max_reservable_bandwidth": self.max_reservable_bandwidth,
+            "unreserved_bandwidth": self.unreserved_bandwidth.map(|bw| bw.to_vec()),
+            "admin_group": self.admin_group,
+        })
+    }
+}
+
+impl ToJson for TeLsa {
+    fn to_json(&self) -> Json {
+        match self {
+            TeLsa::RouterAddress(addr) => json!({ "router_address": *addr }),
+            TeLsa::Link(link) => json!({ "link": link.to_json() }),
+            TeLsa::Unknown(tlv) => json!({ "tlv": tlv.to_json() }),
+        }
+    }
+}
+
+impl ToJson for Sid {
+    fn to_json(&self) -> Json {
+        match self {
+            Sid::Label(label) => json!({ "label": *label }),
+            Sid::Index(index) => json!({ "index": *index }),
+        }
+    }
+}
+
+impl ToJson for SidLabelRange {
+    fn to_json(&self) -> Json {
+        json!({ "first": self.first.to_json(), "size": self.size })
+    }
+}
+
+impl ToJson for RouterInfo {
+    fn to_json(&self) -> Json {
+        json!({
+            "capabilities": self.capabilities,
+            "capability_names": self.capabilities.map(capabilities::to_string),
+            "hostname": self.hostname.clone(),
+            "sr_algorithms": self.sr_algorithms.clone(),
+            "srgb": self.srgb.map(|r| r.to_json()),
+            "srlb": self.srlb.map(|r| r.to_json()),
+            "others": self.others.iter().collect::<Json>(),
+        })
+    }
+}
+
+impl ToJson for ExtendedPrefix {
+    fn to_json(&self) -> Json {
+        let sids: Vec<_> = self
+            .prefix_sids
+            .iter()
+            .map(|sid| json!({ "flags": sid.flags, "algorithm": sid.algorithm, "sid": sid.sid.to_json() }))
+            .collect();
+        json!({
+            "route_type": self.route_type,
+            "prefix": format!("{}/{}", self.prefix, self.prefix_len),
+            "flags": self.flags,
+            "prefix_sids": sids,
+        })
+    }
+}
+
+impl ToJson for ExtendedLink {
+    fn to_json(&self) -> Json {
+        let sids: Vec<_> = self
+            .adj_sids
+            .iter()
+            .map(|sid| {
+                json!({
+                    "flags": sid.flags,
+                    "weight": sid.weight,
+                    "neighbor": sid.neighbor,
+                    "sid": sid.sid.to_json(),
+                })
+            })
+            .collect();
+        json!({
+            "link_type": link_types::to_string(self.link_type),
+            "link_id": self.link_id,
+            "link_data": self.link_data,
+            "adj_sids": sids,
+        })
+    }
+}
+
+impl ToJson for GraceLsa {
+    fn to_json(&self) -> Json {
+        json!({
+            "grace_period": self.grace_period,
+            "reason": restart_reasons::to_string(self.reason),
+            "interface_address": self.interface_address,
+        })
+    }
+}
+
+impl<T: ToJson> ToJson for &T {
+    fn to_json(&self) -> Json {
+        (*self).to_json()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let json = json!({
+            "name": "eth\"0\n",
+            "cost": 10u16,
+            "bw": f32::NAN,
+            "dr": Some(Ipv4Addr::new(10, 0, 0, 1)),
+            "bdr": None::<Ipv4Addr>,
+            "list": vec![1u8, 2],
+            "nested": json!({ "up": true }),
+        });
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"eth\"0\n","cost":10,"bw":null,"dr":"10.0.0.1","bdr":null,"list":[1,2],"nested":{"up":true}}"#
+        );
+        let header = LsaHeader {
+            ls_age: 1,
+            options: 2,
+            ls_type: types::ROUTER_LSA,
+            link_state_id: Ipv4Addr::new(1, 1, 1, 1),
+            advertising_router: Ipv4Addr::new(1, 1, 1, 1),
+            ls_sequence_number: 0x80000001u32 as i32,
+            ls_checksum: 0xabcd,
+            length: 24,
+        };
+        let text = header.to_json().to_string();
+        assert!(text.contains(r#""type_name":"Router""#) && text.contains(r#""sequence":"0x80000001""#));
+
+        for json in [json, header.to_json(), Json::Array(vec![])] {
+            let mut out = vec![];
+            write_line(&mut out, &json).unwrap();
+            let line = String::from_utf8(out).unwrap();
+            assert!(line.ends_with("\r\n") && !line.contains('\x1b'));
+            assert_eq!(value(&line).map(skip_ws), Some(""), "{line}");
+        }
+        assert_eq!(value("{\"a\":[1,}"), None);
+    }
+
+    fn skip_ws(s: &str) -> &str {
+        s.trim_start_matches([' ', '\t', '\r', '\n'])
+    }
+
+    /// 解析一个 JSON 值，返回剩余的部分
+    fn value(s: &str) -> Option<&str> {
+        let s = skip_ws(s);
+        let (open, close) = match s.chars().next()? {
+            '"' => return string(s),
+            '{' => ('{', '}'),
+            '[' => ('[', ']'),
+            _ => return literal(s),
+        };
+        let mut s = skip_ws(s.strip_prefix(open)?);
+        if let Some(rest) = s.strip_prefix(close) {
+            return Some(rest);
+        }
+        loop {
+            if open == '{' {
+                s = skip_ws(skip_ws(string(s)?).strip_prefix(':')?);
+            }
+            s = skip_ws(value(s)?);
+            match s.strip_prefix(',') {
+                Some(rest) => s = skip_ws(rest),
+                None => return s.strip_prefix(close),
+            }
+        }
+    }
+
+    fn string(s: &str) -> Option<&str> {
+        let mut chars = s.strip_prefix('"')?.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Some(&s[i + 2..]),
+                '\\' => match chars.next()?.1 {
+                    'u' => (0..4).try_for_each(|_| chars.next()?.1.is_ascii_hexdigit().then_some(()))?,
+                    c if "\"\\/bfnrt".contains(c) => (),
+                    _ => return None,
+                },
+                c if c < ' ' => return None,
+                _ => (),
+            }
+        }
+        None
+    }
+
+    fn literal(s: &str) -> Option<&str> {
+        for word in ["true", "false", "null"] {
+            if let Some(rest) = s.strip_prefix(word) {
+                return Some(rest);
+            }
+        }
+        let end = s.find(|c: char| !"+-.eE0123456789".contains(c)).unwrap_or(s.len());
+        s[..end].parse::<f64>().ok()?;
+        Some(&s[end..])
+    }
+}
@@ -1,39 +1,479 @@
+//! 分级日志
+//!
+//! 每条日志带有时间、级别和子系统，同时写到终端、日志文件（按大小轮转）和 syslog/journald 套接字。
+//! 默认只输出 info 及以上级别的日志；用 `debug ospf <子系统>` 打开某个子系统的调试日志，
+//! 未打开时调试日志在格式化之前就被丢弃。命令的输出用 [`echo!`] 打印，不属于日志。
+
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::net::UnixDatagram,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU16, AtomicU8, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use ospf_packet::packet::types;
+
+use crate::{config::LogConfig, must};
+
+/// 默认单个日志文件的大小（MB）
+pub const DEFAULT_FILE_SIZE: u64 = 10;
+/// 默认保留的轮转日志文件数
+pub const DEFAULT_FILE_ROTATE: u32 = 5;
+/// 默认的 syslog 套接字，systemd 下由 journald 接收
+pub const DEFAULT_SYSLOG: &str = "/dev/log";
+
+/// 日志级别，数值与 syslog 的严重程度相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+    Debug = 7,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    General,
+    Hello,
+    Dd,
+    Lsu,
+    Flooding,
+    Spf,
+    Fib,
+    /// 接口状态机
+    Ism,
+    /// 邻居状态机
+    Nsm,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 9] = [
+        Subsystem::General,
+        Subsystem::Hello,
+        Subsystem::Dd,
+        Subsystem::Lsu,
+        Subsystem::Flooding,
+        Subsystem::Spf,
+        Subsystem::Fib,
+        Subsystem::Ism,
+        Subsystem::Nsm,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::General => "general",
+            Subsystem::Hello => "hello",
+            Subsystem::Dd => "dd",
+            Subsystem::Lsu => "lsu",
+            Subsystem::Flooding => "flooding",
+            Subsystem::Spf => "spf",
+            Subsystem::Fib => "fib",
+            Subsystem::Ism => "ism",
+            Subsystem::Nsm => "nsm",
+        }
+    }
+
+    /// 收发报文的日志所属的子系统，LSR 和 LSAck 也归入 lsu
+    pub fn of_packet(message_type: u8) -> Self {
+        match message_type {
+            types::HELLO_PACKET => Subsystem::Hello,
+            types::DB_DESCRIPTION => Subsystem::Dd,
+            types::LS_REQUEST | types::LS_UPDATE | types::LS_ACKNOWLEDGE => Subsystem::Lsu,
+            _ => Subsystem::General,
+        }
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+impl FromStr for Subsystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|sub| sub.name() == s)
+            .ok_or(format!("bad subsystem: {s}"))
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Level::Error),
+            "warning" => Ok(Level::Warning),
+            "notice" => Ok(Level::Notice),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            _ => Err(format!("bad log level: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Notice => "notice",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        };
+        f.pad(name)
+    }
+}
+
+/// 一条日志
+pub struct Record<'a> {
+    pub time: SystemTime,
+    pub level: Level,
+    pub subsystem: Subsystem,
+    pub message: fmt::Arguments<'a>,
+}
+
+/// 日志的输出目标
+trait Sink: Send {
+    fn write(&mut self, record: &Record);
+}
+
+/// 本地时间
+struct Time {
+    tm: libc::tm,
+    millis: u32,
+}
+
+impl Time {
+    fn new(time: SystemTime) -> Self {
+        let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since.as_secs() as libc::time_t;
+        // SAFETY: localtime_r 只写入传入的 tm
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        unsafe { libc::localtime_r(&secs, &mut tm) };
+        Self {
+            tm,
+            millis: since.subsec_millis(),
+        }
+    }
+
+    fn clock(&self) -> String {
+        let tm = &self.tm;
+        format!("{:02}:{:02}:{:02}.{:03}", tm.tm_hour, tm.tm_min, tm.tm_sec, self.millis)
+    }
+
+    fn date(&self) -> String {
+        let tm = &self.tm;
+        format!("{}-{:02}-{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday)
+    }
+
+    /// RFC 3164 的时间格式
+    fn syslog(&self) -> String {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let tm = &self.tm;
+        let month = MONTHS[tm.tm_mon.clamp(0, 11) as usize];
+        format!("{} {:>2} {:02}:{:02}:{:02}", month, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec)
+    }
+}
+
+/// 终端：按级别着色，先清除当前的命令行
+struct Terminal;
+
+impl Sink for Terminal {
+    fn write(&mut self, record: &Record) {
+        let color = match record.level {
+            Level::Error => "\x1b[31m",
+            Level::Warning => "\x1b[33m",
+            Level::Notice => "\x1b[32m",
+            Level::Info | Level::Debug => "",
+        };
+        let tag = match record.subsystem {
+            Subsystem::General => String::new(),
+            sub => format!("[{}] ", sub.name()),
+        };
+        let time = Time::new(record.time).clock();
+        println!("\r\x1b[2K{color}{time} {tag}{}\x1b[39m", record.message);
+    }
+}
+
+/// 日志文件：超过大小时轮转为 path.1、path.2 ……，只保留 rotate 个旧文件
+struct File {
+    path: PathBuf,
+    size: u64,
+    rotate: u32,
+    file: Option<fs::File>,
+    written: u64,
+}
+
+impl File {
+    fn open(path: PathBuf, size: u64, rotate: u32) -> io::Result<Self> {
+        let mut file = Self {
+            path,
+            size,
+            rotate,
+            file: None,
+            written: 0,
+        };
+        file.reopen()?;
+        Ok(file)
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.written = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn rotated(&self, n: u32) -> PathBuf {
+        format!("{}.{}", self.path.display(), n).into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        for n in (1..self.rotate).rev() {
+            let _ = fs::rename(self.rotated(n), self.rotated(n + 1));
+        }
+        if self.rotate == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.reopen()
+    }
+}
+
+impl Sink for File {
+    fn write(&mut self, record: &Record) {
+        let time = Time::new(record.time);
+        let line = format!(
+            "{} {} {:<7} [{}] {}\n",
+            time.date(),
+            time.clock(),
+            record.level,
+            record.subsystem.name(),
+            record.message
+        );
+        let Some(ref mut file) = self.file else { return };
+        // 写日志失败时不能再写日志，只能丢弃
+        must!(file.write_all(line.as_bytes()).is_ok());
+        self.written += line.len() as u64;
+        if self.written >= self.size {
+            let _ = self.rotate();
+        }
+    }
+}
+
+/// syslog 套接字，发送失败时重新连接一次，journald 也在 /dev/log 上接收
+struct Syslog {
+    path: PathBuf,
+    socket: Option<UnixDatagram>,
+}
+
+impl Syslog {
+    fn send(&mut self, msg: &[u8]) -> io::Result<()> {
+        if self.socket.is_none() {
+            let socket = UnixDatagram::unbound()?;
+            socket.connect(&self.path)?;
+            self.socket = Some(socket);
+        }
+        let result = self.socket.as_ref().unwrap().send(msg);
+        if result.is_err() {
+            self.socket = None;
+        }
+        result.map(|_| ())
+    }
+}
+
+impl Sink for Syslog {
+    fn write(&mut self, record: &Record) {
+        // facility: daemon
+        let priority = 3 * 8 + record.level as u8;
+        let msg = format!(
+            "<{}>{} ospfd[{}]: [{}] {}",
+            priority,
+            Time::new(record.time).syslog(),
+            std::process::id(),
+            record.subsystem.name(),
+            record.message
+        );
+        if self.send(msg.as_bytes()).is_err() {
+            let _ = self.send(msg.as_bytes());
+        }
+    }
+}
+
+/// 没有打开调试的子系统输出的最低级别
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+/// 打开了调试的子系统
+static DEBUG: AtomicU16 = AtomicU16::new(0);
+
+lazy_static! {
+    static ref SINKS: Mutex<Vec<Box<dyn Sink>>> = Mutex::new(vec![Box::new(Terminal)]);
+}
+
+/// 按配置打开日志文件和 syslog，在读取配置后调用一次
+pub fn init(config: &LogConfig) {
+    if let Some(level) = config.level {
+        LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+    config.debug.iter().for_each(|sub| set_debug(Some(*sub), true));
+    if let Some(ref file) = config.file {
+        match File::open(file.path.clone(), file.size * 1024 * 1024, file.rotate) {
+            Ok(sink) => SINKS.lock().unwrap().push(Box::new(sink)),
+            Err(e) => crate::log_error!("failed to open log file {}: {}", file.path.display(), e),
+        }
+    }
+    if let Some(ref path) = config.syslog {
+        let sink = Syslog {
+            path: path.clone(),
+            socket: None,
+        };
+        SINKS.lock().unwrap().push(Box::new(sink));
+    }
+}
+
+pub fn enabled(level: Level, subsystem: Subsystem) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+        || DEBUG.load(Ordering::Relaxed) & subsystem.bit() != 0
+}
+
+pub fn write(level: Level, subsystem: Subsystem, message: fmt::Arguments) {
+    let record = Record {
+        time: SystemTime::now(),
+        level,
+        subsystem,
+        message,
+    };
+    let mut sinks = SINKS.lock().unwrap_or_else(|e| e.into_inner());
+    sinks.iter_mut().for_each(|sink| sink.write(&record));
+}
+
+/// 打开或关闭子系统的调试日志，None 表示所有子系统
+pub fn set_debug(subsystem: Option<Subsystem>, on: bool) {
+    let bits = subsystem.map_or(u16::MAX, Subsystem::bit);
+    if on {
+        DEBUG.fetch_or(bits, Ordering::Relaxed);
+    } else {
+        DEBUG.fetch_and(!bits, Ordering::Relaxed);
+    }
+}
+
+/// 打开了调试的子系统
+pub fn debugging() -> Vec<Subsystem> {
+    let bits = DEBUG.load(Ordering::Relaxed);
+    Subsystem::ALL
+        .into_iter()
+        .filter(|sub| bits & sub.bit() != 0)
+        .collect()
+}
+
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        3 => Level::Error,
+        4 => Level::Warning,
+        5 => Level::Notice,
+        6 => Level::Info,
+        _ => Level::Debug,
+    }
+}
+
+/// 子系统在运行时才能确定时使用，例如收发的报文
 #[macro_export]
-macro_rules! log {
-    () => {{
-        println!("\r\x1b[2K")
-    }};
-    ($($arg:tt)*) => {{
-        println!("\r\x1b[2K{}", format!($($arg)*))
+macro_rules! log_at {
+    ($level:ident, $sub:expr; $($arg:tt)*) => {{
+        let sub: $crate::logging::Subsystem = $sub;
+        if $crate::logging::enabled($crate::logging::Level::$level, sub) {
+            $crate::logging::write($crate::logging::Level::$level, sub, format_args!($($arg)*));
+        }
     }};
 }
 
+/// 日志宏的第一个参数可以是子系统，例如 `log_debug!(Spf; "...")`，不写时为 General
+#[macro_export]
+macro_rules! log {
+    ($sub:ident; $($arg:tt)*) => { $crate::log_at!(Info, $crate::logging::Subsystem::$sub; $($arg)*) };
+    ($($arg:tt)*) => { $crate::log_at!(Info, $crate::logging::Subsystem::General; $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($sub:ident; $($arg:tt)*) => { $crate::log_at!(Debug, $crate::logging::Subsystem::$sub; $($arg)*) };
+    ($($arg:tt)*) => { $crate::log_at!(Debug, $crate::logging::Subsystem::General; $($arg)*) };
+}
+
 #[macro_export]
 macro_rules! log_warning {
-    ($($arg:tt)*) => {{
-        print!("\r\x1b[2K\x1b[33m");
-        print!($($arg)*);
-        print!("\x1b[39m");
-        println!()
-    }};
+    ($sub:ident; $($arg:tt)*) => { $crate::log_at!(Warning, $crate::logging::Subsystem::$sub; $($arg)*) };
+    ($($arg:tt)*) => { $crate::log_at!(Warning, $crate::logging::Subsystem::General; $($arg)*) };
 }
 
 #[macro_export]
 macro_rules! log_error {
-    ($($arg:tt)*) => {{
-        print!("\r\x1b[2K\x1b[31m");
-        print!($($arg)*);
-        print!("\x1b[39m");
-        println!()
-    }};
+    ($sub:ident; $($arg:tt)*) => { $crate::log_at!(Error, $crate::logging::Subsystem::$sub; $($arg)*) };
+    ($($arg:tt)*) => { $crate::log_at!(Error, $crate::logging::Subsystem::General; $($arg)*) };
 }
 
+/// 状态变化等值得注意的事件
 #[macro_export]
 macro_rules! log_success {
+    ($sub:ident; $($arg:tt)*) => { $crate::log_at!(Notice, $crate::logging::Subsystem::$sub; $($arg)*) };
+    ($($arg:tt)*) => { $crate::log_at!(Notice, $crate::logging::Subsystem::General; $($arg)*) };
+}
+
+/// 命令的输出，只打印到终端
+#[macro_export]
+macro_rules! echo {
+    () => {{
+        println!("\r\x1b[2K")
+    }};
     ($($arg:tt)*) => {{
-        print!("\r\x1b[2K\x1b[32m");
-        print!($($arg)*);
-        print!("\x1b[39m");
-        println!()
+        println!("\r\x1b[2K{}", format!($($arg)*))
     }};
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let dir = std::env::temp_dir().join(format!("ospfd-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ospfd.log");
+        let mut file = File::open(path.clone(), 100, 2).unwrap();
+        for i in 0..10 {
+            file.write(&Record {
+                time: SystemTime::now(),
+                level: Level::Info,
+                subsystem: Subsystem::Spf,
+                message: format_args!("message {}", i),
+            });
+        }
+        // 每条约 50 字节，写满三条轮转一次，只保留两个旧文件
+        assert!(file.rotated(2).exists() && !file.rotated(3).exists());
+        let rotated = fs::read_to_string(file.rotated(1)).unwrap();
+        assert!(rotated.contains("info    [spf] message 8") && !rotated.contains("message 5"));
+        assert!(fs::read_to_string(&path).unwrap().contains("message 9"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!enabled(Level::Debug, Subsystem::Hello));
+        set_debug(Some(Subsystem::Hello), true);
+        assert!(enabled(Level::Debug, Subsystem::Hello) && !enabled(Level::Debug, Subsystem::Dd));
+        set_debug(None, false);
+        assert!(debugging().is_empty());
+    }
+}
@@ -1,40 +1,75 @@
 mod area;
 mod capture;
 mod command;
+mod config;
 mod constant;
 mod daemon;
 mod database;
 mod flooding;
 mod gen_lsa;
+mod gr;
 mod handler;
 mod interface;
+mod json;
 mod logging;
+mod metrics;
 mod neighbor;
+mod netlink;
+mod opaque;
+mod prefix_list;
+mod route_map;
+mod router_info;
 mod sender;
+mod sr;
+mod statistics;
+mod stub_router;
+mod te;
 mod util;
 
-use std::{io::Write, net::Ipv4Addr, time::Duration};
+use std::{io::Write, time::Duration};
 
+use config::Config;
 use constant::BackboneArea;
-use daemon::Daemon;
 use database::ProtocolDB;
 use interface::{AInterface, Interface};
 use pnet::datalink::{self, NetworkInterface};
 
 #[tokio::main()]
 async fn main() {
+    // 读取配置文件（可选）
+    if let Some(path) = std::env::args().nth(1) {
+        match Config::load(&path) {
+            Ok(config) => Config::init(config),
+            Err(e) => panic!("failed to load config file {path}: {e}"),
+        }
+    }
+    logging::init(&Config::get().log);
+
     // 初始化 OSPF 数据库，插入 Backbone 区域
     ProtocolDB::get().await.insert_area(BackboneArea).await;
     
     // 筛选可用网络接口
-    let interfaces: Vec<_> = datalink::interfaces().iter().filter_map(start).collect();
+    let interfaces: Vec<_> = datalink::interfaces().iter().flat_map(start).collect();
     if interfaces.is_empty() {
         panic!("No interface is available");
     }
+    // 插入各接口所在的区域
+    for iface in interfaces.iter() {
+        let area_id = iface.lock().await.area_id;
+        ProtocolDB::get().await.insert_area(area_id).await;
+    }
     
     // 初始化数据库并启动接口
     ProtocolDB::init(&interfaces);
+    // 上次是平滑重启时进入重启状态，必须在接口启动前
+    gr::init().await;
+    stub_router::init();
     interfaces.iter().for_each(|i| Interface::start(i));
+    // 监听接口的增减和地址变化
+    tokio::spawn(netlink::watch());
+    if let Some(addr) = Config::get().metrics_listen {
+        tokio::spawn(metrics::serve(addr));
+    }
 
     log!("waiting to start...");
     tokio::time::sleep(Duration::from_secs(2)).await;
@@ -44,20 +79,27 @@ async fn main() {
 
     // 调用使用 Crossterm 实现的交互主循环
     // 此函数内部会启用原始模式并持续读取用户输入
-    command::main_loop();
+    // 命令处理中会同步等待异步任务，所以必须离开异步上下文运行
+    tokio::task::block_in_place(command::main_loop);
 }
 
-fn start(iface: &NetworkInterface) -> Option<AInterface> {
-    if iface.ips.iter().find(|i| i.ip() == Ipv4Addr::LOCALHOST).is_some() {
-        return None;
+fn start(iface: &NetworkInterface) -> Vec<AInterface> {
+    // 只有 127.0.0.0/8 地址的环回接口不需要启用
+    if iface.is_loopback() && interface::ipv4_addrs(iface).next().is_none() {
+        return vec![];
     }
-    if iface.ips.iter().find(|i| i.is_ipv4()).is_none() {
+    if interface::ipv4_addrs(iface).next().is_none() {
         log_warning!("The interface {} do NOT have an ipv4 address", iface.name);
-        return None;
+        return vec![];
+    }
+    let mut interfaces = vec![];
+    for ip in interface::instance_addrs(iface) {
+        // 按配置文件的 include/exclude 和 network 语句筛选
+        guard!(Some(area_id) = Config::get().select(&iface.name, &interface::area_ips(iface, ip)); continue);
+        match interface::Interface::create(iface, ip, area_id) {
+            Ok(interface) => interfaces.push(interface),
+            Err(e) => log_error!("failed to start interface {}: {}", iface.name, e),
+        }
     }
-    let interface = interface::Interface::from(&iface, BackboneArea);
-    let ospf_handler = handler::ospf_handler_maker(interface.clone());
-    let capture_daemon = capture::CaptureOspfDaemon::new(&iface, ospf_handler).unwrap();
-    tokio::spawn(capture_daemon.run_forever());
-    Some(interface)
+    interfaces
 }
@@ -0,0 +1,297 @@
+//! Prometheus 指标
+//!
+//! 配置了 `metrics listen` 时启动一个最简单的 HTTP 服务，`GET /metrics` 返回文本格式的指标：
+//! 接口和邻居状态、各区域各类型的 LSA 数、LSA 老化时间分布、SPF 计算、各类路径的路由数、
+//! 内核路由写入失败次数，以及协议统计中的报文和 LSA 计数。每次请求时重新采集，不缓存。
+
+use std::{
+    fmt::{self, Write as _},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use ospf_packet::lsa::{types, LsaHeader};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    area::Area,
+    database::{ProtocolDB, RoutingTableItemType, RoutingTablePathType},
+    log_debug, log_error, route_map,
+    statistics::{self, PACKET_NAMES},
+};
+
+/// LSA 老化时间直方图的桶（秒）
+const AGE_BUCKETS: [u16; 7] = [60, 300, 600, 900, 1800, 2700, 3600];
+
+/// 请求头的最大长度
+const MAX_REQUEST: usize = 8192;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 文本格式的指标
+#[derive(Default)]
+struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &dyn fmt::Display)], value: impl fmt::Display) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape(&value.to_string())))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {value}");
+    }
+}
+
+/// 标签值中的反斜杠、引号和换行需要转义
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// 监听并处理请求，直到进程退出
+pub async fn serve(addr: SocketAddr) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => return log_error!("failed to listen for metrics on {}: {}", addr, e),
+    };
+    log_debug!("metrics listening on {}", addr);
+    accept(listener).await
+}
+
+async fn accept(listener: TcpListener) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                log_error!("failed to accept metrics connection: {}", e);
+                continue;
+            }
+        };
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream).await {
+                log_debug!("metrics request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut buf)).await??;
+        if n == 0 || request.len() + n > MAX_REQUEST {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.lines().next().unwrap_or_default().split_ascii_whitespace();
+    let (method, path) = (words.next(), words.next().map(|p| p.split('?').next().unwrap()));
+    let (status, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render().await),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// 采集当前的所有指标
+///
+/// # Safety
+/// This function should be awaited when caller hasn't have any locks.
+pub async fn render() -> String {
+    let mut m = Exposition::default();
+
+    // 先取接口锁，释放后再取数据库锁
+    let mut interfaces = vec![];
+    let mut neighbors = vec![];
+    for iface in tokio::task::block_in_place(ProtocolDB::get_interfaces_impl) {
+        let name = iface.interface_name.clone();
+        for n in iface.neighbors.values() {
+            neighbors.push((name.clone(), n.router_id, n.ip_addr, n.state as u8));
+        }
+        interfaces.push((name, iface.area_id, iface.ip_addr, iface.state as u8, iface.cost));
+    }
+    m.family("ospf_interface_state", "gauge", "Interface state (0 Down .. 6 DR).");
+    for (name, area, ip, state, _) in interfaces.iter() {
+        m.sample("ospf_interface_state", &[("interface", name), ("area", area), ("address", ip)], state);
+    }
+    m.family("ospf_interface_cost", "gauge", "Interface output cost.");
+    for (name, area, ip, _, cost) in interfaces.iter() {
+        m.sample("ospf_interface_cost", &[("interface", name), ("area", area), ("address", ip)], cost);
+    }
+    m.family("ospf_neighbor_state", "gauge", "Neighbor state (0 Down .. 7 Full).");
+    for (name, router_id, ip, state) in neighbors.iter() {
+        let labels: [(&str, &dyn fmt::Display); 3] = [("interface", name), ("router_id", router_id), ("address", ip)];
+        m.sample("ospf_neighbor_state", &labels, state);
+    }
+
+    let db = ProtocolDB::get().await;
+    let mut lsdb: Vec<(&str, String, Vec<LsaHeader>)> = vec![];
+    let mut areas: Vec<_> = db.areas.values().collect();
+    areas.sort_by_key(|area| area.area_id);
+    for area in areas {
+        lsdb.push(("area", area.area_id.to_string(), area.get_all_area_lsa()));
+    }
+    let mut links: Vec<_> = db.links.values().collect();
+    links.sort_by_key(|link| link.ip_addr);
+    for link in links {
+        lsdb.push(("link", link.ip_addr.to_string(), link.get_all_lsa()));
+    }
+    let mut routes = [
+        RoutingTablePathType::AreaInternal,
+        RoutingTablePathType::AreaExternal,
+        RoutingTablePathType::AsExternalT1,
+        RoutingTablePathType::AsExternalT2,
+    ]
+    .map(|t| (t, 0));
+    for item in db.routing_table.get_routings() {
+        if item.dest_type == RoutingTableItemType::Network {
+            routes.iter_mut().filter(|(t, _)| *t == item.path_type).for_each(|(_, n)| *n += 1);
+        }
+    }
+    drop(db);
+    lsdb.push(("as", "0.0.0.0".to_string(), Area::get_all_as_lsa().await));
+
+    m.family("ospf_lsdb_lsas", "gauge", "LSAs in the database by flooding scope and LS type.");
+    for (scope, id, headers) in lsdb.iter() {
+        let mut ls_types: Vec<_> = headers.iter().map(|h| h.ls_type).collect();
+        ls_types.sort();
+        ls_types.dedup();
+        for ls_type in ls_types {
+            let count = headers.iter().filter(|h| h.ls_type == ls_type).count();
+            let labels: [(&str, &dyn fmt::Display); 3] =
+                [("scope", scope), ("id", id), ("type", &types::to_string(ls_type))];
+            m.sample("ospf_lsdb_lsas", &labels, count);
+        }
+    }
+    let ages: Vec<_> = lsdb.iter().flat_map(|(.., h)| h.iter().map(|h| h.ls_age)).collect();
+    m.family("ospf_lsa_age_seconds", "histogram", "Age of the LSAs in the database.");
+    for bucket in AGE_BUCKETS {
+        let count = ages.iter().filter(|age| **age <= bucket).count();
+        m.sample("ospf_lsa_age_seconds_bucket", &[("le", &bucket)], count);
+    }
+    m.sample("ospf_lsa_age_seconds_bucket", &[("le", &"+Inf")], ages.len());
+    m.sample("ospf_lsa_age_seconds_sum", &[], ages.iter().map(|a| *a as u64).sum::<u64>());
+    m.sample("ospf_lsa_age_seconds_count", &[], ages.len());
+
+    m.family("ospf_routes", "gauge", "Network routes in the routing table by path type.");
+    for (path_type, count) in routes {
+        m.sample("ospf_routes", &[("path_type", &route_map::path_type_name(path_type))], count);
+    }
+
+    let stat = statistics::get();
+    m.family("ospf_spf_runs_total", "counter", "Routing table calculations.");
+    m.sample("ospf_spf_runs_total", &[], stat.spf_runs);
+    m.family("ospf_spf_duration_seconds_total", "counter", "Time spent calculating the routing table.");
+    m.sample("ospf_spf_duration_seconds_total", &[], stat.spf_total.as_secs_f64());
+    m.family("ospf_spf_last_duration_seconds", "gauge", "Duration of the last routing table calculation.");
+    m.sample("ospf_spf_last_duration_seconds", &[], stat.spf_last.as_secs_f64());
+    m.family("ospf_fib_errors_total", "counter", "Failed kernel route installs and removals.");
+    m.sample("ospf_fib_errors_total", &[], stat.fib_errors);
+    for (name, help, value) in [
+        ("ospf_lsas_originated_total", "LSAs originated by this router.", stat.lsa_originated),
+        ("ospf_lsas_received_total", "LSAs received in link state updates.", stat.lsa_received),
+        ("ospf_lsas_installed_total", "Received LSAs installed in the database.", stat.lsa_installed),
+        ("ospf_lsas_flushed_total", "LSAs flushed by this router.", stat.lsa_flushed),
+    ] {
+        m.family(name, "counter", help);
+        m.sample(name, &[], value);
+    }
+    for (name, help, sent) in [
+        ("ospf_packets_sent_total", "Packets sent by interface and type.", true),
+        ("ospf_packets_received_total", "Packets received by interface and type.", false),
+    ] {
+        m.family(name, "counter", help);
+        for (iface, s) in stat.interfaces.iter() {
+            let counters = if sent { &s.packets.sent } else { &s.packets.received };
+            for (ty, n) in PACKET_NAMES.iter().zip(counters) {
+                m.sample(name, &[("interface", iface), ("type", ty)], n);
+            }
+        }
+    }
+    m.family("ospf_packets_discarded_total", "counter", "Packets discarded by interface and reason.");
+    for (iface, s) in stat.interfaces.iter() {
+        for (reason, n) in s.discarded.iter() {
+            m.sample("ospf_packets_discarded_total", &[("interface", iface), ("reason", &reason.name())], n);
+        }
+    }
+    m.family("ospf_neighbor_state_changes_total", "counter", "Neighbor state changes.");
+    for ((router_id, ip), s) in stat.neighbors.iter() {
+        let labels: [(&str, &dyn fmt::Display); 3] =
+            [("interface", &s.interface), ("router_id", router_id), ("address", ip)];
+        m.sample("ospf_neighbor_state_changes_total", &labels, s.state_changes);
+    }
+    m.out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn request(addr: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(accept(listener));
+
+        let response = request(addr, b"GET /metrics?x=1 HTTP/1.1\r\nHost: a\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\r\n\r\n# HELP ") && response.contains("\n# TYPE ospf_routes gauge\n"));
+        let response = request(addr, b"POST / HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        let response = request(addr, b"GET /other HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        // 超过 MAX_REQUEST 的请求头不再等待结束，按第一行立即回复
+        let mut long = b"GET /other HTTP/1.1\r\nX-Pad: ".to_vec();
+        long.resize(MAX_REQUEST + 1, b'a');
+        let response = tokio::time::timeout(READ_TIMEOUT / 2, request(addr, &long)).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test() {
+        let mut m = Exposition::default();
+        m.family("ospf_routes", "gauge", "Routes.");
+        m.sample("ospf_routes", &[("path_type", &"intra-area"), ("note", &"a\"b\\c\nd")], 3);
+        m.sample("ospf_spf_runs_total", &[], 1.5);
+        assert_eq!(
+            m.out,
+            "# HELP ospf_routes Routes.\n# TYPE ospf_routes gauge\n\
+             ospf_routes{path_type=\"intra-area\",note=\"a\\\"b\\\\c\\nd\"} 3\n\
+             ospf_spf_runs_total 1.5\n"
+        );
+    }
+}
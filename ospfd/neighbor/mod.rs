@@ -9,10 +9,17 @@ use std::{
     net::Ipv4Addr,
 };
 
-use ospf_packet::{lsa::LsaHeader, packet::DBDescription};
+use ospf_packet::{
+    lls::extended_options,
+    lsa::LsaHeader,
+    packet::DBDescription,
+};
 
 use crate::{
     database::LsaIndex,
+    json,
+    json::{Json, ToJson},
+    router_info,
     util::{hex2ip, AbortHandle},
 };
 
@@ -29,6 +36,10 @@ pub struct Neighbor {
     pub priority: u8,
     pub ip_addr: Ipv4Addr,
     pub option: u8,
+    /// 邻居在 LLS 中宣告的扩展选项
+    pub extended_options: u32,
+    /// 正在进行带外 LSDB 重新同步（RFC 4811），期间保持完全邻接
+    pub oob_resync: bool,
     pub dr: Ipv4Addr,
     pub bdr: Ipv4Addr,
     /// DD 包重传 （仅主机才能重传）
@@ -55,6 +66,8 @@ impl Neighbor {
             priority: 0,
             ip_addr,
             option: 0,
+            extended_options: 0,
+            oob_resync: false,
             dr: hex2ip(0),
             bdr: hex2ip(0),
             dd_rxmt: DdRxmt::None,
@@ -67,6 +80,7 @@ impl Neighbor {
 
     pub fn reset(&mut self) {
         self.dd_seq_num = 0;
+        self.oob_resync = false;
         self.dd_rxmt.reset();
         self.lsr_handle.abort();
         self.ls_retransmission_list.clear();
@@ -81,6 +95,16 @@ impl Neighbor {
     pub fn is_bdr(&self) -> bool {
         self.ip_addr == self.bdr
     }
+
+    /// 生成 LSA 时是否作为完全邻接，带外重新同步期间保持不变
+    pub fn is_adjacent(&self) -> bool {
+        self.state == NeighborState::Full || self.oob_resync
+    }
+
+    /// 邻居是否支持带外重新同步
+    pub fn resync_capable(&self) -> bool {
+        self.extended_options & extended_options::LR != 0
+    }
 }
 
 #[derive(Debug)]
@@ -141,6 +165,7 @@ pub struct NeighborSubStruct {
     pub priority: u8,
     pub ip_addr: Ipv4Addr,
     pub option: u8,
+    pub oob_resync: bool,
     pub dr: Ipv4Addr,
     pub bdr: Ipv4Addr,
 }
@@ -156,6 +181,7 @@ impl From<&Neighbor> for NeighborSubStruct {
             priority: value.priority,
             ip_addr: value.ip_addr,
             option: value.option,
+            oob_resync: value.oob_resync,
             dr: value.dr,
             bdr: value.bdr,
         }
@@ -174,9 +200,29 @@ impl NeighborSubStruct {
 
 impl std::fmt::Display for Neighbor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Router ID: {}\t\tAddress: {}", self.router_id, self.ip_addr)?;
+        writeln!(f, "Router ID: {}\t\tAddress: {}", router_info::name(self.router_id), self.ip_addr)?;
         writeln!(f, "  State: {:?}\tMode: {}\tPriority: {}", self.state, if self.master { "master" } else { "slave" }, self.priority)?;
         writeln!(f, "  DR: {}\t\tBDR: {}", self.dr, self.bdr)?;
+        if self.extended_options != 0 {
+            writeln!(f, "  LLS: {}{}", extended_options::to_string(self.extended_options), if self.oob_resync { "\tOOB resync in progress" } else { "" })?;
+        }
         Ok(())
     }
 }
+
+impl ToJson for Neighbor {
+    fn to_json(&self) -> Json {
+        json!({
+            "router_id": self.router_id,
+            "hostname": router_info::hostname(self.router_id),
+            "address": self.ip_addr,
+            "state": format!("{:?}", self.state),
+            "priority": self.priority,
+            "master": self.master,
+            "dr": self.dr,
+            "bdr": self.bdr,
+            "extended_options": self.extended_options,
+            "oob_resync": self.oob_resync,
+        })
+    }
+}
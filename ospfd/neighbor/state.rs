@@ -1,18 +1,18 @@
 use std::{marker::PhantomData, ops::DerefMut};
 
-use ospf_packet::packet::DBDescription;
+use ospf_packet::{
+    lsa::types,
+    packet::{options, DBDescription},
+};
 
 use super::{Neighbor, RefNeighbor};
 use crate::{
     database::ProtocolDB,
-    guard,
+    gr, guard,
     interface::{InterfaceEvent, NetType},
-    log_success, must,
+    log_debug, log_success, must, sr, statistics,
 };
 
-#[cfg(debug_assertions)]
-use crate::log;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum NeighborState {
     Down,
@@ -37,15 +37,16 @@ pub trait NeighborEvent: Send {
     async fn loading_done(&mut self);
     async fn adj_ok(&mut self);
     async fn seq_number_mismatch(&mut self);
+    async fn oob_resync(&mut self);
     async fn one_way_received(&mut self);
     async fn kill_nbr(&mut self);
     async fn inactivity_timer(&mut self);
     async fn ll_down(&mut self);
 }
 
-#[cfg(debug_assertions)]
 fn log_event(event: &str, neighbor: &Neighbor) {
-    log!(
+    log_debug!(
+        Nsm;
         "neighbor {}({:?}) recv event: {}",
         neighbor.router_id,
         neighbor.state,
@@ -55,7 +56,10 @@ fn log_event(event: &str, neighbor: &Neighbor) {
 
 fn log_state(old: NeighborState, neighbor: &Neighbor) {
     must!(old != neighbor.state);
+    let full = |state| state == NeighborState::Full;
+    statistics::neighbor_state_changed((neighbor.router_id, neighbor.ip_addr), full(old), full(neighbor.state));
     log_success!(
+        Nsm;
         "neighbor {}({})'s state changed: {:?} -> {:?}",
         neighbor.router_id,
         if neighbor.master { "master" } else { "slave" },
@@ -66,7 +70,6 @@ fn log_state(old: NeighborState, neighbor: &Neighbor) {
 
 impl NeighborEvent for RefNeighbor<'_> {
     async fn hello_receive(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("hello_receive", self.get_neighbor());
         let old = self.get_neighbor().state;
         if old <= NeighborState::Attempt {
@@ -77,14 +80,12 @@ impl NeighborEvent for RefNeighbor<'_> {
     }
 
     async fn start(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("start", self.get_neighbor());
         //todo with NBMA
         todo!("start NBMA")
     }
 
     async fn two_way_received(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("two_way_received", self.get_neighbor());
         let old = self.get_neighbor().state;
         must!(old == NeighborState::Init);
@@ -93,13 +94,15 @@ impl NeighborEvent for RefNeighbor<'_> {
         } else {
             NeighborState::TwoWay
         };
+        // 重启后第一次同步时，与支持的邻居进行带外重新同步，失败后按普通方式同步
+        let this = self.get_neighbor();
+        this.oob_resync = gr::restarting() && this.resync_capable() && this.state == NeighborState::ExStart;
         ex_start(self);
         log_state(old, self.get_neighbor());
     }
 
     async fn negotiation_done(&mut self) {
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("negotiation_done", this);
         must!(this.state == NeighborState::ExStart);
         summary_lsa(self).await;
@@ -109,12 +112,12 @@ impl NeighborEvent for RefNeighbor<'_> {
 
     async fn exchange_done(&mut self) {
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("exchange_done", this);
         must!(this.state == NeighborState::Exchange);
         this.dd_rxmt.reset();
         if this.ls_request_list.is_empty() {
             this.state = NeighborState::Full;
+            resync_done(this);
         } else {
             this.state = NeighborState::Loading;
             self.spawn_lsr_sender();
@@ -124,7 +127,6 @@ impl NeighborEvent for RefNeighbor<'_> {
 
     async fn bad_ls_req(&mut self) {
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("bad_ls_req", this);
         let old = this.state;
         must!(old >= NeighborState::Exchange);
@@ -136,15 +138,14 @@ impl NeighborEvent for RefNeighbor<'_> {
 
     async fn loading_done(&mut self) {
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("loading_done", this);
         must!(this.state == NeighborState::Loading);
         this.state = NeighborState::Full;
+        resync_done(this);
         log_state(NeighborState::Loading, this);
     }
 
     async fn adj_ok(&mut self) {
-        #[cfg(debug_assertions)]
         log_event("adj_ok", self.get_neighbor());
         let old = self.get_neighbor().state;
         if old == NeighborState::TwoWay {
@@ -154,18 +155,15 @@ impl NeighborEvent for RefNeighbor<'_> {
                 NeighborState::TwoWay
             };
             ex_start(self);
-        } else if old >= NeighborState::ExStart {
-            if !judge_connect(self).await {
-                self.get_neighbor().state = NeighborState::TwoWay;
-                self.get_neighbor().reset();
-            }
+        } else if old >= NeighborState::ExStart && !judge_connect(self).await {
+            self.get_neighbor().state = NeighborState::TwoWay;
+            self.get_neighbor().reset();
         }
         log_state(old, self.get_neighbor());
     }
 
     async fn seq_number_mismatch(&mut self) {
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("seq_number_mismatch", this);
         let old = this.state;
         must!(old >= NeighborState::Exchange);
@@ -175,10 +173,25 @@ impl NeighborEvent for RefNeighbor<'_> {
         log_state(old, self.get_neighbor());
     }
 
+    async fn oob_resync(&mut self) {
+        let this = self.get_neighbor();
+        log_event("oob_resync", this);
+        // 完全邻接的邻居请求带外重新同步，重新交换数据库但不拆除邻接
+        must!(this.state == NeighborState::Full);
+        this.reset();
+        this.oob_resync = true;
+        this.state = NeighborState::ExStart;
+        log_success!("neighbor {} starts out-of-band resynchronization", this.router_id);
+        ex_start(self);
+        log_state(NeighborState::Full, self.get_neighbor());
+    }
+
     async fn one_way_received(&mut self) {
+        let ip = self.get_interface().ip_addr;
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("one_way_received", this);
+        // 重启的邻居刚启动时还不认识自己，帮助期间保持邻接
+        must!(!gr::helping(ip, this.router_id));
         let old = this.state;
         must!(old >= NeighborState::TwoWay);
         this.reset();
@@ -188,9 +201,11 @@ impl NeighborEvent for RefNeighbor<'_> {
     }
 
     async fn kill_nbr(&mut self) {
+        let ip = self.get_interface().ip_addr;
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("kill_nbr", this);
+        gr::stop_helping((ip, this.router_id), "neighbor killed");
+        sr::release(ip, Some(this.router_id));
         let old = this.state;
         this.reset();
         this.inactive_timer.abort();
@@ -200,9 +215,10 @@ impl NeighborEvent for RefNeighbor<'_> {
     }
 
     async fn inactivity_timer(&mut self) {
+        let ip = self.get_interface().ip_addr;
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("inactivity_timer", this);
+        sr::release(ip, Some(this.router_id));
         let old = this.state;
         this.reset();
         this.state = NeighborState::Down;
@@ -211,9 +227,11 @@ impl NeighborEvent for RefNeighbor<'_> {
     }
 
     async fn ll_down(&mut self) {
+        let ip = self.get_interface().ip_addr;
         let this = self.get_neighbor();
-        #[cfg(debug_assertions)]
         log_event("ll_down", this);
+        gr::stop_helping((ip, this.router_id), "link down");
+        sr::release(ip, Some(this.router_id));
         let old = this.state;
         this.reset();
         this.inactive_timer.abort();
@@ -227,16 +245,21 @@ async fn reset_timer(this: &mut RefNeighbor<'_>) {
     let dead_interval = this.get_interface().dead_interval as u64;
     let iface = this.get_interface().me.clone();
     let this = this.get_neighbor();
-    let ip = this.ip_addr;
+    let (ip, router_id) = (this.ip_addr, this.router_id);
     this.inactive_timer.abort();
     this.inactive_timer = tokio::spawn(async move {
-        tokio::time::sleep(std::time::Duration::from_secs(dead_interval)).await;
-        guard!(Some(iface) = iface.upgrade());
-        let mut iface = iface.lock().await;
-        RefNeighbor::from(iface.deref_mut(), ip)
-            .unwrap()
-            .inactivity_timer()
-            .await;
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(dead_interval)).await;
+            guard!(Some(iface) = iface.upgrade());
+            let mut iface = iface.lock().await;
+            // 重启的邻居可能暂时不发 Hello，宽限期结束前不认为它失效
+            must!(!gr::helping(iface.ip_addr, router_id); continue);
+            RefNeighbor::from(iface.deref_mut(), ip)
+                .unwrap()
+                .inactivity_timer()
+                .await;
+            break;
+        }
     })
     .into();
 }
@@ -261,9 +284,10 @@ fn ex_start(this: &mut RefNeighbor<'_>) {
         .as_secs() as u16 as u32;
     neighbor.master = false;
     let packet = DBDescription {
-        interface_mtu: 0,
-        options: this.get_neighbor().option,
+        interface_mtu: this.get_interface().mtu,
+        options: this.get_neighbor().option | options::L,
         _zeros: PhantomData,
+        resync: this.get_neighbor().oob_resync as u8,
         init: 1,
         more: 1,
         master: 1,
@@ -273,13 +297,24 @@ fn ex_start(this: &mut RefNeighbor<'_>) {
     this.spawn_master_send_dd(packet);
 }
 
+fn resync_done(neighbor: &mut Neighbor) {
+    must!(neighbor.oob_resync);
+    neighbor.oob_resync = false;
+    log_success!("neighbor {} out-of-band resynchronization done", neighbor.router_id);
+}
+
 async fn summary_lsa(this: &mut RefNeighbor<'_>) {
-    let areas = &ProtocolDB::get().await.areas;
+    let db = ProtocolDB::get().await;
     guard! {
-        Some(area) = areas.get(&this.get_interface().area_id);
+        Some(area) = db.areas.get(&this.get_interface().area_id);
         error: "Area({}) not found in database!", this.get_interface().area_id
     };
-    this.get_neighbor()
-        .db_summary_list
-        .extend(area.get_all_lsa().await);
+    let mut lsa = area.get_all_lsa().await;
+    if let Some(link) = db.links.get(&this.get_interface().ip_addr) {
+        lsa.extend(link.get_all_lsa());
+    }
+    // 不支持不透明 LSA 的邻居不交换它们
+    let opaque = this.get_neighbor().option & options::O != 0;
+    lsa.retain(|header| opaque || !types::is_opaque(header.ls_type));
+    this.get_neighbor().db_summary_list.extend(lsa);
 }
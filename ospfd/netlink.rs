@@ -0,0 +1,167 @@
+//! 通过 rtnetlink 订阅接口和地址的变化
+
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use tokio::io::unix::AsyncFd;
+
+use crate::{interface, log_error, log_success, log_warning};
+
+pub struct NetlinkSocket(AsyncFd<OwnedFd>);
+
+impl NetlinkSocket {
+    /// 打开 NETLINK_ROUTE 套接字，订阅链路和 IPv4 地址的通知
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR) as u32;
+        if unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                std::ptr::from_ref(&addr) as *const libc::sockaddr,
+                std::mem::size_of_val(&addr) as u32,
+            )
+        } == -1
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(AsyncFd::new(fd)?))
+    }
+
+    /// 等待一批通知，返回其中的消息类型
+    pub async fn recv(&self) -> io::Result<Vec<u16>> {
+        let mut buf = vec![0u8; 16384];
+        loop {
+            let mut guard = self.0.readable().await?;
+            let n = match guard.try_io(|fd| {
+                let n = unsafe {
+                    libc::recv(
+                        fd.as_raw_fd(),
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len(),
+                        0,
+                    )
+                };
+                if n == -1 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            }) {
+                Ok(n) => n?,
+                Err(_would_block) => continue,
+            };
+            return Ok(message_types(&buf[..n]));
+        }
+    }
+}
+
+/// 解析 netlink 消息头，取出每条消息的类型
+fn message_types(mut buf: &[u8]) -> Vec<u16> {
+    const HEADER_LEN: usize = std::mem::size_of::<libc::nlmsghdr>();
+    let mut types = vec![];
+    while buf.len() >= HEADER_LEN {
+        let len = u32::from_ne_bytes(buf[0..4].try_into().unwrap()) as usize;
+        let ty = u16::from_ne_bytes(buf[4..6].try_into().unwrap());
+        if len < HEADER_LEN || len > buf.len() {
+            break;
+        }
+        types.push(ty);
+        // 消息按 4 字节对齐
+        buf = &buf[((len + 3) & !3).min(buf.len())..];
+    }
+    types
+}
+
+/// 套接字本身仍然可用的接收错误
+fn recoverable(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::ENOBUFS | libc::ENOMEM | libc::EINTR))
+}
+
+/// 监听接口变化：链路状态变化时通知各接口立即检查，地址和 MTU 变化时同步接口列表
+pub async fn watch() {
+    let socket = match NetlinkSocket::new() {
+        Ok(socket) => socket,
+        Err(e) => {
+            log_error!("failed to subscribe netlink events: {}", e);
+            return;
+        }
+    };
+    log_success!("watching interface changes via netlink");
+    loop {
+        let changed = match socket.recv().await {
+            Ok(types) => types.iter().any(|ty| {
+                matches!(
+                    *ty,
+                    libc::RTM_NEWLINK | libc::RTM_DELLINK | libc::RTM_NEWADDR | libc::RTM_DELADDR
+                )
+            }),
+            // 接收缓冲区溢出等错误会丢失通知，重新同步整个接口列表
+            Err(e) if recoverable(&e) => {
+                log_warning!("netlink events may be lost ({}), resyncing interfaces", e);
+                true
+            }
+            Err(e) => {
+                log_error!("failed to receive netlink events: {}", e);
+                return;
+            }
+        };
+        if changed {
+            interface::sync().await;
+            interface::notify_changed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 构造一条 netlink 消息，nlmsg_len 为 len，后面跟着 payload 并补齐到 4 字节
+    fn message(len: usize, ty: u16, payload: usize) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend_from_slice(&(len as u32).to_ne_bytes());
+        buf.extend_from_slice(&ty.to_ne_bytes());
+        buf.extend_from_slice(&[0; 10]);
+        buf.resize(buf.len() + ((payload + 3) & !3), 0);
+        buf
+    }
+
+    #[test]
+    fn test() {
+        // 一次收到多条消息，最后一条消息不需要补齐
+        let mut buf = message(21, libc::RTM_NEWLINK, 5);
+        buf.extend(message(16, libc::RTM_DELADDR, 0));
+        let mut last = message(18, libc::RTM_NEWADDR, 2);
+        last.truncate(18);
+        buf.extend(last);
+        assert_eq!(message_types(&buf), [libc::RTM_NEWLINK, libc::RTM_DELADDR, libc::RTM_NEWADDR]);
+        // 不完整的消息头
+        assert_eq!(message_types(&buf[..10]), []);
+        let mut buf = message(16, libc::RTM_NEWLINK, 0);
+        buf.extend_from_slice(&[0; 10]);
+        assert_eq!(message_types(&buf), [libc::RTM_NEWLINK]);
+        // nlmsg_len 比消息头还短
+        let mut buf = message(16, libc::RTM_NEWLINK, 0);
+        buf.extend(message(8, libc::RTM_DELLINK, 0));
+        buf.extend(message(16, libc::RTM_NEWADDR, 0));
+        assert_eq!(message_types(&buf), [libc::RTM_NEWLINK]);
+        // nlmsg_len 超出收到的数据
+        let mut buf = message(16, libc::RTM_NEWLINK, 0);
+        buf.extend(message(100, libc::RTM_DELLINK, 4));
+        assert_eq!(message_types(&buf), [libc::RTM_NEWLINK]);
+    }
+}
@@ -0,0 +1,210 @@
+//! 供应用生成和撤销自己的不透明 LSA（RFC 5250）。
+//! 已生成的 LSA 会被记录下来，接口检查时重新生成，从而按时刷新，也能出现在新启用的接口上。
+
+use std::{collections::BTreeMap, net::Ipv4Addr, sync::Mutex};
+
+use lazy_static::lazy_static;
+use ospf_packet::lsa::{
+    types::{OPAQUE_AREA_LSA, OPAQUE_AS_LSA, OPAQUE_LINK_LSA},
+    Lsa, LsaIndex,
+};
+
+use crate::{
+    database::InterfacesGuard,
+    gen_lsa,
+    interface::Interface,
+    must, router_info, sr, te,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OpaqueScope {
+    /// 类型 9，只在指定地址的接口上洪泛
+    Link(Ipv4Addr),
+    /// 类型 10，在指定区域内洪泛
+    Area(Ipv4Addr),
+    /// 类型 11，在整个自治系统内洪泛（目前的应用都不使用）
+    #[allow(unused)]
+    As,
+}
+
+impl OpaqueScope {
+    pub fn ls_type(&self) -> u8 {
+        match self {
+            OpaqueScope::Link(_) => OPAQUE_LINK_LSA,
+            OpaqueScope::Area(_) => OPAQUE_AREA_LSA,
+            OpaqueScope::As => OPAQUE_AS_LSA,
+        }
+    }
+
+    fn contains(&self, iface: &Interface) -> bool {
+        match self {
+            OpaqueScope::Link(ip) => iface.ip_addr == *ip,
+            OpaqueScope::Area(area_id) => iface.area_id == *area_id,
+            OpaqueScope::As => iface.external_routing,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpaqueError {
+    #[error("opaque id {0} is out of range (24 bits)")]
+    BadId(u32),
+    #[error("interface is not in scope {0:?}")]
+    NotInScope(OpaqueScope),
+}
+
+type OpaqueKey = (OpaqueScope, u8, u32);
+
+lazy_static! {
+    /// (范围, 不透明类型, 不透明标识) -> 内容
+    static ref ORIGINATED: Mutex<BTreeMap<OpaqueKey, Vec<u8>>> = Mutex::new(BTreeMap::new());
+}
+
+fn check(interfaces: &InterfacesGuard, scope: &OpaqueScope, opaque_id: u32) -> Result<(), OpaqueError> {
+    must!(opaque_id < 1 << 24; ret: Err(OpaqueError::BadId(opaque_id)));
+    must!(scope.contains(&interfaces.me); ret: Err(OpaqueError::NotInScope(scope.clone())));
+    Ok(())
+}
+
+/// 通过 interfaces.me 生成（或更新）一个不透明 LSA 并洪泛，me 必须在 scope 内
+pub async fn originate(
+    interfaces: &mut InterfacesGuard,
+    scope: OpaqueScope,
+    opaque_type: u8,
+    opaque_id: u32,
+    data: Vec<u8>,
+) -> Result<(), OpaqueError> {
+    check(interfaces, &scope, opaque_id)?;
+    let ls_type = scope.ls_type();
+    ORIGINATED.lock().unwrap().insert((scope, opaque_type, opaque_id), data.clone());
+    gen_lsa::gen_opaque_lsa(interfaces, ls_type, opaque_type, opaque_id, data).await;
+    Ok(())
+}
+
+/// 通过 interfaces.me 撤销一个自己生成的不透明 LSA
+pub async fn withdraw(
+    interfaces: &mut InterfacesGuard,
+    scope: OpaqueScope,
+    opaque_type: u8,
+    opaque_id: u32,
+) -> Result<(), OpaqueError> {
+    check(interfaces, &scope, opaque_id)?;
+    let ls_type = scope.ls_type();
+    ORIGINATED.lock().unwrap().remove(&(scope, opaque_type, opaque_id));
+    gen_lsa::flush_opaque_lsa(interfaces, ls_type, opaque_type, opaque_id).await;
+    Ok(())
+}
+
+/// 重新生成 interfaces.me 所在范围内已记录的不透明 LSA
+pub async fn refresh(interfaces: &mut InterfacesGuard) {
+    let originated: Vec<_> = ORIGINATED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((scope, ..), _)| scope.contains(&interfaces.me))
+        .map(|(key, data)| (key.clone(), data.clone()))
+        .collect();
+    for ((scope, opaque_type, opaque_id), data) in originated {
+        gen_lsa::gen_opaque_lsa(interfaces, scope.ls_type(), opaque_type, opaque_id, data).await;
+    }
+}
+
+/// 区域数据库加入 LSA 时调用，交给关心该 LSA 的应用处理
+pub fn on_insert(area_id: Ipv4Addr, lsa: &Lsa) {
+    te::update(area_id, lsa);
+    router_info::update(area_id, lsa);
+    sr::update(area_id, lsa);
+}
+
+/// 区域数据库删除 LSA 时调用
+pub fn on_remove(area_id: Ipv4Addr, key: LsaIndex) {
+    te::remove(area_id, key);
+    router_info::remove(area_id, key);
+    sr::remove(area_id, key);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        area::Area,
+        database::{InterfaceGuard, ProtocolDB},
+        guard,
+        neighbor::{Neighbor, NeighborState},
+    };
+    use ospf_packet::{
+        lsa::{LsaHeader, LsaIndex},
+        packet::options,
+    };
+    use pnet::datalink;
+
+    /// 在环回接口上创建一个带有完全邻居的接口
+    async fn iface(ip: &str, area_id: &str, external_routing: bool) -> Option<InterfaceGuard> {
+        let lo = datalink::interfaces().into_iter().find(|i| i.is_loopback())?;
+        let iface = Interface::from(&lo, ip.parse().unwrap(), area_id.parse().unwrap()).ok()?;
+        let mut iface = iface.lock_owned().await;
+        iface.external_routing = external_routing;
+        let peer = Ipv4Addr::from(u32::from(iface.ip_addr) + 100);
+        let mut neighbor = Neighbor::new(peer, peer);
+        neighbor.state = NeighborState::Full;
+        neighbor.option = options::O;
+        iface.neighbors.insert(neighbor.ip_addr, neighbor);
+        Some(iface)
+    }
+
+    fn key(ls_type: u8, opaque_id: u32) -> LsaIndex {
+        LsaIndex::new(ls_type, LsaHeader::opaque_id(218, opaque_id), ProtocolDB::get_router_id())
+    }
+
+    /// 依次为 a、b、c 的邻居是否需要重传 key
+    fn flooded(interfaces: &InterfacesGuard, key: LsaIndex) -> Vec<bool> {
+        let mut result: Vec<_> = interfaces
+            .iter()
+            .map(|i| (i.ip_addr, i.neighbors.values().all(|n| n.ls_retransmission_list.contains(&key))))
+            .collect();
+        result.sort();
+        result.into_iter().map(|(_, b)| b).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test() {
+        // a、b 在区域 0.0.0.34 中，b 不接收外部路由；c 在区域 0.0.0.35 中。没有原始套接字时跳过
+        guard!(Some(a) = iface("127.0.34.1/8", "0.0.0.34", true).await);
+        guard!(Some(b) = iface("127.0.34.2/8", "0.0.0.34", false).await);
+        guard!(Some(c) = iface("127.0.34.3/8", "0.0.0.35", true).await);
+        let me = a.ip_addr;
+        let mut db = ProtocolDB::get().await;
+        db.insert_area(a.area_id).await;
+        db.insert_area(c.area_id).await;
+        drop(db);
+        let mut interfaces: InterfacesGuard = vec![a, b, c].into();
+
+        originate(&mut interfaces, OpaqueScope::Link(me), 218, 1, vec![1]).await.unwrap();
+        let key_link = key(OPAQUE_LINK_LSA, 1);
+        assert!(ProtocolDB::get().await.links[&me].get_lsa(key_link).await.is_some());
+        assert_eq!(flooded(&interfaces, key_link), [true, false, false]);
+
+        let scope = OpaqueScope::Area(interfaces.me.area_id);
+        originate(&mut interfaces, scope, 218, 2, vec![2]).await.unwrap();
+        let key_area = key(OPAQUE_AREA_LSA, 2);
+        assert!(ProtocolDB::get().await.get_lsa(&interfaces.me, key_area).await.is_some());
+        assert_eq!(flooded(&interfaces, key_area), [true, true, false]);
+
+        originate(&mut interfaces, OpaqueScope::As, 218, 3, vec![3]).await.unwrap();
+        let key_as = key(OPAQUE_AS_LSA, 3);
+        assert!(Area::get_as_lsa(key_as).await.is_some());
+        assert_eq!(flooded(&interfaces, key_as), [true, false, true]);
+
+        // 撤销后提前老化，并且不再记录
+        withdraw(&mut interfaces, OpaqueScope::Link(me), 218, 1).await.unwrap();
+        let (lsa, ..) = ProtocolDB::get().await.links[&me].get_lsa(key_link).await.unwrap();
+        assert_eq!(lsa.header.ls_age, crate::constant::LsaMaxAge);
+        assert!(!ORIGINATED.lock().unwrap().contains_key(&(OpaqueScope::Link(me), 218, 1)));
+
+        let other = OpaqueScope::Area("0.0.0.35".parse().unwrap());
+        let result = originate(&mut interfaces, other, 218, 4, vec![]).await;
+        assert!(matches!(result, Err(OpaqueError::NotInScope(_))));
+        let result = originate(&mut interfaces, OpaqueScope::As, 218, 1 << 24, vec![]).await;
+        assert!(matches!(result, Err(OpaqueError::BadId(_))));
+    }
+}
@@ -0,0 +1,137 @@
+//! 前缀列表
+//!
+//! 条目按序号从小到大匹配，第一条匹配的条目决定允许或拒绝，都不匹配时拒绝。
+//! 没有 ge/le 时只匹配前缀长度相同的网段，否则匹配长度在 [ge, le] 内、落在前缀中的网段。
+
+use std::fmt;
+
+use pnet::ipnetwork::Ipv4Network;
+
+use crate::{
+    config::Config,
+    json,
+    json::{Json, ToJson},
+    must,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixListEntry {
+    pub seq: u32,
+    pub permit: bool,
+    pub prefix: Ipv4Network,
+    /// 最小前缀长度，不配置时为前缀本身的长度
+    pub ge: Option<u8>,
+    /// 最大前缀长度，不配置时为 32
+    pub le: Option<u8>,
+}
+
+impl PrefixListEntry {
+    pub fn matches(&self, net: Ipv4Network) -> bool {
+        let (len, plen) = (net.prefix(), self.prefix.prefix());
+        must!(len >= plen && self.prefix.contains(net.network()); ret: false);
+        match (self.ge, self.le) {
+            (None, None) => len == plen,
+            (ge, le) => (ge.unwrap_or(plen)..=le.unwrap_or(32)).contains(&len),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrefixList {
+    entries: Vec<PrefixListEntry>,
+}
+
+impl PrefixList {
+    /// 不写序号时使用的序号：比最大的序号大 5
+    pub fn next_seq(&self) -> u32 {
+        self.entries.last().map_or(5, |e| e.seq + 5)
+    }
+
+    /// 加入条目，序号相同时替换原来的条目
+    pub fn insert(&mut self, entry: PrefixListEntry) {
+        self.entries.retain(|e| e.seq != entry.seq);
+        self.entries.push(entry);
+        self.entries.sort_by_key(|e| e.seq);
+    }
+
+    pub fn permits(&self, net: Ipv4Network) -> bool {
+        self.entries
+            .iter()
+            .find(|e| e.matches(net))
+            .is_some_and(|e| e.permit)
+    }
+}
+
+impl fmt::Display for PrefixListEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = if self.permit { "permit" } else { "deny" };
+        write!(f, "seq {} {} {}", self.seq, action, self.prefix)?;
+        if let Some(ge) = self.ge {
+            write!(f, " ge {}", ge)?;
+        }
+        if let Some(le) = self.le {
+            write!(f, " le {}", le)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PrefixList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "  {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for PrefixListEntry {
+    fn to_json(&self) -> Json {
+        json!({
+            "seq": self.seq,
+            "action": if self.permit { "permit" } else { "deny" },
+            "prefix": self.prefix,
+            "ge": self.ge,
+            "le": self.le,
+        })
+    }
+}
+
+impl ToJson for PrefixList {
+    fn to_json(&self) -> Json {
+        self.entries.iter().collect()
+    }
+}
+
+/// 用配置的前缀列表检查网段，引用了不存在的前缀列表时允许所有网段
+pub fn permits(name: &str, net: Ipv4Network) -> bool {
+    Config::get()
+        .prefix_lists
+        .get(name)
+        .is_none_or(|list| list.permits(net))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let net = |s: &str| s.parse::<Ipv4Network>().unwrap();
+        let entry = |seq, permit, prefix, ge, le| PrefixListEntry { seq, permit, prefix: net(prefix), ge, le };
+        let mut list = PrefixList::default();
+        list.insert(entry(10, false, "10.1.0.0/16", None, Some(32)));
+        list.insert(entry(20, true, "10.0.0.0/8", Some(16), Some(24)));
+        list.insert(entry(5, true, "192.168.0.0/16", None, None));
+        assert_eq!(list.next_seq(), 25);
+        assert!(list.permits(net("192.168.0.0/16")));
+        assert!(!list.permits(net("192.168.1.0/24")));
+        assert!(!list.permits(net("10.1.2.0/24")));
+        assert!(list.permits(net("10.2.0.0/16")));
+        assert!(list.permits(net("10.2.3.0/24")));
+        assert!(!list.permits(net("10.2.3.4/32")));
+        assert!(!list.permits(net("10.0.0.0/8")));
+        list.insert(entry(10, true, "10.1.0.0/16", None, Some(32)));
+        assert!(list.permits(net("10.1.2.0/24")));
+    }
+}
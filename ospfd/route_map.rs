@@ -0,0 +1,301 @@
+//! 路由策略（route-map）
+//!
+//! 子句按序号从小到大检查，所有 match 条件都满足的第一条子句生效：permit 子句执行其中的 set 后接受路由，
+//! deny 子句拒绝路由；没有子句生效时拒绝。引用了不存在的策略时接受所有路由，不做修改。
+//! 引入外部路由时用策略过滤和修改路由；配置了 fib-filter 时，被拒绝的路由照常计算，但不写入内核。
+
+use std::{fmt, net::Ipv4Addr};
+
+use pnet::ipnetwork::Ipv4Network;
+
+use crate::{
+    config::{Config, ExternalRoute},
+    database::{RoutingTableItem, RoutingTablePathType},
+    guard, json,
+    json::{Json, ToJson},
+    must, prefix_list,
+    util::ip2hex,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Match {
+    PrefixList(String),
+    Tag(u32),
+    PathType(RoutingTablePathType),
+    Area(Ipv4Addr),
+    NextHop(Ipv4Addr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Set {
+    Metric(u32),
+    MetricType(u8),
+    Tag(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub seq: u32,
+    pub permit: bool,
+    pub matches: Vec<Match>,
+    pub sets: Vec<Set>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteMap {
+    clauses: Vec<Clause>,
+}
+
+/// 策略检查和修改的路由属性，引入的外部路由没有路径类型、区域和下一跳
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub network: Ipv4Network,
+    pub metric: u32,
+    /// 外部度量类型：1 或 2
+    pub metric_type: u8,
+    pub tag: u32,
+    pub path_type: Option<RoutingTablePathType>,
+    pub area_id: Option<Ipv4Addr>,
+    pub next_hop: Option<Ipv4Addr>,
+}
+
+impl Match {
+    fn matches(&self, route: &Route) -> bool {
+        match self {
+            Match::PrefixList(name) => prefix_list::permits(name, route.network),
+            Match::Tag(tag) => route.tag == *tag,
+            Match::PathType(path_type) => route.path_type == Some(*path_type),
+            Match::Area(area_id) => route.area_id == Some(*area_id),
+            Match::NextHop(ip) => route.next_hop == Some(*ip),
+        }
+    }
+}
+
+impl RouteMap {
+    /// 取得序号对应的子句，没有时加入一条空的 permit 子句
+    pub fn clause(&mut self, seq: u32) -> &mut Clause {
+        let idx = match self.clauses.binary_search_by_key(&seq, |c| c.seq) {
+            Ok(idx) => idx,
+            Err(idx) => {
+                let clause = Clause {
+                    seq,
+                    permit: true,
+                    matches: vec![],
+                    sets: vec![],
+                };
+                self.clauses.insert(idx, clause);
+                idx
+            }
+        };
+        &mut self.clauses[idx]
+    }
+
+    /// 对路由应用策略，拒绝时返回 None
+    pub fn apply(&self, mut route: Route) -> Option<Route> {
+        let clause = self
+            .clauses
+            .iter()
+            .find(|c| c.matches.iter().all(|m| m.matches(&route)))?;
+        must!(clause.permit; ret: None);
+        for set in clause.sets.iter() {
+            match *set {
+                Set::Metric(metric) => route.metric = metric,
+                Set::MetricType(metric_type) => route.metric_type = metric_type,
+                Set::Tag(tag) => route.tag = tag,
+            }
+        }
+        Some(route)
+    }
+}
+
+/// 按名称应用配置的策略
+pub fn apply(name: &str, route: Route) -> Option<Route> {
+    match Config::get().route_maps.get(name) {
+        Some(map) => map.apply(route),
+        None => Some(route),
+    }
+}
+
+/// 引入配置的外部路由，配置了策略时过滤和修改路由
+pub fn redistribute() -> Vec<ExternalRoute> {
+    let config = Config::get();
+    let routes = config.redistribute.iter().copied();
+    guard!(Some(ref name) = config.redistribute_route_map; ret: routes.collect());
+    routes
+        .filter_map(|r| apply(name, r.into()))
+        .map(|r| ExternalRoute {
+            network: r.network,
+            metric: r.metric,
+            metric_type: r.metric_type,
+            tag: r.tag,
+        })
+        .collect()
+}
+
+/// 计算出的路由是否写入内核
+pub fn fib_permits(item: &RoutingTableItem) -> bool {
+    Config::get()
+        .fib_filter
+        .as_deref()
+        .is_none_or(|name| apply(name, item.into()).is_some())
+}
+
+impl From<ExternalRoute> for Route {
+    fn from(value: ExternalRoute) -> Self {
+        Self {
+            network: value.network,
+            metric: value.metric,
+            metric_type: value.metric_type,
+            tag: value.tag,
+            path_type: None,
+            area_id: None,
+            next_hop: None,
+        }
+    }
+}
+
+impl From<&RoutingTableItem> for Route {
+    fn from(value: &RoutingTableItem) -> Self {
+        let prefix = ip2hex(value.addr_mask).leading_ones() as u8;
+        let t2 = value.path_type == RoutingTablePathType::AsExternalT2;
+        Self {
+            network: Ipv4Network::new(value.dest_id, prefix).unwrap(),
+            metric: if t2 { value.cost_t2 } else { value.cost },
+            metric_type: if t2 { 2 } else { 1 },
+            tag: value.tag,
+            path_type: Some(value.path_type),
+            area_id: Some(value.area_id),
+            next_hop: Some(value.next_hop),
+        }
+    }
+}
+
+/// 配置中使用的路径类型名称
+pub fn parse_path_type(s: &str) -> Option<RoutingTablePathType> {
+    use RoutingTablePathType::*;
+    match s {
+        "intra-area" => Some(AreaInternal),
+        "inter-area" => Some(AreaExternal),
+        "external-1" => Some(AsExternalT1),
+        "external-2" => Some(AsExternalT2),
+        _ => None,
+    }
+}
+
+pub fn path_type_name(path_type: RoutingTablePathType) -> &'static str {
+    use RoutingTablePathType::*;
+    match path_type {
+        AreaInternal => "intra-area",
+        AreaExternal => "inter-area",
+        AsExternalT1 => "external-1",
+        AsExternalT2 => "external-2",
+    }
+}
+
+impl fmt::Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Match::PrefixList(name) => write!(f, "match prefix-list {}", name),
+            Match::Tag(tag) => write!(f, "match tag {}", tag),
+            Match::PathType(path_type) => write!(f, "match path-type {}", path_type_name(*path_type)),
+            Match::Area(area_id) => write!(f, "match area {}", area_id),
+            Match::NextHop(ip) => write!(f, "match next-hop {}", ip),
+        }
+    }
+}
+
+impl fmt::Display for Set {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Set::Metric(metric) => write!(f, "set metric {}", metric),
+            Set::MetricType(metric_type) => write!(f, "set metric-type {}", metric_type),
+            Set::Tag(tag) => write!(f, "set tag {}", tag),
+        }
+    }
+}
+
+impl fmt::Display for RouteMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for clause in self.clauses.iter() {
+            let action = if clause.permit { "permit" } else { "deny" };
+            writeln!(f, "  {} {}", clause.seq, action)?;
+            for m in clause.matches.iter() {
+                writeln!(f, "    {}", m)?;
+            }
+            for s in clause.sets.iter() {
+                writeln!(f, "    {}", s)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for Match {
+    fn to_json(&self) -> Json {
+        match self {
+            Match::PrefixList(name) => json!({ "prefix_list": name }),
+            Match::Tag(tag) => json!({ "tag": *tag }),
+            Match::PathType(path_type) => json!({ "path_type": path_type_name(*path_type) }),
+            Match::Area(area_id) => json!({ "area": *area_id }),
+            Match::NextHop(ip) => json!({ "next_hop": *ip }),
+        }
+    }
+}
+
+impl ToJson for Set {
+    fn to_json(&self) -> Json {
+        match self {
+            Set::Metric(metric) => json!({ "metric": *metric }),
+            Set::MetricType(metric_type) => json!({ "metric_type": *metric_type }),
+            Set::Tag(tag) => json!({ "tag": *tag }),
+        }
+    }
+}
+
+impl ToJson for RouteMap {
+    fn to_json(&self) -> Json {
+        self.clauses
+            .iter()
+            .map(|clause| {
+                json!({
+                    "seq": clause.seq,
+                    "action": if clause.permit { "permit" } else { "deny" },
+                    "match": clause.matches.iter().collect::<Json>(),
+                    "set": clause.sets.iter().collect::<Json>(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let route = |network: &str, tag| Route {
+            network: network.parse().unwrap(),
+            metric: 20,
+            metric_type: 2,
+            tag,
+            path_type: None,
+            area_id: None,
+            next_hop: None,
+        };
+        let mut map = RouteMap::default();
+        map.clause(20).matches.push(Match::Tag(100));
+        map.clause(20).sets.extend([Set::Metric(50), Set::MetricType(1)]);
+        map.clause(10).permit = false;
+        map.clause(10).matches.push(Match::Tag(7));
+        map.clause(30).matches.push(Match::PathType(RoutingTablePathType::AreaInternal));
+        assert_eq!(map.apply(route("10.0.0.0/8", 7)), None);
+        let r = map.apply(route("10.0.0.0/8", 100)).unwrap();
+        assert_eq!((r.metric, r.metric_type, r.tag), (50, 1, 100));
+        // 引入的外部路由没有路径类型，最后一条子句不匹配
+        assert_eq!(map.apply(route("10.0.0.0/8", 0)), None);
+        let mut r = route("10.0.0.0/8", 0);
+        r.path_type = Some(RoutingTablePathType::AreaInternal);
+        assert_eq!(map.apply(r), Some(r));
+    }
+}
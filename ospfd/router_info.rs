@@ -0,0 +1,104 @@
+//! 路由器信息 LSA（RFC 7770）：宣告本路由器的能力、主机名（RFC 5642）和分段路由的标签块，
+//! 并从收到的 RI LSA 中学习其他路由器的主机名，用于显示。
+
+use std::{cell::Cell, collections::BTreeMap, net::Ipv4Addr, sync::Mutex};
+
+use ospf_packet::{
+    lsa::{types::OPAQUE_AREA_LSA, Lsa, LsaData, LsaIndex},
+    ri::{capabilities, RouterInfo, RI_OPAQUE_TYPE},
+    sr::algorithms,
+    ToBytes, TryFromBuf,
+};
+
+use crate::{
+    config::Config,
+    constant::LsaMaxAge,
+    database::InterfacesGuard,
+    guard, log_warning, must,
+    opaque::{self, OpaqueError, OpaqueScope},
+    sr,
+};
+
+/// (区域, 路由器) -> 主机名
+static HOSTNAMES: Mutex<BTreeMap<(Ipv4Addr, Ipv4Addr), String>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    /// 为 true 时显示原始的路由器标识
+    static RAW: Cell<bool> = const { Cell::new(false) };
+}
+
+fn is_ri_lsa(key: LsaIndex) -> bool {
+    key.ls_type == OPAQUE_AREA_LSA && key.ls_id.octets()[0] == RI_OPAQUE_TYPE
+}
+
+/// 区域数据库加入 LSA 时调用
+pub fn update(area_id: Ipv4Addr, lsa: &Lsa) {
+    must!(is_ri_lsa(lsa.header.into()));
+    if lsa.header.ls_age == LsaMaxAge {
+        return remove(area_id, lsa.header.into());
+    }
+    guard!(LsaData::Opaque(ref data) = lsa.data);
+    let info = match RouterInfo::try_from_slice(&data.data) {
+        Ok(info) => info,
+        Err(e) => return log_warning!("bad ri lsa from {}: {}", lsa.header.advertising_router, e),
+    };
+    let key = (area_id, lsa.header.advertising_router);
+    let mut names = HOSTNAMES.lock().unwrap();
+    match info.hostname {
+        Some(name) => names.insert(key, name),
+        None => names.remove(&key),
+    };
+}
+
+/// 区域数据库删除 LSA 时调用
+pub fn remove(area_id: Ipv4Addr, key: LsaIndex) {
+    must!(is_ri_lsa(key));
+    HOSTNAMES.lock().unwrap().remove(&(area_id, key.ad_router));
+}
+
+pub fn hostname(router_id: Ipv4Addr) -> Option<String> {
+    let names = HOSTNAMES.lock().unwrap();
+    names
+        .iter()
+        .find(|((_, id), _)| *id == router_id)
+        .map(|(_, name)| name.clone())
+}
+
+/// 显示用的路由器名称：学到主机名时显示主机名，否则显示路由器标识
+pub fn name(router_id: Ipv4Addr) -> String {
+    if RAW.get() {
+        return router_id.to_string();
+    }
+    hostname(router_id).unwrap_or_else(|| router_id.to_string())
+}
+
+/// 在 f 中显示原始的路由器标识
+pub fn raw<T>(f: impl FnOnce() -> T) -> T {
+    RAW.set(true);
+    let result = f();
+    RAW.set(false);
+    result
+}
+
+/// 生成 interfaces.me 所在区域的 RI LSA
+pub async fn gen_ri_lsa(interfaces: &mut InterfacesGuard) -> Result<(), OpaqueError> {
+    let mut cap = capabilities::GRACEFUL_RESTART | capabilities::STUB_ROUTER;
+    if Config::get().graceful_restart.helper {
+        cap |= capabilities::GRACEFUL_RESTART_HELPER;
+    }
+    if interfaces.iter().any(|i| i.te.is_some()) {
+        cap |= capabilities::TRAFFIC_ENGINEERING;
+    }
+    let sr = Config::get().segment_routing;
+    let info = RouterInfo {
+        capabilities: Some(cap),
+        hostname: Config::get().hostname.clone(),
+        sr_algorithms: sr.map(|_| vec![algorithms::SPF]),
+        srgb: sr.map(|sr| sr::range(sr.global_block)),
+        srlb: sr.map(|sr| sr::range(sr.local_block)),
+        others: vec![],
+    };
+    let data = info.to_bytes().to_vec();
+    let scope = OpaqueScope::Area(interfaces.me.area_id);
+    opaque::originate(interfaces, scope, RI_OPAQUE_TYPE, 0, data).await
+}
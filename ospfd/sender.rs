@@ -1,9 +1,13 @@
 use std::net::{IpAddr, Ipv4Addr};
 
-use ospf_packet::{packet::OspfSubPacket, MutableOspfPacket, Ospf};
+use ospf_packet::{
+    lls::{extended_options, Lls},
+    packet::OspfSubPacket,
+    MutableOspfPacket, Ospf, ToBytes,
+};
 use pnet::packet::Packet as _;
 
-use crate::{database::ProtocolDB, interface::Interface, util::ip2hex};
+use crate::{database::ProtocolDB, gr, interface::Interface, logging::Subsystem, statistics, util::ip2hex};
 
 async fn create_packet(interface: &Interface, packet: &impl OspfSubPacket) -> Ospf {
     Ospf {
@@ -30,9 +34,15 @@ pub async fn send_packet(
     m_packet.populate(&raw);
     m_packet.set_length(m_packet.packet().len() as u16);
     m_packet.auto_set_checksum();
-    let pkg = m_packet.to_immutable();
+    // LLS 数据块附在报文之后，不计入报头中的长度和校验和
+    if packet.has_lls() {
+        buffer.extend_from_slice(&local_lls().to_bytes());
+    }
+    let neighbor = iface.neighbors.values().find(|n| n.ip_addr == destination);
+    statistics::sent(&iface.interface_name, neighbor.map(|n| (n.router_id, n.ip_addr)), packet.get_type());
+    let pkg = ospf_packet::OspfPacket::new(&buffer).unwrap();
     match iface.sender.send_to(pkg, IpAddr::V4(destination)) {
-        Ok(n) => assert_eq!(n, m_packet.packet().len()),
+        Ok(n) => assert_eq!(n, buffer.len()),
         Err(e) => panic!("failed to send packet: {}", e),
     }
     // try update lsa in database
@@ -40,14 +50,26 @@ pub async fn send_packet(
         let rt = tokio::runtime::Handle::current();
         rt.block_on(
             rt.block_on(ProtocolDB::get())
-                .lsa_has_sent(iface.area_id, lsa),
+                .lsa_has_sent(iface, lsa),
         )
     }));
-    #[cfg(debug_assertions)]
-    crate::log!(
+    crate::log_at!(
+        Debug, Subsystem::of_packet(packet.get_type());
         "sent packet to {}: {}({} bytes)",
         destination,
         packet.get_type_string(),
-        m_packet.packet().len()
+        buffer.len()
     );
 }
+
+/// 本机总是支持带外重新同步，重启状态下发出重启信号
+fn local_lls() -> Lls {
+    let mut options = extended_options::LR;
+    if gr::restarting() {
+        options |= extended_options::RS;
+    }
+    Lls {
+        extended_options: Some(options),
+        others: vec![],
+    }
+}
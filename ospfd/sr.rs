@@ -0,0 +1,415 @@
+//! 分段路由（RFC 8665）：为环回接口宣告前缀 SID，为邻接分配邻接 SID，
+//! 收集其他路由器的 SR 信息，并按 SPF 的结果计算标签转发表（LFIB）。
+//! 目前只计算 LFIB 供查看，不写入内核。
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    net::Ipv4Addr,
+    sync::Mutex,
+};
+
+use ospf_packet::{
+    lsa::{link_types, types::OPAQUE_AREA_LSA, Lsa, LsaData, LsaIndex},
+    ri::{RouterInfo, RI_OPAQUE_TYPE},
+    sr::*,
+    ToBytes, TryFromBuf,
+};
+
+use crate::{
+    config::{Config, SrConfig},
+    constant::LsaMaxAge,
+    database::{InterfaceGuard, InterfacesGuard, ProtocolDB, RoutingTable},
+    guard,
+    interface::{Interface, InterfaceState, NetType},
+    json,
+    json::{Json, ToJson},
+    log_warning, must,
+    neighbor::Neighbor,
+    opaque::{self, OpaqueError, OpaqueScope},
+    router_info,
+    util::ip2hex,
+};
+
+/// 一台路由器宣告的 SR 信息
+#[derive(Debug, Clone)]
+pub struct SrRouter {
+    pub area_id: Ipv4Addr,
+    pub router_id: Ipv4Addr,
+    pub srgb: Option<SidLabelRange>,
+    pub srlb: Option<SidLabelRange>,
+    /// 不透明标识 -> 扩展前缀
+    pub prefixes: BTreeMap<u32, ExtendedPrefix>,
+    /// 不透明标识 -> 扩展链路
+    pub links: BTreeMap<u32, ExtendedLink>,
+}
+
+impl SrRouter {
+    fn is_empty(&self) -> bool {
+        self.srgb.is_none() && self.prefixes.is_empty() && self.links.is_empty()
+    }
+}
+
+/// (区域, 路由器) -> SR 信息
+static SR_DB: Mutex<BTreeMap<(Ipv4Addr, Ipv4Addr), SrRouter>> = Mutex::new(BTreeMap::new());
+
+/// (接口地址, 邻居路由器标识) -> 邻接 SID 的标签
+static ADJ_LABELS: Mutex<BTreeMap<(Ipv4Addr, Ipv4Addr), u32>> = Mutex::new(BTreeMap::new());
+
+/// 邻居失效时调用，释放为它分配的邻接标签；router_id 为 None 时释放接口上的所有标签
+pub fn release(ip: Ipv4Addr, router_id: Option<Ipv4Addr>) {
+    ADJ_LABELS
+        .lock()
+        .unwrap()
+        .retain(|key, _| key.0 != ip || router_id.is_some_and(|id| key.1 != id));
+}
+
+/// 导出 SR 数据库的快照
+pub fn database() -> Vec<SrRouter> {
+    SR_DB.lock().unwrap().values().cloned().collect()
+}
+
+/// 配置的标签块
+pub fn range((base, size): (u32, u32)) -> SidLabelRange {
+    SidLabelRange {
+        size,
+        first: Sid::Label(base),
+    }
+}
+
+fn is_sr_lsa(key: LsaIndex) -> bool {
+    let opaque_type = key.ls_id.octets()[0];
+    key.ls_type == OPAQUE_AREA_LSA
+        && matches!(
+            opaque_type,
+            RI_OPAQUE_TYPE | EXTENDED_PREFIX_OPAQUE_TYPE | EXTENDED_LINK_OPAQUE_TYPE
+        )
+}
+
+/// 区域数据库加入 LSA 时调用
+pub fn update(area_id: Ipv4Addr, lsa: &Lsa) {
+    must!(is_sr_lsa(lsa.header.into()));
+    if lsa.header.ls_age == LsaMaxAge {
+        return remove(area_id, lsa.header.into());
+    }
+    guard!(LsaData::Opaque(ref data) = lsa.data);
+    let router_id = lsa.header.advertising_router;
+    let id = lsa.header.opaque_local_id();
+    let mut db = SR_DB.lock().unwrap();
+    let router = db.entry((area_id, router_id)).or_insert_with(|| SrRouter {
+        area_id,
+        router_id,
+        srgb: None,
+        srlb: None,
+        prefixes: BTreeMap::new(),
+        links: BTreeMap::new(),
+    });
+    let result = match lsa.header.opaque_type() {
+        RI_OPAQUE_TYPE => RouterInfo::try_from_slice(&data.data).map(|info| {
+            router.srgb = info.srgb;
+            router.srlb = info.srlb;
+        }),
+        EXTENDED_PREFIX_OPAQUE_TYPE => ExtendedPrefix::try_from_slice(&data.data).map(|prefix| {
+            router.prefixes.insert(id, prefix);
+        }),
+        _ => ExtendedLink::try_from_slice(&data.data).map(|link| {
+            router.links.insert(id, link);
+        }),
+    };
+    if let Err(e) = result {
+        log_warning!("bad sr lsa from {}: {}", router_id, e);
+    }
+    if router.is_empty() {
+        db.remove(&(area_id, router_id));
+    }
+}
+
+/// 区域数据库删除 LSA 时调用
+pub fn remove(area_id: Ipv4Addr, key: LsaIndex) {
+    must!(is_sr_lsa(key));
+    let id = u32::from(key.ls_id) & 0x00ff_ffff;
+    let mut db = SR_DB.lock().unwrap();
+    let key = (area_id, key.ad_router, key.ls_id.octets()[0]);
+    guard!(Some(router) = db.get_mut(&(key.0, key.1)));
+    match key.2 {
+        RI_OPAQUE_TYPE => (router.srgb, router.srlb) = (None, None),
+        EXTENDED_PREFIX_OPAQUE_TYPE => {
+            router.prefixes.remove(&id);
+        }
+        _ => {
+            router.links.remove(&id);
+        }
+    }
+    if router.is_empty() {
+        db.remove(&(key.0, key.1));
+    }
+}
+
+/// 生成 interfaces.me 的扩展前缀 LSA 和扩展链路 LSA，不透明标识都使用接口序号
+pub async fn gen_sr_lsa(interfaces: &mut InterfacesGuard) -> Result<(), OpaqueError> {
+    guard!(Some(sr) = Config::get().segment_routing; ret: Ok(()));
+    let scope = OpaqueScope::Area(interfaces.me.area_id);
+    let id = interfaces.me.index;
+    match extended_prefix(&interfaces.me) {
+        Some(prefix) => {
+            let data = prefix.to_bytes().to_vec();
+            opaque::originate(interfaces, scope.clone(), EXTENDED_PREFIX_OPAQUE_TYPE, id, data).await?
+        }
+        None => opaque::withdraw(interfaces, scope.clone(), EXTENDED_PREFIX_OPAQUE_TYPE, id).await?,
+    }
+    match extended_link(&interfaces.me, &sr) {
+        Some(link) => {
+            let data = link.to_bytes().to_vec();
+            opaque::originate(interfaces, scope, EXTENDED_LINK_OPAQUE_TYPE, id, data).await
+        }
+        None => opaque::withdraw(interfaces, scope, EXTENDED_LINK_OPAQUE_TYPE, id).await,
+    }
+}
+
+/// 接口离开区域前调用，撤销它的扩展前缀 LSA 和扩展链路 LSA
+pub async fn withdraw(interfaces: &mut InterfacesGuard) -> Result<(), OpaqueError> {
+    let scope = OpaqueScope::Area(interfaces.me.area_id);
+    let id = interfaces.me.index;
+    opaque::withdraw(interfaces, scope.clone(), EXTENDED_PREFIX_OPAQUE_TYPE, id).await?;
+    opaque::withdraw(interfaces, scope, EXTENDED_LINK_OPAQUE_TYPE, id).await
+}
+
+/// 环回接口的地址以 /32 宣告，前缀 SID 为节点 SID
+fn extended_prefix(iface: &Interface) -> Option<ExtendedPrefix> {
+    let sid = iface.prefix_sid?;
+    must!(iface.state == InterfaceState::Loopback; ret: None);
+    let flags = if sid.no_php { prefix_sid_flags::NO_PHP } else { 0 };
+    Some(ExtendedPrefix {
+        route_type: 1, // 区域内路由
+        prefix_len: 32,
+        flags: prefix_flags::NODE,
+        prefix: iface.ip_addr,
+        prefix_sids: vec![PrefixSid {
+            flags,
+            mt_id: 0,
+            algorithm: algorithms::SPF,
+            sid: Sid::Index(sid.index),
+        }],
+    })
+}
+
+/// 为每个完全邻接的邻居分配邻接 SID，邻接消失时释放标签
+fn extended_link(iface: &Interface, sr: &SrConfig) -> Option<ExtendedLink> {
+    let full: Vec<_> = iface
+        .neighbors
+        .values()
+        .filter(|n| n.is_adjacent())
+        .map(|n| n.router_id)
+        .collect();
+    must!(!iface.passive && iface.state != InterfaceState::Down && iface.state != InterfaceState::Loopback; ret: None);
+    must!(!full.is_empty(); ret: None);
+    let mut labels = ADJ_LABELS.lock().unwrap();
+    let p2p = matches!(iface.net_type, NetType::P2P | NetType::Virtual);
+    let (link_type, link_id) = match p2p {
+        true => (link_types::P2P_LINK, full[0]),
+        false => (link_types::TRANSIT_LINK, iface.dr),
+    };
+    let (base, size) = sr.local_block;
+    let mut adj_sids = vec![];
+    for id in full {
+        let key = (iface.ip_addr, id);
+        let label = match labels.get(&key) {
+            Some(label) => *label,
+            None => {
+                // 取本地块中最小的空闲标签
+                guard!(Some(label) = (base..base + size).find(|l| !labels.values().any(|v| v == l));
+                    else: log_warning!("local block exhausted, no adj-sid for {}", id); continue);
+                labels.insert(key, label);
+                label
+            }
+        };
+        adj_sids.push(AdjSid {
+            flags: adj_sid_flags::VALUE | adj_sid_flags::LOCAL,
+            mt_id: 0,
+            weight: 0,
+            neighbor: (!p2p).then_some(id),
+            sid: Sid::Label(label),
+        });
+    }
+    Some(ExtendedLink {
+        link_type,
+        link_id,
+        link_data: iface.ip_addr,
+        adj_sids,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelAction {
+    /// 弹出标签；没有下一跳时在本地处理
+    Pop,
+    Swap(u32),
+}
+
+/// 标签转发表项
+#[derive(Debug, Clone)]
+pub struct LfibEntry {
+    pub in_label: u32,
+    pub action: LabelAction,
+    /// 前缀 SID 为前缀，邻接 SID 为邻居
+    pub fec: String,
+    pub next_hop: Option<Ipv4Addr>,
+    pub interface: Option<String>,
+}
+
+fn find_neighbor(interfaces: &[InterfaceGuard], ip: Ipv4Addr) -> Option<(&InterfaceGuard, &Neighbor)> {
+    interfaces.iter().find_map(|iface| {
+        let n = iface.neighbors.values().find(|n| n.ip_addr == ip)?;
+        Some((iface, n))
+    })
+}
+
+/// 按路由表计算标签转发表，调用时需持有所有接口的锁
+pub fn lfib(interfaces: &[InterfaceGuard], routing: &RoutingTable) -> Vec<LfibEntry> {
+    use LabelAction::*;
+    guard!(Some(sr) = Config::get().segment_routing; ret: vec![]);
+    let srgb = range(sr.global_block);
+    let mut lfib = BTreeMap::new();
+    // 自己的节点 SID
+    for iface in interfaces {
+        guard!(Some(sid) = iface.prefix_sid; continue);
+        guard!(Some(label) = srgb.label(sid.index); continue);
+        lfib.entry(label).or_insert(LfibEntry {
+            in_label: label,
+            action: Pop,
+            fec: format!("{}/32", iface.ip_addr),
+            next_hop: None,
+            interface: None,
+        });
+    }
+    let db = SR_DB.lock().unwrap();
+    let me = ProtocolDB::get_router_id();
+    for router in db.values().filter(|r| r.router_id != me) {
+        for prefix in router.prefixes.values() {
+            for sid in prefix.prefix_sids.iter() {
+                must!(sid.algorithm == algorithms::SPF; continue);
+                guard!(Sid::Index(index) = sid.sid; continue);
+                guard!(Some(in_label) = srgb.label(index); continue);
+                guard!(Some(route) = routing.get_routing(prefix.prefix); continue);
+                must!(ip2hex(route.addr_mask).leading_ones() == prefix.prefix_len as u32; continue);
+                guard!(Some((iface, n)) = find_neighbor(interfaces, route.next_hop); continue);
+                // 下一跳就是前缀的宣告者时，按标志决定是否在倒数第二跳弹出
+                let last_hop = n.router_id == router.router_id;
+                let action = if last_hop && sid.flags & prefix_sid_flags::NO_PHP == 0 {
+                    Pop
+                } else if last_hop && sid.flags & prefix_sid_flags::EXPLICIT_NULL != 0 {
+                    Swap(0)
+                } else {
+                    let srgb = db.get(&(iface.area_id, n.router_id)).and_then(|r| r.srgb);
+                    guard!(Some(out) = srgb.and_then(|r| r.label(index)); continue);
+                    Swap(out)
+                };
+                lfib.entry(in_label).or_insert(LfibEntry {
+                    in_label,
+                    action,
+                    fec: format!("{}/{}", prefix.prefix, prefix.prefix_len),
+                    next_hop: Some(n.ip_addr),
+                    interface: Some(iface.interface_name.clone()),
+                });
+            }
+        }
+    }
+    // 自己的邻接 SID
+    for ((ip, id), label) in ADJ_LABELS.lock().unwrap().iter() {
+        guard!(Some(iface) = interfaces.iter().find(|i| i.ip_addr == *ip); continue);
+        guard!(Some(n) = iface.neighbors.values().find(|n| n.router_id == *id && n.is_adjacent()); continue);
+        lfib.entry(*label).or_insert(LfibEntry {
+            in_label: *label,
+            action: Pop,
+            fec: format!("adj {}", router_info::name(*id)),
+            next_hop: Some(n.ip_addr),
+            interface: Some(iface.interface_name.clone()),
+        });
+    }
+    lfib.into_values().collect()
+}
+
+impl Display for LfibEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self.action {
+            LabelAction::Pop => "Pop".to_string(),
+            LabelAction::Swap(label) => format!("Swap {}", label),
+        };
+        write!(
+            f,
+            "{:<9} {:<11} {:<20} {:<15} {}",
+            self.in_label,
+            action,
+            self.fec,
+            self.next_hop.map_or("local".to_string(), |ip| ip.to_string()),
+            self.interface.as_deref().unwrap_or("-"),
+        )
+    }
+}
+
+impl Display for SrRouter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let block = |r: Option<SidLabelRange>| match r {
+            Some(SidLabelRange { size, first: Sid::Label(base) }) => format!("{}-{}", base, base + size - 1),
+            _ => "-".to_string(),
+        };
+        write!(
+            f,
+            "Router {}, SRGB {}, SRLB {}",
+            router_info::name(self.router_id),
+            block(self.srgb),
+            block(self.srlb)
+        )?;
+        for prefix in self.prefixes.values() {
+            for sid in prefix.prefix_sids.iter() {
+                write!(f, "\n  Prefix {}/{} SID {}", prefix.prefix, prefix.prefix_len, sid.sid)?;
+                if sid.flags & prefix_sid_flags::NO_PHP != 0 {
+                    write!(f, " no-php")?;
+                }
+            }
+        }
+        for link in self.links.values() {
+            for sid in link.adj_sids.iter() {
+                let neighbor = sid.neighbor.unwrap_or(link.link_id);
+                write!(
+                    f,
+                    "\n  Adj {} via {} SID {}",
+                    router_info::name(neighbor),
+                    link.link_data,
+                    sid.sid
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for LfibEntry {
+    fn to_json(&self) -> Json {
+        let (action, out_label) = match self.action {
+            LabelAction::Pop => ("pop", None),
+            LabelAction::Swap(label) => ("swap", Some(label)),
+        };
+        json!({
+            "in_label": self.in_label,
+            "action": action,
+            "out_label": out_label,
+            "fec": &self.fec,
+            "next_hop": self.next_hop,
+            "interface": self.interface.clone(),
+        })
+    }
+}
+
+impl ToJson for SrRouter {
+    fn to_json(&self) -> Json {
+        json!({
+            "area": self.area_id,
+            "router_id": self.router_id,
+            "srgb": self.srgb.map(|r| r.to_json()),
+            "srlb": self.srlb.map(|r| r.to_json()),
+            "prefixes": self.prefixes.values().collect::<Json>(),
+            "links": self.links.values().collect::<Json>(),
+        })
+    }
+}
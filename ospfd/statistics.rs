@@ -0,0 +1,387 @@
+//! 协议统计
+//!
+//! 按接口统计收发的报文、丢弃的报文和洪泛的 LSA，按邻居统计接受的报文、收到的 LSA 和邻接变化，
+//! 全局统计 LSA 的生成、接收、清除和 SPF 计算。计数只在 `clear ospf statistics` 时清零。
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::Ipv4Addr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use ospf_packet::DecodeError;
+
+use crate::{
+    json,
+    json::{Json, ToJson},
+};
+
+/// 报文类型 1 到 5
+const PACKET_TYPES: usize = 5;
+pub const PACKET_NAMES: [&str; PACKET_TYPES] = ["Hello", "DD", "LSR", "LSU", "LSAck"];
+
+/// 丢弃报文的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiscardReason {
+    BadChecksum,
+    BadVersion,
+    BadArea,
+    BadSource,
+    BadDestination,
+    Passive,
+    Truncated,
+    BadLength,
+    UnknownType,
+    TrailingBytes,
+    BadAuth,
+}
+
+impl DiscardReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            DiscardReason::BadChecksum => "bad checksum",
+            DiscardReason::BadVersion => "bad version",
+            DiscardReason::BadArea => "bad area",
+            DiscardReason::BadSource => "bad source",
+            DiscardReason::BadDestination => "bad destination",
+            DiscardReason::Passive => "passive interface",
+            DiscardReason::Truncated => "truncated",
+            DiscardReason::BadLength => "bad length",
+            DiscardReason::UnknownType => "unknown type",
+            DiscardReason::TrailingBytes => "trailing bytes",
+            DiscardReason::BadAuth => "bad authentication",
+        }
+    }
+}
+
+impl From<&DecodeError> for DiscardReason {
+    fn from(value: &DecodeError) -> Self {
+        match value {
+            DecodeError::Truncated { .. } => DiscardReason::Truncated,
+            DecodeError::BadLength(_) => DiscardReason::BadLength,
+            DecodeError::UnknownType(_) => DiscardReason::UnknownType,
+            DecodeError::TrailingBytes(_) => DiscardReason::TrailingBytes,
+            DecodeError::BadChecksum => DiscardReason::BadChecksum,
+        }
+    }
+}
+
+/// 按类型统计的报文数
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PacketCounters {
+    pub sent: [u64; PACKET_TYPES],
+    pub received: [u64; PACKET_TYPES],
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InterfaceStatistics {
+    pub packets: PacketCounters,
+    pub discarded: BTreeMap<DiscardReason, u64>,
+    /// 从接口洪泛出去的 LSA
+    pub lsa_flooded: u64,
+    pub state_changes: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NeighborStatistics {
+    /// 邻居所在的接口
+    pub interface: String,
+    /// 只统计通过检查的报文，以及单播给邻居的报文
+    pub packets: PacketCounters,
+    pub lsa_received: u64,
+    pub state_changes: u64,
+    /// 进入 Full 的次数
+    pub adjacency_up: u64,
+    /// 离开 Full 的次数
+    pub adjacency_down: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statistics {
+    /// 启动或上次清零的时间
+    pub since: Instant,
+    pub lsa_originated: u64,
+    pub lsa_received: u64,
+    /// 比数据库中的实例新、加入数据库的 LSA
+    pub lsa_installed: u64,
+    pub lsa_flushed: u64,
+    pub spf_runs: u64,
+    pub spf_last: Duration,
+    pub spf_total: Duration,
+    /// 写入或删除内核路由失败的次数
+    pub fib_errors: u64,
+    /// 接口名 -> 统计
+    pub interfaces: BTreeMap<String, InterfaceStatistics>,
+    /// （路由器标识，接口地址）-> 统计
+    pub neighbors: BTreeMap<(Ipv4Addr, Ipv4Addr), NeighborStatistics>,
+}
+
+impl Statistics {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            lsa_originated: 0,
+            lsa_received: 0,
+            lsa_installed: 0,
+            lsa_flushed: 0,
+            spf_runs: 0,
+            spf_last: Duration::ZERO,
+            spf_total: Duration::ZERO,
+            fib_errors: 0,
+            interfaces: BTreeMap::new(),
+            neighbors: BTreeMap::new(),
+        }
+    }
+
+    fn interface(&mut self, name: &str) -> &mut InterfaceStatistics {
+        if !self.interfaces.contains_key(name) {
+            self.interfaces.insert(name.to_string(), Default::default());
+        }
+        self.interfaces.get_mut(name).unwrap()
+    }
+}
+
+lazy_static! {
+    static ref STATISTICS: Mutex<Statistics> = Mutex::new(Statistics::new());
+}
+
+fn with<T>(f: impl FnOnce(&mut Statistics) -> T) -> T {
+    f(&mut STATISTICS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn count(counters: &mut [u64; PACKET_TYPES], message_type: u8) {
+    if let Some(n) = (message_type as usize).checked_sub(1).and_then(|i| counters.get_mut(i)) {
+        *n += 1;
+    }
+}
+
+/// 接口收到发给自己的报文
+pub fn received(iface: &str, message_type: u8) {
+    with(|s| count(&mut s.interface(iface).packets.received, message_type));
+}
+
+/// 邻居发来的报文通过了检查
+pub fn neighbor_received(iface: &str, neighbor: (Ipv4Addr, Ipv4Addr), message_type: u8) {
+    with(|s| {
+        let stat = s.neighbors.entry(neighbor).or_default();
+        stat.interface = iface.to_string();
+        count(&mut stat.packets.received, message_type);
+    });
+}
+
+/// 从接口发出报文，单播给邻居时同时计入邻居
+pub fn sent(iface: &str, neighbor: Option<(Ipv4Addr, Ipv4Addr)>, message_type: u8) {
+    with(|s| {
+        count(&mut s.interface(iface).packets.sent, message_type);
+        if let Some(stat) = neighbor.and_then(|n| s.neighbors.get_mut(&n)) {
+            count(&mut stat.packets.sent, message_type);
+        }
+    });
+}
+
+pub fn discarded(iface: &str, reason: DiscardReason) {
+    with(|s| *s.interface(iface).discarded.entry(reason).or_default() += 1);
+}
+
+/// 收到邻居 LSU 中的一条 LSA
+pub fn lsa_received(neighbor: (Ipv4Addr, Ipv4Addr)) {
+    with(|s| {
+        s.lsa_received += 1;
+        s.neighbors.entry(neighbor).or_default().lsa_received += 1;
+    });
+}
+
+pub fn lsa_installed() {
+    with(|s| s.lsa_installed += 1);
+}
+
+pub fn lsa_originated() {
+    with(|s| s.lsa_originated += 1);
+}
+
+pub fn lsa_flushed() {
+    with(|s| s.lsa_flushed += 1);
+}
+
+pub fn lsa_flooded(iface: &str) {
+    with(|s| s.interface(iface).lsa_flooded += 1);
+}
+
+pub fn spf(time: Duration) {
+    with(|s| {
+        s.spf_runs += 1;
+        s.spf_last = time;
+        s.spf_total += time;
+    });
+}
+
+pub fn fib_error() {
+    with(|s| s.fib_errors += 1);
+}
+
+pub fn interface_state_changed(iface: &str) {
+    with(|s| s.interface(iface).state_changes += 1);
+}
+
+/// 邻居状态变化，was_full/is_full 表示变化前后是否为 Full
+pub fn neighbor_state_changed(neighbor: (Ipv4Addr, Ipv4Addr), was_full: bool, is_full: bool) {
+    with(|s| {
+        let stat = s.neighbors.entry(neighbor).or_default();
+        stat.state_changes += 1;
+        stat.adjacency_up += (!was_full && is_full) as u64;
+        stat.adjacency_down += (was_full && !is_full) as u64;
+    });
+}
+
+pub fn clear() {
+    with(|s| *s = Statistics::new());
+}
+
+pub fn get() -> Statistics {
+    with(|s| s.clone())
+}
+
+impl fmt::Display for PacketCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  {:<10}", "")?;
+        for name in PACKET_NAMES {
+            write!(f, "{:>10}", name)?;
+        }
+        for (name, counters) in [("Sent", &self.sent), ("Received", &self.received)] {
+            write!(f, "\n  {:<10}", name)?;
+            for n in counters {
+                write!(f, "{:>10}", n)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Statistics of the last {}s", self.since.elapsed().as_secs())?;
+        writeln!(
+            f,
+            "LSAs: originated {}, received {}, installed {}, flushed {}",
+            self.lsa_originated, self.lsa_received, self.lsa_installed, self.lsa_flushed
+        )?;
+        write!(f, "SPF: runs {}", self.spf_runs)?;
+        if self.spf_runs > 0 {
+            let average = self.spf_total / self.spf_runs as u32;
+            write!(f, ", last {:?}, average {:?}", self.spf_last, average)?;
+        }
+        write!(f, "\nFIB errors: {}", self.fib_errors)?;
+        for (name, stat) in self.interfaces.iter() {
+            writeln!(f)?;
+            writeln!(f, "Interface {}", name)?;
+            writeln!(f, "{}", stat.packets)?;
+            write!(f, "  LSAs flooded {}, state changes {}", stat.lsa_flooded, stat.state_changes)?;
+            for (reason, n) in stat.discarded.iter() {
+                write!(f, "\n  Discarded ({}) {}", reason.name(), n)?;
+            }
+        }
+        for ((router_id, ip), stat) in self.neighbors.iter() {
+            writeln!(f)?;
+            writeln!(f, "Neighbor {} ({}) on {}", router_id, ip, stat.interface)?;
+            writeln!(f, "{}", stat.packets)?;
+            write!(
+                f,
+                "  LSAs received {}, state changes {}, adjacency up {}, down {}",
+                stat.lsa_received, stat.state_changes, stat.adjacency_up, stat.adjacency_down
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for PacketCounters {
+    fn to_json(&self) -> Json {
+        let counters = |counters: &[u64; PACKET_TYPES]| {
+            Json::Object(PACKET_NAMES.iter().zip(counters).map(|(name, n)| (*name, Json::from(*n))).collect())
+        };
+        json!({ "sent": counters(&self.sent), "received": counters(&self.received) })
+    }
+}
+
+impl ToJson for Statistics {
+    fn to_json(&self) -> Json {
+        let interfaces: Vec<_> = self
+            .interfaces
+            .iter()
+            .map(|(name, stat)| {
+                let discarded: Vec<_> = stat
+                    .discarded
+                    .iter()
+                    .map(|(reason, n)| json!({ "reason": reason.name(), "count": *n }))
+                    .collect();
+                json!({
+                    "name": name,
+                    "packets": stat.packets.to_json(),
+                    "discarded": discarded,
+                    "lsa_flooded": stat.lsa_flooded,
+                    "state_changes": stat.state_changes,
+                })
+            })
+            .collect();
+        let neighbors: Vec<_> = self
+            .neighbors
+            .iter()
+            .map(|((router_id, ip), stat)| {
+                json!({
+                    "router_id": *router_id,
+                    "address": *ip,
+                    "interface": &stat.interface,
+                    "packets": stat.packets.to_json(),
+                    "lsa_received": stat.lsa_received,
+                    "state_changes": stat.state_changes,
+                    "adjacency_up": stat.adjacency_up,
+                    "adjacency_down": stat.adjacency_down,
+                })
+            })
+            .collect();
+        json!({
+            "seconds": self.since.elapsed().as_secs(),
+            "lsa_originated": self.lsa_originated,
+            "lsa_received": self.lsa_received,
+            "lsa_installed": self.lsa_installed,
+            "lsa_flushed": self.lsa_flushed,
+            "spf_runs": self.spf_runs,
+            "spf_last_seconds": self.spf_last.as_secs_f64(),
+            "spf_total_seconds": self.spf_total.as_secs_f64(),
+            "fib_errors": self.fib_errors,
+            "interfaces": interfaces,
+            "neighbors": neighbors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let neighbor = (Ipv4Addr::new(2, 2, 2, 2), Ipv4Addr::new(10, 0, 0, 2));
+        received("eth0", 1);
+        received("eth0", 9);
+        neighbor_received("eth0", neighbor, 4);
+        sent("eth0", Some(neighbor), 5);
+        sent("eth0", None, 1);
+        discarded("eth0", DiscardReason::from(&DecodeError::BadChecksum));
+        neighbor_state_changed(neighbor, false, true);
+        neighbor_state_changed(neighbor, true, false);
+        let s = get();
+        let iface = &s.interfaces["eth0"];
+        assert_eq!(iface.packets.received, [1, 0, 0, 0, 0]);
+        assert_eq!(iface.packets.sent, [1, 0, 0, 0, 1]);
+        assert_eq!(iface.discarded[&DiscardReason::BadChecksum], 1);
+        let stat = &s.neighbors[&neighbor];
+        assert_eq!((stat.packets.received[3], stat.packets.sent[4]), (1, 1));
+        assert_eq!((stat.state_changes, stat.adjacency_up, stat.adjacency_down), (2, 1, 1));
+        clear();
+        assert!(get().interfaces.is_empty());
+    }
+}
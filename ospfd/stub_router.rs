@@ -0,0 +1,150 @@
+//! 存根路由器（RFC 6987）
+//!
+//! 最大度量状态下，路由器 LSA 中除存根网络以外的连接都使用 MaxLinkMetric，其他路由器只在没有别的路径时
+//! 才经过本路由器转发，但仍能到达本路由器的存根网络。可以从命令行开启，用于维护前引走流量；
+//! 也可以在启动后自动开启一段时间，或直到所有邻接都完全建立，避免转发表收敛前形成黑洞。
+//! 配置了 summary-lsa 或 external-lsa 时，在这些 LSA 中也宣告配置的度量。
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    constant::MaxLinkMetric,
+    database::InterfacesGuard,
+    guard,
+    interface::{self, InterfaceState},
+    json,
+    json::Json,
+    log_success, must,
+    neighbor::NeighborState,
+    util::AbortHandle,
+};
+
+struct Startup {
+    deadline: Instant,
+    _timer: AbortHandle,
+}
+
+/// 从命令行（或配置 router-lsa）开启
+static ADMINISTRATIVE: Mutex<bool> = Mutex::new(false);
+
+/// 启动后自动开启
+static STARTUP: Mutex<Option<Startup>> = Mutex::new(None);
+
+/// 是否处于最大度量状态
+pub fn active() -> bool {
+    *ADMINISTRATIVE.lock().unwrap() || STARTUP.lock().unwrap().is_some()
+}
+
+/// 启动时调用，按配置进入最大度量状态
+pub fn init() {
+    let config = Config::get().max_metric;
+    *ADMINISTRATIVE.lock().unwrap() = config.administrative;
+    guard!(Some(secs) = config.on_startup);
+    let period = Duration::from_secs(secs as u64);
+    let timer = tokio::spawn(async move {
+        tokio::time::sleep(period).await;
+        end_startup("startup period expired");
+    })
+    .into();
+    *STARTUP.lock().unwrap() = Some(Startup {
+        deadline: Instant::now() + period,
+        _timer: timer,
+    });
+    log_success!("advertising max metric for {}s after startup", secs);
+}
+
+fn end_startup(reason: &str) {
+    must!(STARTUP.lock().unwrap().take().is_some());
+    log_success!("stop advertising max metric on startup: {}", reason);
+    interface::notify_changed();
+}
+
+/// 命令行开启或关闭最大度量
+pub fn set(on: bool) {
+    *ADMINISTRATIVE.lock().unwrap() = on;
+    interface::notify_changed();
+}
+
+/// 接口检查时调用：配置了 wait-for-full 时，没有正在建立的邻接且至少有一个完全邻接的邻居后提前结束
+pub fn check(interfaces: &InterfacesGuard) {
+    must!(Config::get().max_metric.wait_for_full && STARTUP.lock().unwrap().is_some());
+    let neighbors = || {
+        interfaces
+            .iter()
+            .filter(|i| !i.passive && !matches!(i.state, InterfaceState::Down | InterfaceState::Loopback))
+            .flat_map(|i| i.neighbors.values())
+    };
+    let forming = neighbors().any(|n| {
+        matches!(
+            n.state,
+            NeighborState::Init | NeighborState::ExStart | NeighborState::Exchange | NeighborState::Loading
+        )
+    });
+    must!(!forming && neighbors().any(|n| n.state == NeighborState::Full));
+    end_startup("all adjacencies are full");
+}
+
+/// 路由器 LSA 中非存根连接的度量
+pub fn link_metric(cost: u16) -> u16 {
+    if active() {
+        MaxLinkMetric
+    } else {
+        cost
+    }
+}
+
+/// 汇总 LSA 的度量
+pub fn summary_metric(metric: u32) -> u32 {
+    match Config::get().max_metric.summary {
+        Some(max) if active() => max,
+        _ => metric,
+    }
+}
+
+/// 外部 LSA 的度量
+pub fn external_metric(metric: u32) -> u32 {
+    match Config::get().max_metric.external {
+        Some(max) if active() => max,
+        _ => metric,
+    }
+}
+
+/// 最大度量的状态（JSON）
+pub fn status_json() -> Json {
+    let startup = STARTUP.lock().unwrap().as_ref().map(|s| s.deadline.saturating_duration_since(Instant::now()));
+    let config = Config::get().max_metric;
+    json!({
+        "active": active(),
+        "administrative": *ADMINISTRATIVE.lock().unwrap(),
+        "startup_seconds_left": startup.map(|left| left.as_secs()),
+        "summary_lsa_metric": config.summary,
+        "external_lsa_metric": config.external,
+    })
+}
+
+/// 显示最大度量的状态
+pub fn status() -> Vec<String> {
+    let mut lines = vec![];
+    if *ADMINISTRATIVE.lock().unwrap() {
+        lines.push("Max metric enabled administratively".to_string());
+    }
+    if let Some(ref startup) = *STARTUP.lock().unwrap() {
+        let left = startup.deadline.saturating_duration_since(Instant::now());
+        lines.push(format!("Max metric on startup, {}s left", left.as_secs()));
+    }
+    if lines.is_empty() {
+        lines.push("Max metric disabled".to_string());
+    }
+    let config = Config::get().max_metric;
+    if let Some(metric) = config.summary {
+        lines.push(format!("  summary-lsa metric {}", metric));
+    }
+    if let Some(metric) = config.external {
+        lines.push(format!("  external-lsa metric {}", metric));
+    }
+    lines
+}
@@ -0,0 +1,274 @@
+//! 流量工程（RFC 3630）：按接口配置生成 TE LSA，并把收到的 TE LSA 整理为单独的 TE 数据库，
+//! 供路径计算使用。
+
+use std::{collections::BTreeMap, fmt::Display, net::Ipv4Addr, sync::Mutex};
+
+use ospf_packet::{
+    lsa::{types::OPAQUE_AREA_LSA, Lsa, LsaData, LsaIndex},
+    te::{te_link_types, TeLink, TeLsa, TE_OPAQUE_TYPE},
+    ToBytes, TryFromBuf,
+};
+
+use crate::{
+    config::Config,
+    constant::LsaMaxAge,
+    database::{InterfacesGuard, ProtocolDB},
+    guard,
+    interface::{Interface, InterfaceState, NetType},
+    json,
+    json::{Json, ToJson},
+    log_warning, must,
+    opaque::{self, OpaqueError, OpaqueScope},
+    router_info,
+};
+
+/// 一台路由器宣告的 TE 信息
+#[derive(Debug, Clone)]
+pub struct TeRouter {
+    pub area_id: Ipv4Addr,
+    pub router_id: Ipv4Addr,
+    pub router_address: Option<Ipv4Addr>,
+    /// 宣告路由器地址的 LSA 的不透明标识
+    pub address_id: u32,
+    /// 不透明标识 -> 链路
+    pub links: BTreeMap<u32, TeLink>,
+}
+
+impl TeRouter {
+    /// 删除不透明标识为 id 的 LSA 宣告的内容
+    fn forget(&mut self, id: u32) {
+        if self.router_address.is_some() && self.address_id == id {
+            self.router_address = None;
+        }
+        self.links.remove(&id);
+    }
+}
+
+/// (区域, 路由器) -> TE 信息
+static TE_DB: Mutex<BTreeMap<(Ipv4Addr, Ipv4Addr), TeRouter>> = Mutex::new(BTreeMap::new());
+
+/// 导出 TE 数据库的快照
+pub fn database() -> Vec<TeRouter> {
+    TE_DB.lock().unwrap().values().cloned().collect()
+}
+
+fn is_te_lsa(key: LsaIndex) -> bool {
+    key.ls_type == OPAQUE_AREA_LSA && key.ls_id.octets()[0] == TE_OPAQUE_TYPE
+}
+
+/// 区域数据库加入 LSA 时调用
+pub fn update(area_id: Ipv4Addr, lsa: &Lsa) {
+    must!(is_te_lsa(lsa.header.into()));
+    if lsa.header.ls_age == LsaMaxAge {
+        return remove(area_id, lsa.header.into());
+    }
+    guard!(LsaData::Opaque(ref data) = lsa.data);
+    let te = match TeLsa::try_from_slice(&data.data) {
+        Ok(te) => te,
+        Err(e) => return log_warning!("bad te lsa from {}: {}", lsa.header.advertising_router, e),
+    };
+    let router_id = lsa.header.advertising_router;
+    let mut db = TE_DB.lock().unwrap();
+    let router = db.entry((area_id, router_id)).or_insert_with(|| TeRouter {
+        area_id,
+        router_id,
+        router_address: None,
+        address_id: 0,
+        links: BTreeMap::new(),
+    });
+    // 同一个不透明标识的 LSA 可能换成另一种 TLV，先删除旧的内容
+    let id = lsa.header.opaque_local_id();
+    router.forget(id);
+    match te {
+        TeLsa::RouterAddress(addr) => (router.router_address, router.address_id) = (Some(addr), id),
+        TeLsa::Link(link) => {
+            router.links.insert(id, link);
+        }
+        TeLsa::Unknown(_) => (),
+    }
+}
+
+/// 区域数据库删除 LSA 时调用
+pub fn remove(area_id: Ipv4Addr, key: LsaIndex) {
+    must!(is_te_lsa(key));
+    let id = u32::from(key.ls_id) & 0x00ff_ffff;
+    let mut db = TE_DB.lock().unwrap();
+    let key = (area_id, key.ad_router);
+    guard!(Some(router) = db.get_mut(&key));
+    router.forget(id);
+    if router.router_address.is_none() && router.links.is_empty() {
+        db.remove(&key);
+    }
+}
+
+/// 生成 interfaces.me 的 TE 链路 LSA，以及所在区域的路由器地址 LSA；
+/// 区域中已经没有配置 TE 的接口时撤销路由器地址 LSA
+pub async fn gen_te_lsa(interfaces: &mut InterfacesGuard) -> Result<(), OpaqueError> {
+    let scope = OpaqueScope::Area(interfaces.me.area_id);
+    // 链路的不透明标识使用接口序号
+    let id = interfaces.me.index;
+    match te_link(&interfaces.me) {
+        Some(link) => {
+            let data = TeLsa::Link(link).to_bytes().to_vec();
+            opaque::originate(interfaces, scope.clone(), TE_OPAQUE_TYPE, id, data).await?;
+        }
+        None => opaque::withdraw(interfaces, scope.clone(), TE_OPAQUE_TYPE, id).await?,
+    }
+    let area_id = interfaces.me.area_id;
+    if !interfaces.iter().any(|i| i.area_id == area_id && i.te.is_some()) {
+        return opaque::withdraw(interfaces, scope, TE_OPAQUE_TYPE, 0).await;
+    }
+    let address = Config::get()
+        .te_router_address
+        .unwrap_or_else(ProtocolDB::get_router_id);
+    let data = TeLsa::RouterAddress(address).to_bytes().to_vec();
+    opaque::originate(interfaces, scope, TE_OPAQUE_TYPE, 0, data).await
+}
+
+/// 接口离开区域前调用，撤销它的 TE 链路 LSA
+pub async fn withdraw(interfaces: &mut InterfacesGuard) -> Result<(), OpaqueError> {
+    let scope = OpaqueScope::Area(interfaces.me.area_id);
+    let id = interfaces.me.index;
+    opaque::withdraw(interfaces, scope, TE_OPAQUE_TYPE, id).await
+}
+
+/// 只为已经建立邻接的链路宣告 TE 属性，与路由器 LSA 中的连接保持一致
+fn te_link(iface: &Interface) -> Option<TeLink> {
+    let te = iface.te?;
+    must!(!iface.passive && iface.state != InterfaceState::Down && iface.state != InterfaceState::Loopback; ret: None);
+    let full = |ip: &Ipv4Addr| iface.neighbors.get(ip).is_some_and(|n| n.is_adjacent());
+    let (link_type, link_id, remote_addrs) = match iface.net_type {
+        NetType::P2P | NetType::Virtual => {
+            let n = iface.neighbors.values().find(|n| n.is_adjacent())?;
+            (te_link_types::P2P_LINK, n.router_id, vec![n.ip_addr])
+        }
+        _ => {
+            must!(iface.is_dr() && !iface.neighbors.is_empty() || full(&iface.dr); ret: None);
+            (te_link_types::MULTI_ACCESS_LINK, iface.dr, vec![])
+        }
+    };
+    // 配置的带宽为 Mbit/s，TLV 中为字节每秒
+    let bytes = |mbps: f32| mbps * 1e6 / 8.0;
+    let reservable = te.reservable.or(te.bandwidth).map(bytes);
+    Some(TeLink {
+        link_type,
+        link_id,
+        local_addrs: vec![iface.ip_addr],
+        remote_addrs,
+        te_metric: Some(te.metric.unwrap_or(iface.cost as u32)),
+        max_bandwidth: te.bandwidth.map(bytes),
+        max_reservable_bandwidth: reservable,
+        // 没有带宽预留，各优先级都未预留
+        unreserved_bandwidth: reservable.map(|bw| [bw; 8]),
+        admin_group: te.admin_group,
+    })
+}
+
+impl Display for TeRouter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mbps = |bw: Option<f32>| bw.map_or("-".to_string(), |bw| format!("{:.2}", bw * 8.0 / 1e6));
+        write!(f, "Router {}", router_info::name(self.router_id))?;
+        if let Some(addr) = self.router_address {
+            write!(f, ", Router Address {}", addr)?;
+        }
+        for link in self.links.values() {
+            write!(
+                f,
+                "\n  {:<12} Link ID {:<15} Local {:<15} Remote {:<15}",
+                te_link_types::to_string(link.link_type),
+                link.link_id,
+                link.local_addrs.first().map_or("-".to_string(), |a| a.to_string()),
+                link.remote_addrs.first().map_or("-".to_string(), |a| a.to_string()),
+            )?;
+            write!(
+                f,
+                "\n    TE Metric {}, Max BW {} Mbit/s, Max Reservable BW {} Mbit/s, Admin Group {:#010x}",
+                link.te_metric.map_or("-".to_string(), |m| m.to_string()),
+                mbps(link.max_bandwidth),
+                mbps(link.max_reservable_bandwidth),
+                link.admin_group.unwrap_or(0),
+            )?;
+            if let Some(bw) = link.unreserved_bandwidth {
+                let bw: Vec<_> = bw.iter().map(|b| mbps(Some(*b))).collect();
+                write!(f, "\n    Unreserved BW [{}] Mbit/s", bw.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for TeRouter {
+    fn to_json(&self) -> Json {
+        json!({
+            "area": self.area_id,
+            "router_id": self.router_id,
+            "router_address": self.router_address,
+            "links": self.links.values().collect::<Json>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ospf_packet::lsa::{LsaHeader, OpaqueLSA};
+
+    fn lsa(opaque_id: u32, te: TeLsa) -> Lsa {
+        Lsa {
+            header: LsaHeader {
+                ls_age: 0,
+                options: 0,
+                ls_type: OPAQUE_AREA_LSA,
+                link_state_id: LsaHeader::opaque_id(TE_OPAQUE_TYPE, opaque_id),
+                advertising_router: Ipv4Addr::new(3, 3, 3, 3),
+                ls_sequence_number: 0,
+                ls_checksum: 0,
+                length: 0,
+            },
+            data: LsaData::Opaque(OpaqueLSA { data: te.to_bytes().to_vec() }),
+        }
+    }
+
+    fn link() -> TeLsa {
+        TeLsa::Link(TeLink {
+            link_type: te_link_types::P2P_LINK,
+            link_id: Ipv4Addr::new(4, 4, 4, 4),
+            local_addrs: vec![],
+            remote_addrs: vec![],
+            te_metric: Some(10),
+            max_bandwidth: None,
+            max_reservable_bandwidth: None,
+            unreserved_bandwidth: None,
+            admin_group: None,
+        })
+    }
+
+    fn router(area_id: Ipv4Addr) -> Option<TeRouter> {
+        database().into_iter().find(|r| r.area_id == area_id)
+    }
+
+    #[test]
+    fn test() {
+        // 其他实现的路由器地址不一定使用不透明标识 0
+        let area_id = Ipv4Addr::new(0, 0, 0, 36);
+        let address = lsa(7, TeLsa::RouterAddress(Ipv4Addr::new(3, 3, 3, 3)));
+        let link0 = lsa(0, link());
+        update(area_id, &address);
+        update(area_id, &link0);
+        let r = router(area_id).unwrap();
+        assert_eq!((r.router_address, r.address_id), (Some(Ipv4Addr::new(3, 3, 3, 3)), 7));
+        assert!(r.links.contains_key(&0));
+
+        remove(area_id, link0.header.into());
+        let r = router(area_id).unwrap();
+        assert!(r.router_address.is_some() && r.links.is_empty());
+
+        // 同一个不透明标识换成链路后，原来的路由器地址不再有效
+        update(area_id, &lsa(7, link()));
+        let r = router(area_id).unwrap();
+        assert!(r.router_address.is_none() && r.links.contains_key(&7));
+
+        remove(area_id, address.header.into());
+        assert!(router(area_id).is_none());
+    }
+}
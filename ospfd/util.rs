@@ -9,6 +9,28 @@ pub const fn hex2ip(hex: u32) -> Ipv4Addr {
     Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])
 }
 
+/// 简单的通配符匹配，支持 `*`（任意个字符）和 `?`（单个字符）
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<_> = pattern.chars().collect();
+    let text: Vec<_> = text.chars().collect();
+    // dp[j]: pattern[..i] 能否匹配 text[..j]
+    let mut dp = vec![false; text.len() + 1];
+    dp[0] = true;
+    for p in pattern {
+        let mut next = vec![false; text.len() + 1];
+        next[0] = dp[0] && p == '*';
+        for j in 1..=text.len() {
+            next[j] = match p {
+                '*' => dp[j] || next[j - 1],
+                '?' => dp[j - 1],
+                c => dp[j - 1] && c == text[j - 1],
+            };
+        }
+        dp = next;
+    }
+    dp[text.len()]
+}
+
 #[derive(Debug, Default)]
 pub struct AbortHandle(Option<tokio::task::AbortHandle>);
 
@@ -87,8 +109,7 @@ macro_rules! must {
     };
     ($x:expr; dbg: $($arg:tt)*) => {
         if !($x) {
-            #[cfg(debug_assertions)]
-            crate::log_warning!($($arg)*);
+            crate::log_debug!($($arg)*);
             return;
         }
     };
@@ -128,8 +149,7 @@ macro_rules! guard {
     };
     ($x:pat = $y:expr; dbg: $($arg:tt)*) => {
         let $x = $y else {
-            #[cfg(debug_assertions)]
-            crate::log_warning!($($arg)*);
+            crate::log_debug!($($arg)*);
             return;
         };
     };
@@ -157,4 +177,14 @@ mod test {
         assert_eq!(ip2hex(AllSPFRouters), 0xe0000005u32);
         assert_eq!(hex2ip(0xe0000005u32), AllSPFRouters);
     }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match("eth0", "eth0"));
+        assert!(glob_match("eth*", "eth0"));
+        assert!(glob_match("veth*", "veth"));
+        assert!(glob_match("e?h*1", "eth0.1"));
+        assert!(!glob_match("eth?", "eth10"));
+        assert!(!glob_match("docker*", "eth0"));
+    }
 }